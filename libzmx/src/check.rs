@@ -0,0 +1,518 @@
+//! Verification of the structural integrity of ZIP archives.
+
+
+use std::fmt;
+use std::io::{self, Read, Seek, SeekFrom};
+
+use crate::{Error, zip_read_data_descriptor, ZipCentralDirectoryEntry, ZipLocalFileHeader};
+use crate::end_records::read_end_records;
+use crate::extra_field::{RawExtraFieldIter, with_length_prefix};
+use crate::io_ext::ReadExt;
+use crate::zip_format::{CentralDirectoryEntry, LocalFileHeader, Zip64ExtraField};
+
+
+/// A field of a local file header or data descriptor that can disagree with the central directory.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum LocalHeaderField {
+    /// The name of the entry.
+    FileName,
+
+    /// The method with which the data of the entry was compressed.
+    CompressionMethod,
+
+    /// The CRC-32 checksum of the data of the entry.
+    Crc32,
+
+    /// The compressed size of the entry.
+    CompressedSize,
+
+    /// The uncompressed size of the entry.
+    UncompressedSize,
+}
+impl fmt::Display for LocalHeaderField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FileName => write!(f, "file name"),
+            Self::CompressionMethod => write!(f, "compression method"),
+            Self::Crc32 => write!(f, "CRC-32"),
+            Self::CompressedSize => write!(f, "compressed size"),
+            Self::UncompressedSize => write!(f, "uncompressed size"),
+        }
+    }
+}
+
+
+/// A structural problem found in a ZIP archive by [`zip_check`].
+///
+/// Offsets are counted from the beginning of the archive. Entries are identified by the offset of
+/// their central directory entry, as in [`ZipCentralDirectoryEntry::offset`].
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Finding {
+    /// The central directory ends prematurely: the central directory entry starting at the given
+    /// offset extends beyond the end of the file.
+    CentralDirectoryTruncated { offset: u64 },
+
+    /// The number of entries found in the central directory differs from the total declared in the
+    /// (Zip64) end-of-central-directory record at the given offset.
+    EntryCountMismatch { offset: u64, declared: u64, found: u64 },
+
+    /// The size of the central directory as parsed differs from the size declared in the (Zip64)
+    /// end-of-central-directory record at the given offset.
+    CentralDirectorySizeMismatch { offset: u64, declared: u64, found: u64 },
+
+    /// The Zip64 extended information extra field of an entry cannot be decoded.
+    InvalidZip64ExtraField { entry_offset: u64 },
+
+    /// An entry starts on a disk that is not part of the archive.
+    MissingDisk { entry_offset: u64 },
+
+    /// The local file header of an entry extends beyond the end of the file.
+    LocalHeaderBeyondEnd { entry_offset: u64, local_header_offset: u64 },
+
+    /// The local file header of an entry does not start with the expected signature.
+    IncorrectLocalHeaderSignature { entry_offset: u64, local_header_offset: u64, signature: u32 },
+
+    /// A field of the local file header of an entry disagrees with its central directory entry.
+    LocalHeaderMismatch { entry_offset: u64, local_header_offset: u64, field: LocalHeaderField },
+
+    /// The data descriptor of an entry extends beyond the end of the file.
+    DataDescriptorBeyondEnd { entry_offset: u64, data_descriptor_offset: u64 },
+
+    /// A field of the data descriptor of an entry disagrees with its central directory entry.
+    DataDescriptorMismatch { entry_offset: u64, data_descriptor_offset: u64, field: LocalHeaderField },
+
+    /// The data of an entry extends beyond the end of the file.
+    DataBeyondEnd { entry_offset: u64, data_offset: u64, data_end: u64 },
+
+    /// The data of an entry extends into the central directory.
+    DataOverlapsCentralDirectory { entry_offset: u64, data_offset: u64, data_end: u64 },
+}
+impl Finding {
+    /// The offset of the structure in which the problem has been found.
+    pub const fn offset(&self) -> u64 {
+        match self {
+            Self::CentralDirectoryTruncated { offset } => *offset,
+            Self::EntryCountMismatch { offset, .. } => *offset,
+            Self::CentralDirectorySizeMismatch { offset, .. } => *offset,
+            Self::InvalidZip64ExtraField { entry_offset } => *entry_offset,
+            Self::MissingDisk { entry_offset } => *entry_offset,
+            Self::LocalHeaderBeyondEnd { local_header_offset, .. } => *local_header_offset,
+            Self::IncorrectLocalHeaderSignature { local_header_offset, .. } => *local_header_offset,
+            Self::LocalHeaderMismatch { local_header_offset, .. } => *local_header_offset,
+            Self::DataDescriptorBeyondEnd { data_descriptor_offset, .. } => *data_descriptor_offset,
+            Self::DataDescriptorMismatch { data_descriptor_offset, .. } => *data_descriptor_offset,
+            Self::DataBeyondEnd { data_offset, .. } => *data_offset,
+            Self::DataOverlapsCentralDirectory { data_offset, .. } => *data_offset,
+        }
+    }
+}
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CentralDirectoryTruncated { offset }
+                => write!(f, "central directory entry at offset {} is truncated", offset),
+            Self::EntryCountMismatch { offset, declared, found }
+                => write!(f, "end of central directory at offset {} declares {} entries but {} were found", offset, declared, found),
+            Self::CentralDirectorySizeMismatch { offset, declared, found }
+                => write!(f, "end of central directory at offset {} declares a central directory of {} bytes but {} bytes were parsed", offset, declared, found),
+            Self::InvalidZip64ExtraField { entry_offset }
+                => write!(f, "entry at offset {} has an invalid Zip64 extra field", entry_offset),
+            Self::MissingDisk { entry_offset }
+                => write!(f, "entry at offset {} starts on a disk that is not part of the archive", entry_offset),
+            Self::LocalHeaderBeyondEnd { entry_offset, local_header_offset }
+                => write!(f, "local header at offset {} of entry at offset {} extends beyond the end of the file", local_header_offset, entry_offset),
+            Self::IncorrectLocalHeaderSignature { entry_offset, local_header_offset, signature }
+                => write!(f, "local header at offset {} of entry at offset {} has incorrect signature 0x{:08X}", local_header_offset, entry_offset, signature),
+            Self::LocalHeaderMismatch { entry_offset, local_header_offset, field }
+                => write!(f, "local header at offset {} disagrees with entry at offset {} on {}", local_header_offset, entry_offset, field),
+            Self::DataDescriptorBeyondEnd { entry_offset, data_descriptor_offset }
+                => write!(f, "data descriptor at offset {} of entry at offset {} extends beyond the end of the file", data_descriptor_offset, entry_offset),
+            Self::DataDescriptorMismatch { entry_offset, data_descriptor_offset, field }
+                => write!(f, "data descriptor at offset {} disagrees with entry at offset {} on {}", data_descriptor_offset, entry_offset, field),
+            Self::DataBeyondEnd { entry_offset, data_offset, data_end }
+                => write!(f, "data at offset {} of entry at offset {} extends beyond the end of the file (to offset {})", data_offset, entry_offset, data_end),
+            Self::DataOverlapsCentralDirectory { entry_offset, data_offset, data_end }
+                => write!(f, "data at offset {} of entry at offset {} extends into the central directory (to offset {})", data_offset, entry_offset, data_end),
+        }
+    }
+}
+
+
+/// Whether the error has been caused by reading beyond the end of the file.
+fn is_eof(error: &Error) -> bool {
+    matches!(error, Error::Io(e) if e.kind() == io::ErrorKind::UnexpectedEof)
+}
+
+
+/// Whether the Zip64 extended information extra field of the given entry, if it has one, can be
+/// decoded strictly, i.e. has exactly the length implied by the 32-bit fields of the entry.
+///
+/// Unlike the lenient decoding used when listing entries, a truncated field or one with trailing
+/// data is considered invalid.
+fn zip64_extra_field_is_valid(entry: &CentralDirectoryEntry) -> bool {
+    let mut position = 0;
+    for block_res in RawExtraFieldIter::new(&entry.extra_fields) {
+        let (tag, block) = match block_res {
+            Ok(tb) => tb,
+            Err(_) => {
+                // the block extending beyond the extra field area might be the Zip64 one
+                let rest = &entry.extra_fields[position..];
+                return !rest.starts_with(&Zip64ExtraField::tag().to_le_bytes());
+            },
+        };
+        if tag == Zip64ExtraField::tag() {
+            return Zip64ExtraField::read_after_tag(
+                with_length_prefix(block).as_slice(),
+                entry.uncompressed_size,
+                entry.compressed_size,
+                entry.local_header_relative_offset,
+                entry.disk_number_start,
+            ).is_ok();
+        }
+        position += 4 + block.len();
+    }
+    true
+}
+
+
+/// Checks a single entry against its local file header and the extent of the file.
+fn check_entry<F: Read + Seek>(
+    mut zip_file: F,
+    entry: &ZipCentralDirectoryEntry,
+    file_length: u64,
+    central_directory_offset: u64,
+    findings: &mut Vec<Finding>,
+) -> Result<(), Error> {
+    let entry_offset = entry.offset;
+    let local_header_offset = entry.local_header_offset;
+    if local_header_offset >= file_length {
+        findings.push(Finding::LocalHeaderBeyondEnd { entry_offset, local_header_offset });
+        return Ok(());
+    }
+
+    // read the signature separately to be able to report it
+    zip_file.seek(SeekFrom::Start(local_header_offset))?;
+    let signature = match zip_file.read_u32_le() {
+        Ok(s) => s,
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+            findings.push(Finding::LocalHeaderBeyondEnd { entry_offset, local_header_offset });
+            return Ok(());
+        },
+        Err(e) => return Err(e.into()),
+    };
+    if signature != LocalFileHeader::signature() {
+        findings.push(Finding::IncorrectLocalHeaderSignature { entry_offset, local_header_offset, signature });
+        return Ok(());
+    }
+    let header = match LocalFileHeader::read_after_signature(&mut zip_file) {
+        Ok(h) => h,
+        Err(e) if is_eof(&e) => {
+            findings.push(Finding::LocalHeaderBeyondEnd { entry_offset, local_header_offset });
+            return Ok(());
+        },
+        Err(e) => return Err(e),
+    };
+    let data_offset = local_header_offset + header.record_len();
+    let local_header = ZipLocalFileHeader {
+        header,
+        offset: local_header_offset,
+        data_offset,
+    };
+
+    let mut mismatch = |field| findings.push(Finding::LocalHeaderMismatch { entry_offset, local_header_offset, field });
+    if local_header.header.file_name != entry.entry.file_name {
+        mismatch(LocalHeaderField::FileName);
+    }
+    if local_header.header.compression_method != entry.entry.compression_method {
+        mismatch(LocalHeaderField::CompressionMethod);
+    }
+    // with a data descriptor, the local header may contain zeroes instead of the actual values
+    if !local_header.header.has_data_descriptor() {
+        let (compressed_size, uncompressed_size) = local_header.header.sizes();
+        if local_header.header.crc32 != entry.entry.crc32 {
+            mismatch(LocalHeaderField::Crc32);
+        }
+        if compressed_size != entry.compressed_size {
+            mismatch(LocalHeaderField::CompressedSize);
+        }
+        if uncompressed_size != entry.uncompressed_size {
+            mismatch(LocalHeaderField::UncompressedSize);
+        }
+    }
+
+    let data_end = data_offset.saturating_add(entry.compressed_size);
+    if data_end > file_length {
+        findings.push(Finding::DataBeyondEnd { entry_offset, data_offset, data_end });
+        return Ok(());
+    } else if data_offset < central_directory_offset && data_end > central_directory_offset {
+        findings.push(Finding::DataOverlapsCentralDirectory { entry_offset, data_offset, data_end });
+    }
+
+    if local_header.header.has_data_descriptor() {
+        let data_descriptor_offset = data_end;
+        let data_descriptor = match zip_read_data_descriptor(&mut zip_file, entry, &local_header) {
+            Ok(dd) => dd,
+            Err(e) if is_eof(&e) => {
+                findings.push(Finding::DataDescriptorBeyondEnd { entry_offset, data_descriptor_offset });
+                return Ok(());
+            },
+            Err(e) => return Err(e),
+        };
+
+        let mut mismatch = |field| findings.push(Finding::DataDescriptorMismatch { entry_offset, data_descriptor_offset, field });
+        if data_descriptor.crc32 != entry.entry.crc32 {
+            mismatch(LocalHeaderField::Crc32);
+        }
+        if data_descriptor.compressed_size != entry.compressed_size {
+            mismatch(LocalHeaderField::CompressedSize);
+        }
+        if data_descriptor.uncompressed_size != entry.uncompressed_size {
+            mismatch(LocalHeaderField::UncompressedSize);
+        }
+    }
+
+    Ok(())
+}
+
+
+/// Verifies the structural integrity of a ZIP archive.
+///
+/// The following properties are checked:
+///
+/// * the number of entries in the central directory and its size agree with the values declared in
+///   the end-of-central-directory record (or its Zip64 counterpart)
+/// * the local file header of each entry exists and has the correct signature
+/// * the local file header of each entry agrees with its central directory entry on the file name,
+///   the compression method, the CRC-32 checksum and the sizes (the latter three are taken from the
+///   data descriptor if the local header defers them to it)
+/// * the data of each entry lies within the file and does not extend into the central directory
+///
+/// Problems with the archive's structure are returned as a list of findings, which is empty if the
+/// archive passes all checks. An error is only returned if the archive cannot be read at all, e.g.
+/// because its end-of-central-directory record is missing.
+pub fn zip_check<F: Read + Seek>(zip_file: F) -> Result<Vec<Finding>, Error> {
+    zip_check_disks(zip_file, &[0])
+}
+
+
+/// Verifies the structural integrity of a ZIP archive spanning multiple disks.
+///
+/// `zip_file` is the concatenation of all disks of the archive and `disk_offsets` contains the
+/// offset of the start of each disk within it (see
+/// [`MultiVolume::disk_offsets`](crate::MultiVolume::disk_offsets)). Offsets in the findings are
+/// relative to the start of `zip_file`; otherwise, this function behaves like [`zip_check`].
+pub fn zip_check_disks<F: Read + Seek>(mut zip_file: F, disk_offsets: &[u64]) -> Result<Vec<Finding>, Error> {
+    let file_length = zip_file.seek(SeekFrom::End(0))?;
+    let end_records = read_end_records(&mut zip_file, disk_offsets)?;
+    let end_offset = end_records.start_offset();
+    let central_directory_offset = end_records.central_directory_offset();
+
+    let mut findings = Vec::new();
+
+    // read out the central directory
+    let mut entries = Vec::new();
+    let mut entry_count: u64 = 0;
+    let mut position = central_directory_offset;
+    zip_file.seek(SeekFrom::Start(position))?;
+    while position < end_offset {
+        let signature = match zip_file.read_u32_le() {
+            Ok(s) => s,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                findings.push(Finding::CentralDirectoryTruncated { offset: position });
+                break;
+            },
+            Err(e) => return Err(e.into()),
+        };
+        if signature != CentralDirectoryEntry::signature() {
+            break;
+        }
+        let entry = match CentralDirectoryEntry::read_after_signature(&mut zip_file) {
+            Ok(e) => e,
+            Err(e) if is_eof(&e) => {
+                findings.push(Finding::CentralDirectoryTruncated { offset: position });
+                break;
+            },
+            Err(e) => return Err(e),
+        };
+        entry_count += 1;
+        if !zip64_extra_field_is_valid(&entry) {
+            // the values of the entry cannot be trusted, so its local header is not checked
+            findings.push(Finding::InvalidZip64ExtraField { entry_offset: position });
+        } else {
+            match ZipCentralDirectoryEntry::resolve(entry, disk_offsets, position, end_records.preamble_length) {
+                Ok(e) => entries.push(e),
+                Err(Error::SpannedArchive) => findings.push(Finding::MissingDisk { entry_offset: position }),
+                Err(e) => return Err(e),
+            }
+        }
+        position = zip_file.stream_position()?;
+    }
+
+    // compare the central directory against the end records
+    if entry_count != end_records.total_entries() {
+        findings.push(Finding::EntryCountMismatch {
+            offset: end_offset,
+            declared: end_records.total_entries(),
+            found: entry_count,
+        });
+    }
+    let found_size = position.saturating_sub(central_directory_offset);
+    if found_size != end_records.central_directory_size() {
+        findings.push(Finding::CentralDirectorySizeMismatch {
+            offset: end_offset,
+            declared: end_records.central_directory_size(),
+            found: found_size,
+        });
+    }
+
+    // check each entry
+    for entry in &entries {
+        check_entry(&mut zip_file, entry, file_length, central_directory_offset, &mut findings)?;
+    }
+
+    Ok(findings)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    use flate2::Crc;
+
+    use crate::zip_format::EndOfCentralDirectory;
+
+    /// Returns a local file header and a central directory entry for a stored file with the given
+    /// name and data.
+    fn stored_entry(name: &[u8], data: &[u8]) -> (LocalFileHeader, CentralDirectoryEntry) {
+        let mut crc = Crc::new();
+        crc.update(data);
+        let size = u32::try_from(data.len()).unwrap();
+        let local_header = LocalFileHeader {
+            required_version: 20,
+            crc32: crc.sum(),
+            compressed_size: size,
+            uncompressed_size: size,
+            file_name: name.to_vec(),
+            ..Default::default()
+        };
+        let central_entry = CentralDirectoryEntry {
+            creator_version: 20,
+            required_version: 20,
+            crc32: crc.sum(),
+            compressed_size: size,
+            uncompressed_size: size,
+            file_name: name.to_vec(),
+            ..Default::default()
+        };
+        (local_header, central_entry)
+    }
+
+    /// Writes an archive consisting of the given entries, filling in their local header offsets.
+    fn write_archive(entries: Vec<(LocalFileHeader, CentralDirectoryEntry, &[u8])>) -> Cursor<Vec<u8>> {
+        let mut buffer = Vec::new();
+        let mut central_entries = Vec::with_capacity(entries.len());
+        for (local_header, mut central_entry, data) in entries {
+            central_entry.local_header_relative_offset = buffer.len().try_into().unwrap();
+            local_header.write(&mut buffer).unwrap();
+            buffer.extend_from_slice(data);
+            central_entries.push(central_entry);
+        }
+        let central_directory_offset = buffer.len();
+        for central_entry in &central_entries {
+            central_entry.write(&mut buffer).unwrap();
+        }
+        let entry_count: u16 = central_entries.len().try_into().unwrap();
+        let eocd = EndOfCentralDirectory {
+            total_central_dir_entries_this_disk: entry_count,
+            total_central_dir_entries: entry_count,
+            central_directory_size: (buffer.len() - central_directory_offset).try_into().unwrap(),
+            central_dir_offset_on_disk: central_directory_offset.try_into().unwrap(),
+            ..Default::default()
+        };
+        eocd.write(&mut buffer).unwrap();
+        Cursor::new(buffer)
+    }
+
+    #[test]
+    fn test_invalid_zip64_extra_field() {
+        let (local_header, mut central_entry) = stored_entry(b"big.txt", b"data");
+        central_entry.compressed_size = u32::MAX;
+        central_entry.uncompressed_size = u32::MAX;
+
+        // both sizes are stored in the extra field
+        let valid = Zip64ExtraField {
+            uncompressed_size: Some(4),
+            compressed_size: Some(4),
+            ..Default::default()
+        };
+        central_entry.extra_fields.clear();
+        valid.write(&mut central_entry.extra_fields).unwrap();
+        let mut zip_file = write_archive(vec![(local_header.clone(), central_entry.clone(), b"data")]);
+        assert_eq!(zip_check(&mut zip_file).unwrap(), Vec::new());
+
+        // the field is missing the compressed size
+        let truncated = Zip64ExtraField {
+            uncompressed_size: Some(4),
+            ..Default::default()
+        };
+        central_entry.extra_fields.clear();
+        truncated.write(&mut central_entry.extra_fields).unwrap();
+        let mut zip_file = write_archive(vec![(local_header.clone(), central_entry.clone(), b"data")]);
+        let entry_offset = 30 + 7 + 4;
+        assert_eq!(zip_check(&mut zip_file).unwrap(), [Finding::InvalidZip64ExtraField { entry_offset }]);
+
+        // the field declares more data than the extra field area contains
+        central_entry.extra_fields = vec![0x01, 0x00, 0x10, 0x00];
+        central_entry.extra_fields.extend_from_slice(&4u64.to_le_bytes());
+        let mut zip_file = write_archive(vec![(local_header, central_entry, b"data")]);
+        assert_eq!(zip_check(&mut zip_file).unwrap(), [Finding::InvalidZip64ExtraField { entry_offset }]);
+    }
+
+    #[test]
+    fn test_clean_archive() {
+        let (first_header, first_entry) = stored_entry(b"first.txt", b"first");
+        let (second_header, second_entry) = stored_entry(b"second.txt", b"second");
+        let mut zip_file = write_archive(vec![
+            (first_header, first_entry, b"first"),
+            (second_header, second_entry, b"second"),
+        ]);
+        assert_eq!(zip_check(&mut zip_file).unwrap(), Vec::new());
+    }
+
+    #[test]
+    fn test_local_header_mismatch() {
+        let (mut local_header, central_entry) = stored_entry(b"name.txt", b"data");
+        local_header.file_name = b"other.txt".to_vec();
+        local_header.crc32 ^= 1;
+        let local_header_length = local_header.record_len();
+        let mut zip_file = write_archive(vec![(local_header, central_entry, b"data")]);
+
+        let entry_offset = local_header_length + 4;
+        let local_header_offset = 0;
+        assert_eq!(zip_check(&mut zip_file).unwrap(), [
+            Finding::LocalHeaderMismatch { entry_offset, local_header_offset, field: LocalHeaderField::FileName },
+            Finding::LocalHeaderMismatch { entry_offset, local_header_offset, field: LocalHeaderField::Crc32 },
+        ]);
+    }
+
+    #[test]
+    fn test_entry_count_mismatch() {
+        let (first_header, first_entry) = stored_entry(b"first.txt", b"first");
+        let (second_header, second_entry) = stored_entry(b"second.txt", b"second");
+        let mut zip_file = write_archive(vec![
+            (first_header, first_entry, b"first"),
+            (second_header, second_entry, b"second"),
+        ]);
+        let eocd_offset = zip_file.get_ref().len() - usize::try_from(EndOfCentralDirectory::min_len()).unwrap();
+
+        // declare three entries instead of two; the entries themselves are still checked
+        for count_offset in [8, 10] {
+            zip_file.get_mut()[eocd_offset + count_offset..][..2].copy_from_slice(&3u16.to_le_bytes());
+        }
+        assert_eq!(zip_check(&mut zip_file).unwrap(), [
+            Finding::EntryCountMismatch { offset: eocd_offset as u64, declared: 3, found: 2 },
+        ]);
+    }
+}
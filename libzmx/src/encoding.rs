@@ -0,0 +1,484 @@
+//! Decoding and encoding of file names and comments stored in ZIP archives.
+//!
+//! The ZIP specification states that names and comments are encoded in IBM code page 437 unless
+//! bit 11 of the general-purpose bit flag is set, in which case they are encoded in UTF-8.
+//! Additionally, the Info-ZIP Unicode path and comment extra fields may provide UTF-8 versions of
+//! names and comments that are stored in a legacy encoding.
+
+
+use std::fmt;
+use std::str::FromStr;
+
+use flate2::Crc;
+
+use crate::Error;
+use crate::extra_field::{ExtraField, RawExtraFieldIter, UnicodePathExtraField};
+use crate::zip_format::CentralDirectoryEntry;
+
+
+/// The bit of the general-purpose bit flag which specifies that the name and comment of an entry
+/// are encoded in UTF-8.
+pub const LANGUAGE_ENCODING_FLAG: u16 = 1 << 11;
+
+
+/// A legacy (DOS) code page in which names and comments of ZIP entries can be encoded.
+///
+/// The lower half (0x00 to 0x7F) of all these code pages is decoded as ASCII.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum CodePage {
+    /// IBM code page 437 (DOS Latin US), the default encoding of the ZIP format.
+    #[default]
+    Cp437,
+
+    /// IBM code page 850 (DOS Latin 1).
+    Cp850,
+
+    /// IBM code page 852 (DOS Latin 2).
+    Cp852,
+
+    /// IBM code page 865 (DOS Nordic).
+    Cp865,
+
+    /// IBM code page 866 (DOS Cyrillic Russian).
+    Cp866,
+}
+impl CodePage {
+    /// The characters encoded by the bytes 0x80 to 0xFF in this code page.
+    const fn high_half(&self) -> &'static [char; 128] {
+        match self {
+            Self::Cp437 => &CP437_HIGH,
+            Self::Cp850 => &CP850_HIGH,
+            Self::Cp852 => &CP852_HIGH,
+            Self::Cp865 => &CP865_HIGH,
+            Self::Cp866 => &CP866_HIGH,
+        }
+    }
+
+    /// The number of this code page.
+    pub const fn number(&self) -> u16 {
+        match self {
+            Self::Cp437 => 437,
+            Self::Cp850 => 850,
+            Self::Cp852 => 852,
+            Self::Cp865 => 865,
+            Self::Cp866 => 866,
+        }
+    }
+
+    /// Decodes the given bytes using this code page.
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        let high_half = self.high_half();
+        bytes.iter()
+            .map(|b| if *b < 0x80 {
+                char::from(*b)
+            } else {
+                high_half[usize::from(*b - 0x80)]
+            })
+            .collect()
+    }
+}
+impl fmt::Display for CodePage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CP{}", self.number())
+    }
+}
+impl FromStr for CodePage {
+    type Err = UnknownCodePageError;
+
+    /// Parses a code page specification such as `437`, `cp850` or `IBM866`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_ascii_lowercase();
+        let number_str = lower.strip_prefix("cp")
+            .or_else(|| lower.strip_prefix("ibm"))
+            .unwrap_or(&lower);
+        match number_str {
+            "437" => Ok(Self::Cp437),
+            "850" => Ok(Self::Cp850),
+            "852" => Ok(Self::Cp852),
+            "865" => Ok(Self::Cp865),
+            "866" => Ok(Self::Cp866),
+            _ => Err(UnknownCodePageError(s.to_owned())),
+        }
+    }
+}
+
+
+/// An error returned when parsing an unknown code page specification.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct UnknownCodePageError(pub String);
+impl fmt::Display for UnknownCodePageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown code page {:?}", self.0)
+    }
+}
+impl std::error::Error for UnknownCodePageError {
+}
+
+
+/// Calculates the CRC-32 checksum of the given data, as stored in the Info-ZIP Unicode extra
+/// fields.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc::new();
+    crc.update(data);
+    crc.sum()
+}
+
+
+/// Decodes a name or comment of an entry.
+///
+/// `unicode_version` is the version of the name or comment from the relevant Info-ZIP Unicode
+/// extra field (if any) along with the CRC-32 checksum of the legacy version stored therein.
+fn decode_name_or_comment(
+    raw: &[u8],
+    general_purpose_bit_flag: u16,
+    unicode_version: Option<(u32, &[u8])>,
+    code_page_override: Option<CodePage>,
+) -> String {
+    if general_purpose_bit_flag & LANGUAGE_ENCODING_FLAG != 0 {
+        if let Ok(s) = std::str::from_utf8(raw) {
+            return s.to_owned();
+        }
+    }
+
+    if let Some((legacy_crc32, unicode_bytes)) = unicode_version {
+        // only trust the extra field if it matches the name or comment in the header
+        if legacy_crc32 == crc32(raw) {
+            if let Ok(s) = std::str::from_utf8(unicode_bytes) {
+                return s.to_owned();
+            }
+        }
+    }
+
+    code_page_override
+        .unwrap_or_default()
+        .decode(raw)
+}
+
+
+/// Decodes the file name of the given central directory entry.
+///
+/// The name is decoded as UTF-8 if the language encoding flag (bit 11 of the general-purpose bit
+/// flag) is set. Otherwise, the name is taken from an Info-ZIP Unicode path extra field whose
+/// checksum matches the name in the header. If there is no such extra field, the name is decoded
+/// using the given code page or, if none is given, code page 437.
+pub fn decode_entry_name(entry: &CentralDirectoryEntry, code_page_override: Option<CodePage>) -> String {
+    let unicode_path = entry.extra_field_iter()
+        .filter_map(|f| match f {
+            Ok(ExtraField::UnicodePath(up)) if up.version == 1 => Some(up),
+            _ => None,
+        })
+        .next();
+    decode_name_or_comment(
+        &entry.file_name,
+        entry.general_purpose_bit_flag,
+        unicode_path.as_ref().map(|up| (up.name_crc32, up.unicode_name.as_slice())),
+        code_page_override,
+    )
+}
+
+
+/// Decodes the file comment of the given central directory entry.
+///
+/// The same rules as for [`decode_entry_name`] apply, except that the Info-ZIP Unicode comment
+/// extra field is consulted instead of the Unicode path extra field.
+pub fn decode_entry_comment(entry: &CentralDirectoryEntry, code_page_override: Option<CodePage>) -> String {
+    let unicode_comment = entry.extra_field_iter()
+        .filter_map(|f| match f {
+            Ok(ExtraField::UnicodeComment(uc)) if uc.version == 1 => Some(uc),
+            _ => None,
+        })
+        .next();
+    decode_name_or_comment(
+        &entry.file_comment,
+        entry.general_purpose_bit_flag,
+        unicode_comment.as_ref().map(|uc| (uc.comment_crc32, uc.unicode_comment.as_slice())),
+        code_page_override,
+    )
+}
+
+
+const CP437_HIGH: [char; 128] = [
+    '\u{00C7}', '\u{00FC}', '\u{00E9}', '\u{00E2}', '\u{00E4}', '\u{00E0}', '\u{00E5}', '\u{00E7}',
+    '\u{00EA}', '\u{00EB}', '\u{00E8}', '\u{00EF}', '\u{00EE}', '\u{00EC}', '\u{00C4}', '\u{00C5}',
+    '\u{00C9}', '\u{00E6}', '\u{00C6}', '\u{00F4}', '\u{00F6}', '\u{00F2}', '\u{00FB}', '\u{00F9}',
+    '\u{00FF}', '\u{00D6}', '\u{00DC}', '\u{00A2}', '\u{00A3}', '\u{00A5}', '\u{20A7}', '\u{0192}',
+    '\u{00E1}', '\u{00ED}', '\u{00F3}', '\u{00FA}', '\u{00F1}', '\u{00D1}', '\u{00AA}', '\u{00BA}',
+    '\u{00BF}', '\u{2310}', '\u{00AC}', '\u{00BD}', '\u{00BC}', '\u{00A1}', '\u{00AB}', '\u{00BB}',
+    '\u{2591}', '\u{2592}', '\u{2593}', '\u{2502}', '\u{2524}', '\u{2561}', '\u{2562}', '\u{2556}',
+    '\u{2555}', '\u{2563}', '\u{2551}', '\u{2557}', '\u{255D}', '\u{255C}', '\u{255B}', '\u{2510}',
+    '\u{2514}', '\u{2534}', '\u{252C}', '\u{251C}', '\u{2500}', '\u{253C}', '\u{255E}', '\u{255F}',
+    '\u{255A}', '\u{2554}', '\u{2569}', '\u{2566}', '\u{2560}', '\u{2550}', '\u{256C}', '\u{2567}',
+    '\u{2568}', '\u{2564}', '\u{2565}', '\u{2559}', '\u{2558}', '\u{2552}', '\u{2553}', '\u{256B}',
+    '\u{256A}', '\u{2518}', '\u{250C}', '\u{2588}', '\u{2584}', '\u{258C}', '\u{2590}', '\u{2580}',
+    '\u{03B1}', '\u{00DF}', '\u{0393}', '\u{03C0}', '\u{03A3}', '\u{03C3}', '\u{00B5}', '\u{03C4}',
+    '\u{03A6}', '\u{0398}', '\u{03A9}', '\u{03B4}', '\u{221E}', '\u{03C6}', '\u{03B5}', '\u{2229}',
+    '\u{2261}', '\u{00B1}', '\u{2265}', '\u{2264}', '\u{2320}', '\u{2321}', '\u{00F7}', '\u{2248}',
+    '\u{00B0}', '\u{2219}', '\u{00B7}', '\u{221A}', '\u{207F}', '\u{00B2}', '\u{25A0}', '\u{00A0}',
+];
+
+const CP850_HIGH: [char; 128] = [
+    '\u{00C7}', '\u{00FC}', '\u{00E9}', '\u{00E2}', '\u{00E4}', '\u{00E0}', '\u{00E5}', '\u{00E7}',
+    '\u{00EA}', '\u{00EB}', '\u{00E8}', '\u{00EF}', '\u{00EE}', '\u{00EC}', '\u{00C4}', '\u{00C5}',
+    '\u{00C9}', '\u{00E6}', '\u{00C6}', '\u{00F4}', '\u{00F6}', '\u{00F2}', '\u{00FB}', '\u{00F9}',
+    '\u{00FF}', '\u{00D6}', '\u{00DC}', '\u{00F8}', '\u{00A3}', '\u{00D8}', '\u{00D7}', '\u{0192}',
+    '\u{00E1}', '\u{00ED}', '\u{00F3}', '\u{00FA}', '\u{00F1}', '\u{00D1}', '\u{00AA}', '\u{00BA}',
+    '\u{00BF}', '\u{00AE}', '\u{00AC}', '\u{00BD}', '\u{00BC}', '\u{00A1}', '\u{00AB}', '\u{00BB}',
+    '\u{2591}', '\u{2592}', '\u{2593}', '\u{2502}', '\u{2524}', '\u{00C1}', '\u{00C2}', '\u{00C0}',
+    '\u{00A9}', '\u{2563}', '\u{2551}', '\u{2557}', '\u{255D}', '\u{00A2}', '\u{00A5}', '\u{2510}',
+    '\u{2514}', '\u{2534}', '\u{252C}', '\u{251C}', '\u{2500}', '\u{253C}', '\u{00E3}', '\u{00C3}',
+    '\u{255A}', '\u{2554}', '\u{2569}', '\u{2566}', '\u{2560}', '\u{2550}', '\u{256C}', '\u{00A4}',
+    '\u{00F0}', '\u{00D0}', '\u{00CA}', '\u{00CB}', '\u{00C8}', '\u{0131}', '\u{00CD}', '\u{00CE}',
+    '\u{00CF}', '\u{2518}', '\u{250C}', '\u{2588}', '\u{2584}', '\u{00A6}', '\u{00CC}', '\u{2580}',
+    '\u{00D3}', '\u{00DF}', '\u{00D4}', '\u{00D2}', '\u{00F5}', '\u{00D5}', '\u{00B5}', '\u{00FE}',
+    '\u{00DE}', '\u{00DA}', '\u{00DB}', '\u{00D9}', '\u{00FD}', '\u{00DD}', '\u{00AF}', '\u{00B4}',
+    '\u{00AD}', '\u{00B1}', '\u{2017}', '\u{00BE}', '\u{00B6}', '\u{00A7}', '\u{00F7}', '\u{00B8}',
+    '\u{00B0}', '\u{00A8}', '\u{00B7}', '\u{00B9}', '\u{00B3}', '\u{00B2}', '\u{25A0}', '\u{00A0}',
+];
+
+const CP852_HIGH: [char; 128] = [
+    '\u{00C7}', '\u{00FC}', '\u{00E9}', '\u{00E2}', '\u{00E4}', '\u{016F}', '\u{0107}', '\u{00E7}',
+    '\u{0142}', '\u{00EB}', '\u{0150}', '\u{0151}', '\u{00EE}', '\u{0179}', '\u{00C4}', '\u{0106}',
+    '\u{00C9}', '\u{0139}', '\u{013A}', '\u{00F4}', '\u{00F6}', '\u{013D}', '\u{013E}', '\u{015A}',
+    '\u{015B}', '\u{00D6}', '\u{00DC}', '\u{0164}', '\u{0165}', '\u{0141}', '\u{00D7}', '\u{010D}',
+    '\u{00E1}', '\u{00ED}', '\u{00F3}', '\u{00FA}', '\u{0104}', '\u{0105}', '\u{017D}', '\u{017E}',
+    '\u{0118}', '\u{0119}', '\u{00AC}', '\u{017A}', '\u{010C}', '\u{015F}', '\u{00AB}', '\u{00BB}',
+    '\u{2591}', '\u{2592}', '\u{2593}', '\u{2502}', '\u{2524}', '\u{00C1}', '\u{00C2}', '\u{011A}',
+    '\u{015E}', '\u{2563}', '\u{2551}', '\u{2557}', '\u{255D}', '\u{017B}', '\u{017C}', '\u{2510}',
+    '\u{2514}', '\u{2534}', '\u{252C}', '\u{251C}', '\u{2500}', '\u{253C}', '\u{0102}', '\u{0103}',
+    '\u{255A}', '\u{2554}', '\u{2569}', '\u{2566}', '\u{2560}', '\u{2550}', '\u{256C}', '\u{00A4}',
+    '\u{0111}', '\u{0110}', '\u{010E}', '\u{00CB}', '\u{010F}', '\u{0147}', '\u{00CD}', '\u{00CE}',
+    '\u{011B}', '\u{2518}', '\u{250C}', '\u{2588}', '\u{2584}', '\u{0162}', '\u{016E}', '\u{2580}',
+    '\u{00D3}', '\u{00DF}', '\u{00D4}', '\u{0143}', '\u{0144}', '\u{0148}', '\u{0160}', '\u{0161}',
+    '\u{0154}', '\u{00DA}', '\u{0155}', '\u{0170}', '\u{00FD}', '\u{00DD}', '\u{0163}', '\u{00B4}',
+    '\u{00AD}', '\u{02DD}', '\u{02DB}', '\u{02C7}', '\u{02D8}', '\u{00A7}', '\u{00F7}', '\u{00B8}',
+    '\u{00B0}', '\u{00A8}', '\u{02D9}', '\u{0171}', '\u{0158}', '\u{0159}', '\u{25A0}', '\u{00A0}',
+];
+
+const CP865_HIGH: [char; 128] = [
+    '\u{00C7}', '\u{00FC}', '\u{00E9}', '\u{00E2}', '\u{00E4}', '\u{00E0}', '\u{00E5}', '\u{00E7}',
+    '\u{00EA}', '\u{00EB}', '\u{00E8}', '\u{00EF}', '\u{00EE}', '\u{00EC}', '\u{00C4}', '\u{00C5}',
+    '\u{00C9}', '\u{00E6}', '\u{00C6}', '\u{00F4}', '\u{00F6}', '\u{00F2}', '\u{00FB}', '\u{00F9}',
+    '\u{00FF}', '\u{00D6}', '\u{00DC}', '\u{00F8}', '\u{00A3}', '\u{00D8}', '\u{20A7}', '\u{0192}',
+    '\u{00E1}', '\u{00ED}', '\u{00F3}', '\u{00FA}', '\u{00F1}', '\u{00D1}', '\u{00AA}', '\u{00BA}',
+    '\u{00BF}', '\u{2310}', '\u{00AC}', '\u{00BD}', '\u{00BC}', '\u{00A1}', '\u{00AB}', '\u{00A4}',
+    '\u{2591}', '\u{2592}', '\u{2593}', '\u{2502}', '\u{2524}', '\u{2561}', '\u{2562}', '\u{2556}',
+    '\u{2555}', '\u{2563}', '\u{2551}', '\u{2557}', '\u{255D}', '\u{255C}', '\u{255B}', '\u{2510}',
+    '\u{2514}', '\u{2534}', '\u{252C}', '\u{251C}', '\u{2500}', '\u{253C}', '\u{255E}', '\u{255F}',
+    '\u{255A}', '\u{2554}', '\u{2569}', '\u{2566}', '\u{2560}', '\u{2550}', '\u{256C}', '\u{2567}',
+    '\u{2568}', '\u{2564}', '\u{2565}', '\u{2559}', '\u{2558}', '\u{2552}', '\u{2553}', '\u{256B}',
+    '\u{256A}', '\u{2518}', '\u{250C}', '\u{2588}', '\u{2584}', '\u{258C}', '\u{2590}', '\u{2580}',
+    '\u{03B1}', '\u{00DF}', '\u{0393}', '\u{03C0}', '\u{03A3}', '\u{03C3}', '\u{00B5}', '\u{03C4}',
+    '\u{03A6}', '\u{0398}', '\u{03A9}', '\u{03B4}', '\u{221E}', '\u{03C6}', '\u{03B5}', '\u{2229}',
+    '\u{2261}', '\u{00B1}', '\u{2265}', '\u{2264}', '\u{2320}', '\u{2321}', '\u{00F7}', '\u{2248}',
+    '\u{00B0}', '\u{2219}', '\u{00B7}', '\u{221A}', '\u{207F}', '\u{00B2}', '\u{25A0}', '\u{00A0}',
+];
+
+const CP866_HIGH: [char; 128] = [
+    '\u{0410}', '\u{0411}', '\u{0412}', '\u{0413}', '\u{0414}', '\u{0415}', '\u{0416}', '\u{0417}',
+    '\u{0418}', '\u{0419}', '\u{041A}', '\u{041B}', '\u{041C}', '\u{041D}', '\u{041E}', '\u{041F}',
+    '\u{0420}', '\u{0421}', '\u{0422}', '\u{0423}', '\u{0424}', '\u{0425}', '\u{0426}', '\u{0427}',
+    '\u{0428}', '\u{0429}', '\u{042A}', '\u{042B}', '\u{042C}', '\u{042D}', '\u{042E}', '\u{042F}',
+    '\u{0430}', '\u{0431}', '\u{0432}', '\u{0433}', '\u{0434}', '\u{0435}', '\u{0436}', '\u{0437}',
+    '\u{0438}', '\u{0439}', '\u{043A}', '\u{043B}', '\u{043C}', '\u{043D}', '\u{043E}', '\u{043F}',
+    '\u{2591}', '\u{2592}', '\u{2593}', '\u{2502}', '\u{2524}', '\u{2561}', '\u{2562}', '\u{2556}',
+    '\u{2555}', '\u{2563}', '\u{2551}', '\u{2557}', '\u{255D}', '\u{255C}', '\u{255B}', '\u{2510}',
+    '\u{2514}', '\u{2534}', '\u{252C}', '\u{251C}', '\u{2500}', '\u{253C}', '\u{255E}', '\u{255F}',
+    '\u{255A}', '\u{2554}', '\u{2569}', '\u{2566}', '\u{2560}', '\u{2550}', '\u{256C}', '\u{2567}',
+    '\u{2568}', '\u{2564}', '\u{2565}', '\u{2559}', '\u{2558}', '\u{2552}', '\u{2553}', '\u{256B}',
+    '\u{256A}', '\u{2518}', '\u{250C}', '\u{2588}', '\u{2584}', '\u{258C}', '\u{2590}', '\u{2580}',
+    '\u{0440}', '\u{0441}', '\u{0442}', '\u{0443}', '\u{0444}', '\u{0445}', '\u{0446}', '\u{0447}',
+    '\u{0448}', '\u{0449}', '\u{044A}', '\u{044B}', '\u{044C}', '\u{044D}', '\u{044E}', '\u{044F}',
+    '\u{0401}', '\u{0451}', '\u{0404}', '\u{0454}', '\u{0407}', '\u{0457}', '\u{040E}', '\u{045E}',
+    '\u{00B0}', '\u{2219}', '\u{00B7}', '\u{221A}', '\u{2116}', '\u{00A4}', '\u{25A0}', '\u{00A0}',
+];
+
+
+/// The name of an entry, encoded along with the header fields that depend on it.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) struct EncodedName {
+    /// The name as stored in the header.
+    pub file_name: Vec<u8>,
+
+    /// The updated general-purpose bit flag of the header.
+    pub general_purpose_bit_flag: u16,
+
+    /// The updated extra fields of the header.
+    pub extra_fields: Vec<u8>,
+}
+
+
+/// Encodes a new name for a header with the given general-purpose bit flag and extra fields.
+///
+/// The name is stored in UTF-8. If it contains characters beyond ASCII, the language encoding flag
+/// is set and, for the benefit of tools that do not know this flag, an Info-ZIP Unicode path extra
+/// field containing the name is stored as well (replacing an existing one in place). Otherwise, the
+/// name reads the same in every supported encoding and Unicode path extra fields are removed. Other
+/// extra fields are retained in their original order.
+pub(crate) fn encode_entry_name(new_name: &str, general_purpose_bit_flag: u16, extra_fields: &[u8]) -> Result<EncodedName, Error> {
+    let file_name = new_name.as_bytes().to_vec();
+    if file_name.len() > 0xFFFF {
+        return Err(Error::FieldTooLong);
+    }
+
+    let unicode_path = if new_name.is_ascii() {
+        None
+    } else {
+        Some(UnicodePathExtraField {
+            version: 1,
+            name_crc32: crc32(&file_name),
+            unicode_name: file_name.clone(),
+        })
+    };
+
+    let mut new_extra_fields = Vec::with_capacity(extra_fields.len());
+    let mut unicode_path_written = false;
+    for block_res in RawExtraFieldIter::new(extra_fields) {
+        let (tag, block) = block_res?;
+        if tag == UnicodePathExtraField::tag() {
+            if let Some(up) = &unicode_path {
+                if !unicode_path_written {
+                    up.write(&mut new_extra_fields)?;
+                    unicode_path_written = true;
+                }
+            }
+            continue;
+        }
+        let length: u16 = block.len().try_into().unwrap();
+        new_extra_fields.extend_from_slice(&tag.to_le_bytes());
+        new_extra_fields.extend_from_slice(&length.to_le_bytes());
+        new_extra_fields.extend_from_slice(block);
+    }
+    if let Some(up) = &unicode_path {
+        if !unicode_path_written {
+            up.write(&mut new_extra_fields)?;
+        }
+    }
+    if new_extra_fields.len() > 0xFFFF {
+        return Err(Error::FieldTooLong);
+    }
+
+    let general_purpose_bit_flag = if unicode_path.is_some() {
+        general_purpose_bit_flag | LANGUAGE_ENCODING_FLAG
+    } else {
+        general_purpose_bit_flag
+    };
+
+    Ok(EncodedName {
+        file_name,
+        general_purpose_bit_flag,
+        extra_fields: new_extra_fields,
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry_with_name(file_name: &[u8], general_purpose_bit_flag: u16, unicode_path: Option<UnicodePathExtraField>) -> CentralDirectoryEntry {
+        let mut extra_fields = Vec::new();
+        if let Some(up) = unicode_path {
+            up.write(&mut extra_fields).unwrap();
+        }
+        CentralDirectoryEntry {
+            general_purpose_bit_flag,
+            file_name: file_name.to_vec(),
+            extra_fields,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0x00000000);
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+    }
+
+    #[test]
+    fn test_code_page_tables() {
+        let cases = [
+            (CodePage::Cp437, 0x80, '\u{00C7}'), // Ç
+            (CodePage::Cp437, 0x81, '\u{00FC}'), // ü
+            (CodePage::Cp437, 0xE1, '\u{00DF}'), // ß
+            (CodePage::Cp437, 0xFF, '\u{00A0}'), // no-break space
+            (CodePage::Cp850, 0x9B, '\u{00F8}'), // ø
+            (CodePage::Cp850, 0xD5, '\u{0131}'), // dotless i
+            (CodePage::Cp850, 0xEE, '\u{00AF}'), // macron
+            (CodePage::Cp852, 0x85, '\u{016F}'), // ů
+            (CodePage::Cp852, 0xA5, '\u{0105}'), // ą
+            (CodePage::Cp852, 0xFD, '\u{0159}'), // ř
+            (CodePage::Cp865, 0x9B, '\u{00F8}'), // ø
+            (CodePage::Cp865, 0x9D, '\u{00D8}'), // Ø
+            (CodePage::Cp865, 0xAF, '\u{00A4}'), // currency sign
+            (CodePage::Cp866, 0x80, '\u{0410}'), // А
+            (CodePage::Cp866, 0xEF, '\u{044F}'), // я
+            (CodePage::Cp866, 0xF0, '\u{0401}'), // Ё
+            (CodePage::Cp866, 0xFC, '\u{2116}'), // №
+        ];
+        for (code_page, byte, expected) in cases {
+            assert_eq!(code_page.decode(&[byte]), expected.to_string(), "{} 0x{:02X}", code_page, byte);
+        }
+
+        // the lower half is ASCII in all code pages
+        for code_page in [CodePage::Cp437, CodePage::Cp850, CodePage::Cp852, CodePage::Cp865, CodePage::Cp866] {
+            assert_eq!(code_page.decode(b"dir/File-1.txt"), "dir/File-1.txt");
+        }
+    }
+
+    #[test]
+    fn test_code_page_from_str() {
+        assert_eq!("437".parse::<CodePage>().unwrap(), CodePage::Cp437);
+        assert_eq!("cp850".parse::<CodePage>().unwrap(), CodePage::Cp850);
+        assert_eq!("IBM866".parse::<CodePage>().unwrap(), CodePage::Cp866);
+        assert!("1252".parse::<CodePage>().is_err());
+    }
+
+    #[test]
+    fn test_decode_entry_name_utf8_flag() {
+        // the flag takes precedence over everything else
+        let unicode_path = UnicodePathExtraField {
+            version: 1,
+            name_crc32: crc32("größe".as_bytes()),
+            unicode_name: b"other".to_vec(),
+        };
+        let entry = entry_with_name("größe".as_bytes(), LANGUAGE_ENCODING_FLAG, Some(unicode_path));
+        assert_eq!(decode_entry_name(&entry, Some(CodePage::Cp866)), "größe");
+
+        // invalid UTF-8 despite the flag falls through to the code page
+        let entry = entry_with_name(b"gr\x94\xE1e", LANGUAGE_ENCODING_FLAG, None);
+        assert_eq!(decode_entry_name(&entry, None), "größe");
+    }
+
+    #[test]
+    fn test_decode_entry_name_unicode_path() {
+        let legacy_name = b"gr\x94\xE1e";
+        let unicode_path = UnicodePathExtraField {
+            version: 1,
+            name_crc32: crc32(legacy_name),
+            unicode_name: "größe".as_bytes().to_vec(),
+        };
+        let entry = entry_with_name(legacy_name, 0, Some(unicode_path.clone()));
+        assert_eq!(decode_entry_name(&entry, Some(CodePage::Cp866)), "größe");
+
+        // a mismatching checksum means the name has been changed by software ignoring the field
+        let stale_path = UnicodePathExtraField {
+            name_crc32: crc32(b"old"),
+            ..unicode_path
+        };
+        let entry = entry_with_name(legacy_name, 0, Some(stale_path));
+        assert_eq!(decode_entry_name(&entry, None), "größe");
+        assert_eq!(decode_entry_name(&entry, Some(CodePage::Cp866)), "grФсe");
+    }
+
+    #[test]
+    fn test_decode_entry_name_code_page() {
+        let entry = entry_with_name(b"\x8F\xE0\xA8\xA2\xA5\xE2", 0, None);
+        assert_eq!(decode_entry_name(&entry, Some(CodePage::Cp866)), "Привет");
+    }
+
+    #[test]
+    fn test_decode_entry_name_cp437_default() {
+        let entry = entry_with_name(b"\x8F\xE0\xA8\xA2\xA5\xE2", 0, None);
+        assert_eq!(decode_entry_name(&entry, None), "Åα¿óÑΓ");
+    }
+}
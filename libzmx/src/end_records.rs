@@ -0,0 +1,547 @@
+//! Locating and reading the records at the end of a ZIP archive.
+
+
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+
+use crate::Error;
+use crate::io_ext::ReadExt;
+use crate::multi_volume::disk_offset;
+use crate::zip_format::{
+    CentralDirectoryEntry, EndOfCentralDirectory, Zip64EndOfCentralDirectory,
+    Zip64EndOfCentralDirectoryLocator,
+};
+
+
+/// The records at the end of a ZIP archive, which describe the location and extent of its central
+/// directory.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub(crate) struct EndRecords {
+    /// The offset of the end-of-central-directory record.
+    pub eocd_offset: u64,
+
+    /// The end-of-central-directory record.
+    pub eocd: EndOfCentralDirectory,
+
+    /// The offset of the Zip64 end-of-central-directory record, if one has been found.
+    pub zip64_eocd_offset: Option<u64>,
+
+    /// The Zip64 end-of-central-directory record, if one has been found.
+    pub zip64_eocd: Option<Zip64EndOfCentralDirectory>,
+
+    /// The offset of the start of the disk on which the central directory starts.
+    pub central_directory_disk_offset: u64,
+
+    /// The number of bytes preceding the archive that are not accounted for by the offsets stored
+    /// within it.
+    ///
+    /// This is the case if data (e.g. a self-extractor stub) has been prepended to the archive
+    /// without adjusting its offsets.
+    pub preamble_length: u64,
+}
+impl EndRecords {
+    /// The offset of the start of the central directory as stored in the end records, relative to
+    /// the start of the archive instead of the start of its disk.
+    pub fn declared_central_directory_offset(&self) -> u64 {
+        let offset_on_disk = match &self.zip64_eocd {
+            Some(z) => z.central_dir_offset_on_disk,
+            None => self.eocd.central_dir_offset_on_disk.into(),
+        };
+        self.central_directory_disk_offset.saturating_add(offset_on_disk)
+    }
+
+    /// The actual offset of the start of the central directory, taking the length of the preamble
+    /// into account.
+    pub fn central_directory_offset(&self) -> u64 {
+        self.declared_central_directory_offset() + self.preamble_length
+    }
+
+    /// The declared size of the central directory, in bytes.
+    pub fn central_directory_size(&self) -> u64 {
+        match &self.zip64_eocd {
+            Some(z) => z.central_directory_size,
+            None => self.eocd.central_directory_size.into(),
+        }
+    }
+
+    /// The declared total number of entries in the central directory.
+    pub fn total_entries(&self) -> u64 {
+        match &self.zip64_eocd {
+            Some(z) => z.total_central_dir_entries,
+            None => self.eocd.total_central_dir_entries.into(),
+        }
+    }
+
+    /// The offset of the earliest record at the end of the archive, i.e. the offset at which the
+    /// central directory should end.
+    pub fn start_offset(&self) -> u64 {
+        self.zip64_eocd_offset.unwrap_or(self.eocd_offset)
+    }
+}
+
+
+/// The maximum distance of the start of the end-of-central-directory record from the end of the
+/// file: the length of the record itself plus the length of the longest possible comment.
+const MAX_EOCD_SEARCH_LENGTH: u64 = EndOfCentralDirectory::min_len() + 0xFFFF;
+
+/// The length of the Zip64 end-of-central-directory locator record, which directly precedes the
+/// end-of-central-directory record.
+const ZIP64_EOCD_LOCATOR_LENGTH: u64 = Zip64EndOfCentralDirectoryLocator::min_len();
+
+
+/// Checks whether a candidate end-of-central-directory record at the given offset is plausible.
+///
+/// The record is plausible if its comment ends exactly at the end of the file. Otherwise (e.g. if
+/// junk has been appended to the archive), the record is plausible if it fits into the file and
+/// either points at a central directory located before it (on one of the disks whose offsets are
+/// given) or is preceded by a Zip64 end-of-central-directory locator.
+fn is_plausible_eocd<F: Read + Seek>(
+    mut zip_file: F,
+    disk_offsets: &[u64],
+    eocd_offset: u64,
+    eocd: &EndOfCentralDirectory,
+    comment_length: u64,
+    file_length: u64,
+) -> Result<bool, Error> {
+    let eocd_end = eocd_offset + EndOfCentralDirectory::min_len() + comment_length;
+    if eocd_end == file_length {
+        return Ok(true);
+    }
+    if eocd_end > file_length {
+        return Ok(false);
+    }
+
+    if eocd.should_check_zip64() {
+        if eocd_offset < ZIP64_EOCD_LOCATOR_LENGTH {
+            return Ok(false);
+        }
+        zip_file.seek(SeekFrom::Start(eocd_offset - ZIP64_EOCD_LOCATOR_LENGTH))?;
+        let signature = zip_file.read_u32_le()?;
+        return Ok(signature == Zip64EndOfCentralDirectoryLocator::signature());
+    }
+
+    let central_directory_disk_offset = match disk_offset(disk_offsets, eocd.start_central_dir_disk_no.into()) {
+        Ok(o) => o,
+        Err(_) => return Ok(false),
+    };
+    let central_directory_offset = central_directory_disk_offset + u64::from(eocd.central_dir_offset_on_disk);
+    let central_directory_size = u64::from(eocd.central_directory_size);
+    if central_directory_offset + central_directory_size > eocd_offset {
+        return Ok(false);
+    }
+    if eocd.total_central_dir_entries == 0 {
+        return Ok(central_directory_size == 0);
+    }
+    zip_file.seek(SeekFrom::Start(central_directory_offset))?;
+    let signature = zip_file.read_u32_le()?;
+    Ok(signature == CentralDirectoryEntry::signature())
+}
+
+
+/// Finds the end-of-central-directory record, returning its offset and contents.
+///
+/// Only the area at the end of the file in which the record can be located (taking the maximum
+/// comment length into account) is searched, starting at the end. The first plausible candidate
+/// (see [`is_plausible_eocd`]) is returned.
+fn find_end_of_central_directory<F: Read + Seek>(mut zip_file: F, disk_offsets: &[u64]) -> Result<(u64, EndOfCentralDirectory), Error> {
+    let file_length = zip_file.seek(SeekFrom::End(0))?;
+    if file_length < EndOfCentralDirectory::min_len() {
+        return Err(Error::MissingEndOfCentralDirectory);
+    }
+
+    // read the whole search area at once
+    let window_offset = file_length.saturating_sub(MAX_EOCD_SEARCH_LENGTH);
+    let mut window = vec![0u8; (file_length - window_offset).try_into().unwrap()];
+    zip_file.seek(SeekFrom::Start(window_offset))?;
+    zip_file.read_exact(&mut window)?;
+
+    let signature_bytes = EndOfCentralDirectory::signature().to_le_bytes();
+    let min_len: usize = EndOfCentralDirectory::min_len().try_into().unwrap();
+    for candidate_pos in (0..=window.len() - min_len).rev() {
+        if window[candidate_pos..candidate_pos+4] != signature_bytes {
+            continue;
+        }
+
+        // skip candidates whose comment would run past the end of the file
+        let comment_length_pos = candidate_pos + min_len - 2;
+        let comment_length = u16::from_le_bytes([window[comment_length_pos], window[comment_length_pos+1]]);
+        let candidate_end = candidate_pos + min_len + usize::from(comment_length);
+        if candidate_end > window.len() {
+            continue;
+        }
+
+        let candidate_offset = window_offset + u64::try_from(candidate_pos).unwrap();
+        let candidate = EndOfCentralDirectory::read_after_signature(&window[candidate_pos+4..candidate_end])?;
+        if is_plausible_eocd(&mut zip_file, disk_offsets, candidate_offset, &candidate, comment_length.into(), file_length)? {
+            return Ok((candidate_offset, candidate));
+        }
+    }
+
+    Err(Error::MissingEndOfCentralDirectory)
+}
+
+
+/// Determines the length of the preamble of the archive, i.e. the number of bytes that have been
+/// prepended to the archive without adjusting the offsets stored within it.
+///
+/// The central directory is expected to directly precede the (Zip64) end-of-central-directory
+/// record. If it is not found at its declared offset but at the offset from which it would end at
+/// the end records, the difference between these offsets is the length of the preamble.
+fn detect_preamble_length<F: Read + Seek>(mut zip_file: F, end_records: &EndRecords) -> Result<u64, Error> {
+    let declared_offset = end_records.declared_central_directory_offset();
+    let actual_offset = match end_records.start_offset().checked_sub(end_records.central_directory_size()) {
+        Some(ao) => ao,
+        None => return Ok(0),
+    };
+    if actual_offset <= declared_offset {
+        return Ok(0);
+    }
+    if end_records.total_entries() == 0 {
+        // no central directory entry whose signature we could check
+        return Ok(actual_offset - declared_offset);
+    }
+
+    let has_central_directory_at = |zip_file: &mut F, offset: u64| -> Result<bool, Error> {
+        zip_file.seek(SeekFrom::Start(offset))?;
+        match zip_file.read_u32_le() {
+            Ok(signature) => Ok(signature == CentralDirectoryEntry::signature()),
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    };
+    if has_central_directory_at(&mut zip_file, declared_offset)? {
+        return Ok(0);
+    }
+    if has_central_directory_at(&mut zip_file, actual_offset)? {
+        return Ok(actual_offset - declared_offset);
+    }
+    Ok(0)
+}
+
+
+/// Checks whether the values in the Zip64 end-of-central-directory record agree with those fields
+/// of the end-of-central-directory record that have not been saturated.
+fn is_consistent_zip64_eocd(eocd: &EndOfCentralDirectory, zip64_eocd: &Zip64EndOfCentralDirectory) -> bool {
+    fn agrees<N: Copy + Into<u64>, W: Into<u64>>(narrow: N, narrow_max: N, wide: W) -> bool {
+        let narrow_value: u64 = narrow.into();
+        narrow_value == narrow_max.into() || narrow_value == wide.into()
+    }
+
+    agrees(eocd.disk_no, u16::MAX, zip64_eocd.disk_no)
+        && agrees(eocd.start_central_dir_disk_no, u16::MAX, zip64_eocd.start_central_dir_disk_no)
+        && agrees(eocd.total_central_dir_entries_this_disk, u16::MAX, zip64_eocd.total_central_dir_entries_this_disk)
+        && agrees(eocd.total_central_dir_entries, u16::MAX, zip64_eocd.total_central_dir_entries)
+        && agrees(eocd.central_directory_size, u32::MAX, zip64_eocd.central_directory_size)
+        && agrees(eocd.central_dir_offset_on_disk, u32::MAX, zip64_eocd.central_dir_offset_on_disk)
+}
+
+
+/// Finds and reads the end-of-central-directory record and, if referenced, the Zip64
+/// end-of-central-directory record. Also determines the [length of the
+/// preamble](EndRecords::preamble_length).
+///
+/// `disk_offsets` contains the offset of the start of each disk of the archive; for an archive
+/// consisting of a single disk, it is `[0]`. The end records must be located on the last disk. If
+/// they refer to a different number of disks, [`Error::SpannedArchive`] is returned.
+///
+/// If the end-of-central-directory record [refers to Zip64
+/// records](EndOfCentralDirectory::should_check_zip64), the Zip64 end-of-central-directory locator
+/// must directly precede it and point at a Zip64 end-of-central-directory record; otherwise,
+/// [`Error::MissingZip64EndOfCentralDirectory`] is returned. If the Zip64 record contradicts the
+/// locator or a field of the end-of-central-directory record that has not been saturated,
+/// [`Error::InconsistentZip64EndOfCentralDirectory`] is returned.
+pub(crate) fn read_end_records<F: Read + Seek>(mut zip_file: F, disk_offsets: &[u64]) -> Result<EndRecords, Error> {
+    let last_disk: u32 = disk_offsets.len().checked_sub(1)
+        .and_then(|d| d.try_into().ok())
+        .ok_or(Error::SpannedArchive)?;
+    let (eocd_offset, eocd) = find_end_of_central_directory(&mut zip_file, disk_offsets)?;
+
+    if !eocd.should_check_zip64() {
+        if u32::from(eocd.disk_no) != last_disk {
+            return Err(Error::SpannedArchive);
+        }
+        // with a single disk, all entries must be on it
+        if last_disk == 0 && eocd.total_central_dir_entries != eocd.total_central_dir_entries_this_disk {
+            return Err(Error::SpannedArchive);
+        }
+        let central_directory_disk_offset = disk_offset(disk_offsets, eocd.start_central_dir_disk_no.into())?;
+        let mut end_records = EndRecords {
+            eocd_offset,
+            eocd,
+            zip64_eocd_offset: None,
+            zip64_eocd: None,
+            central_directory_disk_offset,
+            preamble_length: 0,
+        };
+        end_records.preamble_length = detect_preamble_length(&mut zip_file, &end_records)?;
+        return Ok(end_records);
+    }
+
+    // the Zip64 EoCD locator directly precedes the EoCD
+    if eocd_offset < ZIP64_EOCD_LOCATOR_LENGTH {
+        return Err(Error::MissingZip64EndOfCentralDirectory);
+    }
+    let zip64_eocd_loc_offset = eocd_offset - ZIP64_EOCD_LOCATOR_LENGTH;
+    zip_file.seek(SeekFrom::Start(zip64_eocd_loc_offset))?;
+    let zip64_eocd_loc_sig = zip_file.read_u32_le()?;
+    if zip64_eocd_loc_sig != Zip64EndOfCentralDirectoryLocator::signature() {
+        return Err(Error::MissingZip64EndOfCentralDirectory);
+    }
+    let zip64_eocd_loc = Zip64EndOfCentralDirectoryLocator::read_after_signature(&mut zip_file)?;
+    if u64::from(zip64_eocd_loc.total_disks) != u64::from(last_disk) + 1 {
+        return Err(Error::SpannedArchive);
+    }
+
+    // the Zip64 EoCD must end before its locator
+    let mut zip64_eocd_offset = disk_offset(disk_offsets, zip64_eocd_loc.disk_no)?
+        .checked_add(zip64_eocd_loc.offset_on_disk)
+        .ok_or(Error::InconsistentZip64EndOfCentralDirectory)?;
+    if zip64_eocd_offset >= zip64_eocd_loc_offset {
+        return Err(Error::InconsistentZip64EndOfCentralDirectory);
+    }
+    zip_file.seek(SeekFrom::Start(zip64_eocd_offset))?;
+    let mut zip64_eocd_sig = zip_file.read_u32_le()?;
+    if zip64_eocd_sig != Zip64EndOfCentralDirectory::signature() {
+        // if data has been prepended to the archive, the offset is too small;
+        // try again directly before the locator (assuming an empty extensible data sector)
+        if let Some(offset) = zip64_eocd_loc_offset.checked_sub(Zip64EndOfCentralDirectory::min_len()) {
+            if offset > zip64_eocd_offset {
+                zip_file.seek(SeekFrom::Start(offset))?;
+                zip64_eocd_sig = zip_file.read_u32_le()?;
+                zip64_eocd_offset = offset;
+            }
+        }
+    }
+    if zip64_eocd_sig != Zip64EndOfCentralDirectory::signature() {
+        return Err(Error::MissingZip64EndOfCentralDirectory);
+    }
+    // the record must fit between its length field and the locator
+    let zip64_eocd_max_length = (zip64_eocd_loc_offset - zip64_eocd_offset)
+        .saturating_sub(Zip64EndOfCentralDirectory::min_len_bias());
+    let zip64_eocd = Zip64EndOfCentralDirectory::read_after_signature(&mut zip_file, zip64_eocd_max_length)?;
+    if !is_consistent_zip64_eocd(&eocd, &zip64_eocd) {
+        return Err(Error::InconsistentZip64EndOfCentralDirectory);
+    }
+    if zip64_eocd.disk_no != last_disk {
+        return Err(Error::SpannedArchive);
+    }
+    if last_disk == 0 && zip64_eocd.total_central_dir_entries != zip64_eocd.total_central_dir_entries_this_disk {
+        return Err(Error::SpannedArchive);
+    }
+    let central_directory_disk_offset = disk_offset(disk_offsets, zip64_eocd.start_central_dir_disk_no)?;
+
+    let mut end_records = EndRecords {
+        eocd_offset,
+        eocd,
+        zip64_eocd_offset: Some(zip64_eocd_offset),
+        zip64_eocd: Some(zip64_eocd),
+        central_directory_disk_offset,
+        preamble_length: 0,
+    };
+    end_records.preamble_length = detect_preamble_length(&mut zip_file, &end_records)?;
+    Ok(end_records)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    use crate::zip_format::Zip64EndOfCentralDirectoryLocator;
+
+    use crate::{zip_read_archive, zip_read_entry_data, CompressionMethod, NewEntryOptions, ReadOptions, ZipWriter};
+
+    /// Writes an archive with a single stored entry containing the given data and the given archive
+    /// comment.
+    fn archive_with(data: &[u8], comment: &[u8]) -> Cursor<Vec<u8>> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new())).unwrap()
+            .comment(comment.to_vec());
+        let options = NewEntryOptions {
+            compression_method: CompressionMethod::Stored,
+            ..Default::default()
+        };
+        writer.add_file("file.bin", data, &options).unwrap();
+        writer.finish().unwrap()
+    }
+
+    /// An end-of-central-directory record that does not belong to the archive, claiming one entry in
+    /// a central directory at the start of the file.
+    fn fake_eocd() -> Vec<u8> {
+        let mut fake = Vec::new();
+        EndOfCentralDirectory {
+            total_central_dir_entries_this_disk: 1,
+            total_central_dir_entries: 1,
+            central_directory_size: 46,
+            ..Default::default()
+        }.write(&mut fake).unwrap();
+        fake
+    }
+
+    fn assert_readable(zip_file: &mut Cursor<Vec<u8>>, data: &[u8], comment: &[u8]) {
+        let archive = zip_read_archive(&mut *zip_file, &ReadOptions::default()).unwrap();
+        assert_eq!(archive.comment, comment);
+        assert_eq!(archive.entries.len(), 1);
+        let mut read = Vec::new();
+        zip_read_entry_data(&mut *zip_file, &archive.entries[0], &mut read).unwrap();
+        assert_eq!(read, data);
+    }
+
+    #[test]
+    fn test_fake_eocd_in_comment() {
+        let mut comment = b"embedded: ".to_vec();
+        comment.extend_from_slice(&fake_eocd());
+        comment.extend_from_slice(b" and some more text");
+        let mut zip_file = archive_with(b"data", &comment);
+
+        let file_length = u64::try_from(zip_file.get_ref().len()).unwrap();
+        let (eocd_offset, eocd) = find_end_of_central_directory(&mut zip_file, &[0]).unwrap();
+        assert_eq!(eocd_offset, file_length - EndOfCentralDirectory::min_len() - u64::try_from(comment.len()).unwrap());
+        assert_eq!(eocd.comment, comment);
+        assert_readable(&mut zip_file, b"data", &comment);
+    }
+
+    #[test]
+    fn test_fake_eocd_in_stored_data() {
+        let mut data = b"stored: ".to_vec();
+        data.extend_from_slice(&fake_eocd());
+        let mut zip_file = archive_with(&data, b"");
+
+        let file_length = u64::try_from(zip_file.get_ref().len()).unwrap();
+        let (eocd_offset, _eocd) = find_end_of_central_directory(&mut zip_file, &[0]).unwrap();
+        assert_eq!(eocd_offset, file_length - EndOfCentralDirectory::min_len());
+        assert_readable(&mut zip_file, &data, b"");
+
+        // with junk appended, the real record must still win over the fake one
+        zip_file.get_mut().extend_from_slice(b"junk");
+        let (junk_eocd_offset, _eocd) = find_end_of_central_directory(&mut zip_file, &[0]).unwrap();
+        assert_eq!(junk_eocd_offset, eocd_offset);
+        assert_readable(&mut zip_file, &data, b"");
+    }
+
+    #[test]
+    fn test_eocd_at_edge_of_search_window() {
+        // the longest possible comment puts the record at the very start of the search area
+        let comment = vec![b'c'; 0xFFFF];
+        let mut zip_file = archive_with(b"data", &comment);
+        let file_length = u64::try_from(zip_file.get_ref().len()).unwrap();
+        let (eocd_offset, _eocd) = find_end_of_central_directory(&mut zip_file, &[0]).unwrap();
+        assert_eq!(eocd_offset, file_length - MAX_EOCD_SEARCH_LENGTH);
+        assert_readable(&mut zip_file, b"data", &comment);
+
+        // one more byte pushes it out of the search area
+        zip_file.get_mut().push(0);
+        let result = find_end_of_central_directory(&mut zip_file, &[0]);
+        assert!(matches!(result, Err(Error::MissingEndOfCentralDirectory)), "{:?}", result);
+    }
+
+    /// The parts of a bare archive consisting of a central directory with a single entry and Zip64
+    /// end records.
+    struct Zip64Parts {
+        central_directory: Vec<u8>,
+        zip64_eocd: Zip64EndOfCentralDirectory,
+        locator: Zip64EndOfCentralDirectoryLocator,
+        eocd: EndOfCentralDirectory,
+    }
+    impl Zip64Parts {
+        fn new() -> Self {
+            let mut central_directory = Vec::new();
+            CentralDirectoryEntry {
+                file_name: b"file.txt".to_vec(),
+                ..Default::default()
+            }.write(&mut central_directory).unwrap();
+            let central_directory_size = u64::try_from(central_directory.len()).unwrap();
+
+            let zip64_eocd = Zip64EndOfCentralDirectory {
+                total_central_dir_entries_this_disk: 1,
+                total_central_dir_entries: 1,
+                central_directory_size,
+                ..Default::default()
+            };
+            let locator = Zip64EndOfCentralDirectoryLocator {
+                disk_no: 0,
+                offset_on_disk: central_directory_size,
+                total_disks: 1,
+            };
+            // only the entry count and offset are saturated
+            let eocd = EndOfCentralDirectory {
+                total_central_dir_entries_this_disk: u16::MAX,
+                total_central_dir_entries: u16::MAX,
+                central_directory_size: central_directory_size.try_into().unwrap(),
+                central_dir_offset_on_disk: u32::MAX,
+                ..Default::default()
+            };
+            Self {
+                central_directory,
+                zip64_eocd,
+                locator,
+                eocd,
+            }
+        }
+
+        fn write(&self) -> Cursor<Vec<u8>> {
+            let mut zip_file = self.central_directory.clone();
+            self.zip64_eocd.write(&mut zip_file).unwrap();
+            self.locator.write(&mut zip_file).unwrap();
+            self.eocd.write(&mut zip_file).unwrap();
+            Cursor::new(zip_file)
+        }
+    }
+
+    #[test]
+    fn test_zip64_end_records() {
+        let parts = Zip64Parts::new();
+        let mut zip_file = parts.write();
+        let end_records = read_end_records(&mut zip_file, &[0]).unwrap();
+        assert_eq!(end_records.zip64_eocd.as_ref(), Some(&parts.zip64_eocd));
+        assert_eq!(end_records.zip64_eocd_offset, Some(parts.locator.offset_on_disk));
+        assert_eq!(end_records.central_directory_offset(), 0);
+        assert_eq!(end_records.total_entries(), 1);
+
+        let archive = zip_read_archive(&mut zip_file, &ReadOptions::default()).unwrap();
+        assert_eq!(archive.entries[0].entry.file_name, b"file.txt");
+    }
+
+    #[test]
+    fn test_corrupted_zip64_locator() {
+        // a different signature where the locator should be; the 0xFFFFFFFF offset is not used
+        let mut zip_file = Zip64Parts::new().write();
+        let eocd_offset = zip_file.get_ref().len() - usize::try_from(EndOfCentralDirectory::min_len()).unwrap();
+        let locator_offset = eocd_offset - usize::try_from(ZIP64_EOCD_LOCATOR_LENGTH).unwrap();
+        zip_file.get_mut()[locator_offset] ^= 0xFF;
+        let result = read_end_records(&mut zip_file, &[0]);
+        assert!(matches!(result, Err(Error::MissingZip64EndOfCentralDirectory)), "{:?}", result);
+
+        // the locator claims a different number of disks
+        let mut parts = Zip64Parts::new();
+        parts.locator.total_disks = 2;
+        let result = read_end_records(parts.write(), &[0]);
+        assert!(matches!(result, Err(Error::SpannedArchive)), "{:?}", result);
+
+        // the locator points at or past itself
+        let mut parts = Zip64Parts::new();
+        parts.locator.offset_on_disk += Zip64EndOfCentralDirectory::min_len();
+        let result = read_end_records(parts.write(), &[0]);
+        assert!(matches!(result, Err(Error::InconsistentZip64EndOfCentralDirectory)), "{:?}", result);
+
+        // the locator points at something that is not a Zip64 end-of-central-directory record
+        let mut zip_file = Zip64Parts::new().write();
+        let zip64_eocd_offset = locator_offset - usize::try_from(Zip64EndOfCentralDirectory::min_len()).unwrap();
+        zip_file.get_mut()[zip64_eocd_offset] ^= 0xFF;
+        let result = read_end_records(&mut zip_file, &[0]);
+        assert!(matches!(result, Err(Error::MissingZip64EndOfCentralDirectory)), "{:?}", result);
+    }
+
+    #[test]
+    fn test_inconsistent_zip64_eocd() {
+        // the central directory size is not saturated and must agree
+        let mut parts = Zip64Parts::new();
+        parts.eocd.central_directory_size += 1;
+        let result = read_end_records(parts.write(), &[0]);
+        assert!(matches!(result, Err(Error::InconsistentZip64EndOfCentralDirectory)), "{:?}", result);
+
+        // saturated fields may differ
+        let mut parts = Zip64Parts::new();
+        parts.eocd.central_directory_size = u32::MAX;
+        assert!(read_end_records(parts.write(), &[0]).is_ok());
+    }
+}
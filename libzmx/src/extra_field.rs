@@ -0,0 +1,870 @@
+//! Structures of the extra fields commonly found in ZIP archives.
+
+
+use std::io::{Read, Write};
+
+use crate::io_ext::{ReadExt, WriteExt};
+use crate::zip_format::Zip64ExtraField;
+
+
+/// Reads the length of an extra field followed by that many bytes of data.
+///
+/// It is assumed that the tag of the extra field has just been read.
+fn read_length_and_data<R: Read>(mut reader: R) -> Result<(u16, Vec<u8>), crate::Error> {
+    let length = reader.read_u16_le()?;
+    let mut data = vec![0u8; length.into()];
+    reader.read_exact(&mut data)?;
+    Ok((length, data))
+}
+
+/// Prepends the length of the given extra field data to it, yielding the format expected by the
+/// `read_after_tag` functions.
+pub(crate) fn with_length_prefix(block: &[u8]) -> Vec<u8> {
+    let length: u16 = block.len().try_into().unwrap();
+    let mut length_and_block = Vec::with_capacity(2 + block.len());
+    length_and_block.extend_from_slice(&length.to_le_bytes());
+    length_and_block.extend_from_slice(block);
+    length_and_block
+}
+
+/// Writes the tag and length of an extra field followed by its data.
+fn write_tag_length_and_data<W: Write>(mut writer: W, tag: u16, data: &[u8]) -> Result<(), crate::Error> {
+    let length: u16 = data.len().try_into()
+        .map_err(|_| crate::Error::FieldTooLong)?;
+    writer.write_u16_le(tag)?;
+    writer.write_u16_le(length)?;
+    writer.write_all(data)?;
+    Ok(())
+}
+
+/// Decodes an unsigned little-endian integer of up to 8 bytes.
+fn decode_variable_le(bytes: &[u8]) -> Option<u64> {
+    if bytes.len() > 8 {
+        return None;
+    }
+    let mut value = 0u64;
+    for (i, b) in bytes.iter().enumerate() {
+        value |= u64::from(*b) << (8*i);
+    }
+    Some(value)
+}
+
+/// Encodes an unsigned integer in little-endian byte order using 4 bytes if possible and 8 bytes
+/// otherwise.
+fn encode_variable_le(value: u64) -> Vec<u8> {
+    match u32::try_from(value) {
+        Ok(v) => v.to_le_bytes().to_vec(),
+        Err(_) => value.to_le_bytes().to_vec(),
+    }
+}
+
+
+/// The "NTFS" extra field (tag 0x000A).
+///
+/// Only the timestamp attribute (tag 0x0001) of this field is decoded. The timestamps are given
+/// as Windows `FILETIME` values, i.e. in units of 100 nanoseconds since 1601-01-01 00:00:00 UTC.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct NtfsExtraField {
+    /// The time of last modification of this file.
+    pub modification_time: u64,
+
+    /// The time of last access of this file.
+    pub access_time: u64,
+
+    /// The creation time of this file.
+    pub creation_time: u64,
+}
+impl NtfsExtraField {
+    /// The tag for this extra field.
+    pub const fn tag() -> u16 { 0x000A }
+
+    /// The tag of the timestamp attribute within this extra field.
+    const fn timestamp_attribute_tag() -> u16 { 0x0001 }
+
+    /// Write the extra field, including tag and length.
+    pub fn write<W: Write>(&self, writer: W) -> Result<(), crate::Error> {
+        let mut data = Vec::with_capacity(32);
+        data.write_u32_le(0)?; // reserved
+        data.write_u16_le(Self::timestamp_attribute_tag())?;
+        data.write_u16_le(24)?;
+        data.write_u64_le(self.modification_time)?;
+        data.write_u64_le(self.access_time)?;
+        data.write_u64_le(self.creation_time)?;
+        write_tag_length_and_data(writer, Self::tag(), &data)
+    }
+
+    /// Read the extra field, including its length.
+    ///
+    /// It is assumed that its tag has just been read.
+    ///
+    /// If the field does not contain a timestamp attribute, `Ok(None)` is returned.
+    pub fn read_after_tag<R: Read>(reader: R) -> Result<Option<Self>, crate::Error> {
+        let (length, data) = read_length_and_data(reader)?;
+        if data.len() < 4 {
+            return Err(crate::Error::UnexpectedExtraDataLength(length));
+        }
+
+        // skip reserved field, then go through attributes
+        let mut attributes = &data[4..];
+        while !attributes.is_empty() {
+            if attributes.len() < 4 {
+                return Err(crate::Error::UnexpectedExtraDataLength(length));
+            }
+            let attribute_tag = attributes.read_u16_le()?;
+            let attribute_length: usize = attributes.read_u16_le()?.into();
+            if attribute_length > attributes.len() {
+                return Err(crate::Error::UnexpectedExtraDataLength(length));
+            }
+            let (mut attribute_data, rest) = attributes.split_at(attribute_length);
+            attributes = rest;
+
+            if attribute_tag == Self::timestamp_attribute_tag() {
+                if attribute_length != 24 {
+                    return Err(crate::Error::UnexpectedExtraDataLength(length));
+                }
+                let modification_time = attribute_data.read_u64_le()?;
+                let access_time = attribute_data.read_u64_le()?;
+                let creation_time = attribute_data.read_u64_le()?;
+                return Ok(Some(Self {
+                    modification_time,
+                    access_time,
+                    creation_time,
+                }));
+            }
+        }
+        Ok(None)
+    }
+}
+
+
+/// The "PKWARE Unix" extra field (tag 0x000D).
+///
+/// The timestamps are given in seconds since 1970-01-01 00:00:00 UTC.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct PkwareUnixExtraField {
+    /// The time of last access of this file.
+    pub access_time: u32,
+
+    /// The time of last modification of this file.
+    pub modification_time: u32,
+
+    /// The numeric ID of the user owning this file.
+    pub uid: u16,
+
+    /// The numeric ID of the group owning this file.
+    pub gid: u16,
+
+    /// Variable-length data.
+    ///
+    /// For symbolic and hard links, this is the target of the link; for device files, these are
+    /// the major and minor device numbers.
+    pub data: Vec<u8>,
+}
+impl PkwareUnixExtraField {
+    /// The tag for this extra field.
+    pub const fn tag() -> u16 { 0x000D }
+
+    /// Write the extra field, including tag and length.
+    pub fn write<W: Write>(&self, writer: W) -> Result<(), crate::Error> {
+        let mut data = Vec::with_capacity(12 + self.data.len());
+        data.write_u32_le(self.access_time)?;
+        data.write_u32_le(self.modification_time)?;
+        data.write_u16_le(self.uid)?;
+        data.write_u16_le(self.gid)?;
+        data.write_all(&self.data)?;
+        write_tag_length_and_data(writer, Self::tag(), &data)
+    }
+
+    /// Read the extra field, including its length.
+    ///
+    /// It is assumed that its tag has just been read.
+    pub fn read_after_tag<R: Read>(reader: R) -> Result<Self, crate::Error> {
+        let (length, data) = read_length_and_data(reader)?;
+        if data.len() < 12 {
+            return Err(crate::Error::UnexpectedExtraDataLength(length));
+        }
+
+        let mut data_reader = data.as_slice();
+        let access_time = data_reader.read_u32_le()?;
+        let modification_time = data_reader.read_u32_le()?;
+        let uid = data_reader.read_u16_le()?;
+        let gid = data_reader.read_u16_le()?;
+        let data = data_reader.to_vec();
+
+        Ok(Self {
+            access_time,
+            modification_time,
+            uid,
+            gid,
+            data,
+        })
+    }
+}
+
+
+/// The "Extended Timestamp" extra field (tag 0x5455).
+///
+/// The timestamps are given in seconds since 1970-01-01 00:00:00 UTC.
+///
+/// The flags specify which timestamps are available for this file. However, the variant of this
+/// field stored in the central directory generally only contains the modification time even if
+/// the flags announce the other timestamps as well.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct ExtendedTimestampExtraField {
+    /// Flags specifying which timestamps are available.
+    ///
+    /// Bit 0 signifies the modification time, bit 1 the access time and bit 2 the creation time.
+    pub flags: u8,
+
+    /// The time of last modification of this file.
+    pub modification_time: Option<i32>,
+
+    /// The time of last access of this file.
+    pub access_time: Option<i32>,
+
+    /// The creation time of this file.
+    pub creation_time: Option<i32>,
+}
+impl ExtendedTimestampExtraField {
+    /// The tag for this extra field.
+    pub const fn tag() -> u16 { 0x5455 }
+
+    /// Write the extra field, including tag and length.
+    ///
+    /// Only the timestamps that are set are written out.
+    pub fn write<W: Write>(&self, writer: W) -> Result<(), crate::Error> {
+        let mut data = Vec::with_capacity(13);
+        data.write_u8(self.flags)?;
+        if let Some(modification_time) = self.modification_time {
+            data.write_i32_le(modification_time)?;
+        }
+        if let Some(access_time) = self.access_time {
+            data.write_i32_le(access_time)?;
+        }
+        if let Some(creation_time) = self.creation_time {
+            data.write_i32_le(creation_time)?;
+        }
+        write_tag_length_and_data(writer, Self::tag(), &data)
+    }
+
+    /// Read the extra field, including its length.
+    ///
+    /// It is assumed that its tag has just been read.
+    pub fn read_after_tag<R: Read>(reader: R) -> Result<Self, crate::Error> {
+        let (length, data) = read_length_and_data(reader)?;
+        if data.is_empty() {
+            return Err(crate::Error::UnexpectedExtraDataLength(length));
+        }
+
+        let mut data_reader = data.as_slice();
+        let flags = data_reader.read_u8()?;
+
+        // a timestamp is only present if it is announced in the flags and there is still data left
+        let mut timestamps = [None; 3];
+        for (i, timestamp) in timestamps.iter_mut().enumerate() {
+            if flags & (1 << i) != 0 && data_reader.len() >= 4 {
+                *timestamp = Some(data_reader.read_i32_le()?);
+            }
+        }
+        if !data_reader.is_empty() {
+            return Err(crate::Error::UnexpectedExtraDataLength(length));
+        }
+        let [modification_time, access_time, creation_time] = timestamps;
+
+        Ok(Self {
+            flags,
+            modification_time,
+            access_time,
+            creation_time,
+        })
+    }
+}
+
+
+/// The "Info-ZIP Unix" extra field (tag 0x7875), also known as "Info-ZIP New Unix".
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct InfoZipUnixExtraField {
+    /// The version of this extra field; currently always 1.
+    pub version: u8,
+
+    /// The numeric ID of the user owning this file.
+    pub uid: u64,
+
+    /// The numeric ID of the group owning this file.
+    pub gid: u64,
+}
+impl InfoZipUnixExtraField {
+    /// The tag for this extra field.
+    pub const fn tag() -> u16 { 0x7875 }
+
+    /// Write the extra field, including tag and length.
+    ///
+    /// The IDs are stored using 4 bytes each if they fit and 8 bytes each otherwise.
+    pub fn write<W: Write>(&self, writer: W) -> Result<(), crate::Error> {
+        let uid_bytes = encode_variable_le(self.uid);
+        let gid_bytes = encode_variable_le(self.gid);
+
+        let mut data = Vec::with_capacity(3 + uid_bytes.len() + gid_bytes.len());
+        data.write_u8(self.version)?;
+        data.write_u8(uid_bytes.len().try_into().unwrap())?;
+        data.write_all(&uid_bytes)?;
+        data.write_u8(gid_bytes.len().try_into().unwrap())?;
+        data.write_all(&gid_bytes)?;
+        write_tag_length_and_data(writer, Self::tag(), &data)
+    }
+
+    /// Read the extra field, including its length.
+    ///
+    /// It is assumed that its tag has just been read.
+    pub fn read_after_tag<R: Read>(reader: R) -> Result<Self, crate::Error> {
+        let (length, data) = read_length_and_data(reader)?;
+        let bad_length = || crate::Error::UnexpectedExtraDataLength(length);
+
+        let mut data_reader = data.as_slice();
+        let version = data_reader.read_u8().map_err(|_| bad_length())?;
+
+        let uid_size: usize = data_reader.read_u8().map_err(|_| bad_length())?.into();
+        if uid_size > data_reader.len() {
+            return Err(bad_length());
+        }
+        let (uid_bytes, rest) = data_reader.split_at(uid_size);
+        data_reader = rest;
+        let uid = decode_variable_le(uid_bytes).ok_or_else(bad_length)?;
+
+        let gid_size: usize = data_reader.read_u8().map_err(|_| bad_length())?.into();
+        if gid_size != data_reader.len() {
+            return Err(bad_length());
+        }
+        let gid = decode_variable_le(data_reader).ok_or_else(bad_length)?;
+
+        Ok(Self {
+            version,
+            uid,
+            gid,
+        })
+    }
+}
+
+
+/// The "Info-ZIP Unicode Path" extra field (tag 0x7075).
+///
+/// Contains the UTF-8 encoded name of a file whose name in the header is encoded differently.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct UnicodePathExtraField {
+    /// The version of this extra field; currently always 1.
+    pub version: u8,
+
+    /// The CRC-32 checksum of the file name in the header.
+    ///
+    /// If the checksum does not match, the header has been modified by software that does not
+    /// know about this extra field, and this extra field should be ignored.
+    pub name_crc32: u32,
+
+    /// The UTF-8 encoded file name.
+    pub unicode_name: Vec<u8>,
+}
+impl UnicodePathExtraField {
+    /// The tag for this extra field.
+    pub const fn tag() -> u16 { 0x7075 }
+
+    /// Write the extra field, including tag and length.
+    pub fn write<W: Write>(&self, writer: W) -> Result<(), crate::Error> {
+        let mut data = Vec::with_capacity(5 + self.unicode_name.len());
+        data.write_u8(self.version)?;
+        data.write_u32_le(self.name_crc32)?;
+        data.write_all(&self.unicode_name)?;
+        write_tag_length_and_data(writer, Self::tag(), &data)
+    }
+
+    /// Read the extra field, including its length.
+    ///
+    /// It is assumed that its tag has just been read.
+    pub fn read_after_tag<R: Read>(reader: R) -> Result<Self, crate::Error> {
+        let (length, data) = read_length_and_data(reader)?;
+        if data.len() < 5 {
+            return Err(crate::Error::UnexpectedExtraDataLength(length));
+        }
+
+        let mut data_reader = data.as_slice();
+        let version = data_reader.read_u8()?;
+        let name_crc32 = data_reader.read_u32_le()?;
+        let unicode_name = data_reader.to_vec();
+
+        Ok(Self {
+            version,
+            name_crc32,
+            unicode_name,
+        })
+    }
+}
+
+
+/// The "Info-ZIP Unicode Comment" extra field (tag 0x6375).
+///
+/// Contains the UTF-8 encoded comment of a file whose comment in the header is encoded
+/// differently.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct UnicodeCommentExtraField {
+    /// The version of this extra field; currently always 1.
+    pub version: u8,
+
+    /// The CRC-32 checksum of the file comment in the header.
+    ///
+    /// If the checksum does not match, the header has been modified by software that does not
+    /// know about this extra field, and this extra field should be ignored.
+    pub comment_crc32: u32,
+
+    /// The UTF-8 encoded file comment.
+    pub unicode_comment: Vec<u8>,
+}
+impl UnicodeCommentExtraField {
+    /// The tag for this extra field.
+    pub const fn tag() -> u16 { 0x6375 }
+
+    /// Write the extra field, including tag and length.
+    pub fn write<W: Write>(&self, writer: W) -> Result<(), crate::Error> {
+        let mut data = Vec::with_capacity(5 + self.unicode_comment.len());
+        data.write_u8(self.version)?;
+        data.write_u32_le(self.comment_crc32)?;
+        data.write_all(&self.unicode_comment)?;
+        write_tag_length_and_data(writer, Self::tag(), &data)
+    }
+
+    /// Read the extra field, including its length.
+    ///
+    /// It is assumed that its tag has just been read.
+    pub fn read_after_tag<R: Read>(reader: R) -> Result<Self, crate::Error> {
+        let (length, data) = read_length_and_data(reader)?;
+        if data.len() < 5 {
+            return Err(crate::Error::UnexpectedExtraDataLength(length));
+        }
+
+        let mut data_reader = data.as_slice();
+        let version = data_reader.read_u8()?;
+        let comment_crc32 = data_reader.read_u32_le()?;
+        let unicode_comment = data_reader.to_vec();
+
+        Ok(Self {
+            version,
+            comment_crc32,
+            unicode_comment,
+        })
+    }
+}
+
+
+/// An extra field whose structure is not known (or not decoded).
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct RawExtraField {
+    /// The tag identifying the type of this extra field.
+    pub tag: u16,
+
+    /// The data of this extra field, excluding its tag and length.
+    pub data: Vec<u8>,
+}
+impl RawExtraField {
+    /// Write the extra field, including tag and length.
+    pub fn write<W: Write>(&self, writer: W) -> Result<(), crate::Error> {
+        write_tag_length_and_data(writer, self.tag, &self.data)
+    }
+}
+
+
+/// A decoded extra field.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum ExtraField {
+    /// The Zip64 extended information extra field (tag 0x0001).
+    Zip64(Zip64ExtraField),
+
+    /// The NTFS extra field (tag 0x000A).
+    Ntfs(NtfsExtraField),
+
+    /// The PKWARE Unix extra field (tag 0x000D).
+    PkwareUnix(PkwareUnixExtraField),
+
+    /// The extended timestamp extra field (tag 0x5455).
+    ExtendedTimestamp(ExtendedTimestampExtraField),
+
+    /// The Info-ZIP Unix extra field (tag 0x7875).
+    InfoZipUnix(InfoZipUnixExtraField),
+
+    /// The Info-ZIP Unicode path extra field (tag 0x7075).
+    UnicodePath(UnicodePathExtraField),
+
+    /// The Info-ZIP Unicode comment extra field (tag 0x6375).
+    UnicodeComment(UnicodeCommentExtraField),
+
+    /// An extra field that is not decoded by this library.
+    Unknown(RawExtraField),
+}
+impl ExtraField {
+    /// The tag identifying the type of this extra field.
+    pub fn tag(&self) -> u16 {
+        match self {
+            Self::Zip64(_) => Zip64ExtraField::tag(),
+            Self::Ntfs(_) => NtfsExtraField::tag(),
+            Self::PkwareUnix(_) => PkwareUnixExtraField::tag(),
+            Self::ExtendedTimestamp(_) => ExtendedTimestampExtraField::tag(),
+            Self::InfoZipUnix(_) => InfoZipUnixExtraField::tag(),
+            Self::UnicodePath(_) => UnicodePathExtraField::tag(),
+            Self::UnicodeComment(_) => UnicodeCommentExtraField::tag(),
+            Self::Unknown(raw) => raw.tag,
+        }
+    }
+
+    /// Write the extra field, including tag and length.
+    pub fn write<W: Write>(&self, writer: W) -> Result<(), crate::Error> {
+        match self {
+            Self::Zip64(f) => f.write(writer),
+            Self::Ntfs(f) => f.write(writer),
+            Self::PkwareUnix(f) => f.write(writer),
+            Self::ExtendedTimestamp(f) => f.write(writer),
+            Self::InfoZipUnix(f) => f.write(writer),
+            Self::UnicodePath(f) => f.write(writer),
+            Self::UnicodeComment(f) => f.write(writer),
+            Self::Unknown(f) => f.write(writer),
+        }
+    }
+}
+
+
+/// An iterator over the blocks of an extra field area, without decoding them.
+///
+/// Each item consists of the tag of a block and its data (excluding the tag and length).
+///
+/// If a block declares a length that exceeds the remaining data, or if the remaining data is too
+/// short to contain a tag and a length, [`Error::UnexpectedExtraDataLength`](crate::Error) is
+/// returned and the iteration ends.
+#[derive(Clone, Debug)]
+pub struct RawExtraFieldIter<'a> {
+    data: &'a [u8],
+}
+impl<'a> RawExtraFieldIter<'a> {
+    /// Creates a new iterator over the blocks in the given extra field area.
+    pub fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+        }
+    }
+}
+impl<'a> Iterator for RawExtraFieldIter<'a> {
+    type Item = Result<(u16, &'a [u8]), crate::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() {
+            return None;
+        }
+        if self.data.len() < 4 {
+            // not even enough for a tag and a length
+            let remaining: u16 = self.data.len().try_into().unwrap();
+            self.data = &[];
+            return Some(Err(crate::Error::UnexpectedExtraDataLength(remaining)));
+        }
+
+        let tag = u16::from_le_bytes([self.data[0], self.data[1]]);
+        let length = u16::from_le_bytes([self.data[2], self.data[3]]);
+        let length_usize: usize = length.into();
+        let rest = &self.data[4..];
+        if length_usize > rest.len() {
+            self.data = &[];
+            return Some(Err(crate::Error::UnexpectedExtraDataLength(length)));
+        }
+
+        let (block, rest) = rest.split_at(length_usize);
+        self.data = rest;
+        Some(Ok((tag, block)))
+    }
+}
+
+
+/// An iterator over the extra fields of a central directory entry, decoding them.
+///
+/// Fields with an unknown tag are returned as [`ExtraField::Unknown`]. If a known field has an
+/// unexpected length, [`Error::UnexpectedExtraDataLength`](crate::Error) is returned for it and the
+/// iteration continues with the next field; if the extra field area itself is malformed, the error
+/// is returned and the iteration ends.
+#[derive(Clone, Debug)]
+pub struct ExtraFieldIter<'a> {
+    raw: RawExtraFieldIter<'a>,
+    cdir_uncompressed_size: u32,
+    cdir_compressed_size: u32,
+    cdir_local_header_relative_offset: i32,
+    cdir_disk_number_start: u16,
+}
+impl<'a> ExtraFieldIter<'a> {
+    /// Creates a new iterator over the extra fields in the given extra field area.
+    ///
+    /// The relevant values from the central directory entry must be passed to decode the Zip64
+    /// extended information extra field; see [`Zip64ExtraField::read_after_tag`].
+    pub fn new(
+        data: &'a [u8],
+        cdir_uncompressed_size: u32,
+        cdir_compressed_size: u32,
+        cdir_local_header_relative_offset: i32,
+        cdir_disk_number_start: u16,
+    ) -> Self {
+        Self {
+            raw: RawExtraFieldIter::new(data),
+            cdir_uncompressed_size,
+            cdir_compressed_size,
+            cdir_local_header_relative_offset,
+            cdir_disk_number_start,
+        }
+    }
+
+    fn decode(&self, tag: u16, block: &[u8]) -> Result<ExtraField, crate::Error> {
+        let length_and_block = with_length_prefix(block);
+        let mut reader = length_and_block.as_slice();
+
+        let field = if tag == Zip64ExtraField::tag() {
+            ExtraField::Zip64(Zip64ExtraField::read_after_tag(
+                &mut reader,
+                self.cdir_uncompressed_size,
+                self.cdir_compressed_size,
+                self.cdir_local_header_relative_offset,
+                self.cdir_disk_number_start,
+            )?)
+        } else if tag == NtfsExtraField::tag() {
+            match NtfsExtraField::read_after_tag(&mut reader)? {
+                Some(f) => ExtraField::Ntfs(f),
+                None => ExtraField::Unknown(RawExtraField { tag, data: block.to_vec() }),
+            }
+        } else if tag == PkwareUnixExtraField::tag() {
+            ExtraField::PkwareUnix(PkwareUnixExtraField::read_after_tag(&mut reader)?)
+        } else if tag == ExtendedTimestampExtraField::tag() {
+            ExtraField::ExtendedTimestamp(ExtendedTimestampExtraField::read_after_tag(&mut reader)?)
+        } else if tag == InfoZipUnixExtraField::tag() {
+            ExtraField::InfoZipUnix(InfoZipUnixExtraField::read_after_tag(&mut reader)?)
+        } else if tag == UnicodePathExtraField::tag() {
+            ExtraField::UnicodePath(UnicodePathExtraField::read_after_tag(&mut reader)?)
+        } else if tag == UnicodeCommentExtraField::tag() {
+            ExtraField::UnicodeComment(UnicodeCommentExtraField::read_after_tag(&mut reader)?)
+        } else {
+            ExtraField::Unknown(RawExtraField { tag, data: block.to_vec() })
+        };
+        Ok(field)
+    }
+}
+impl<'a> Iterator for ExtraFieldIter<'a> {
+    type Item = Result<ExtraField, crate::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (tag, block) = match self.raw.next()? {
+            Ok(tb) => tb,
+            Err(e) => return Some(Err(e)),
+        };
+        Some(self.decode(tag, block))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes the given field and returns its tag and its data with the length prefix, ready to be
+    /// passed to `read_after_tag`.
+    fn written(field: &ExtraField) -> (u16, Vec<u8>) {
+        let mut bytes = Vec::new();
+        field.write(&mut bytes).unwrap();
+        let tag = u16::from_le_bytes([bytes[0], bytes[1]]);
+        (tag, bytes[2..].to_vec())
+    }
+
+    fn sample_fields() -> Vec<ExtraField> {
+        vec![
+            ExtraField::Ntfs(NtfsExtraField {
+                modification_time: 133_000_000_000_000_000,
+                access_time: 133_000_000_010_000_000,
+                creation_time: 132_000_000_000_000_000,
+            }),
+            ExtraField::PkwareUnix(PkwareUnixExtraField {
+                access_time: 1_700_000_100,
+                modification_time: 1_700_000_000,
+                uid: 1000,
+                gid: 100,
+                data: b"target".to_vec(),
+            }),
+            ExtraField::ExtendedTimestamp(ExtendedTimestampExtraField {
+                flags: 0b111,
+                modification_time: Some(1_700_000_000),
+                access_time: Some(1_700_000_100),
+                creation_time: Some(-1),
+            }),
+            ExtraField::InfoZipUnix(InfoZipUnixExtraField {
+                version: 1,
+                uid: 1000,
+                gid: 0x1_0000_0000,
+            }),
+            ExtraField::UnicodePath(UnicodePathExtraField {
+                version: 1,
+                name_crc32: 0x1234_5678,
+                unicode_name: "Grüße.txt".as_bytes().to_vec(),
+            }),
+            ExtraField::UnicodeComment(UnicodeCommentExtraField {
+                version: 1,
+                comment_crc32: 0x9ABC_DEF0,
+                unicode_comment: "Kommentar ✓".as_bytes().to_vec(),
+            }),
+        ]
+    }
+
+    #[test]
+    fn test_read_after_tag_round_trip() {
+        for field in sample_fields() {
+            let (tag, length_and_data) = written(&field);
+            assert_eq!(tag, field.tag());
+            let reader = length_and_data.as_slice();
+            let read = match &field {
+                ExtraField::Ntfs(_)
+                    => ExtraField::Ntfs(NtfsExtraField::read_after_tag(reader).unwrap().unwrap()),
+                ExtraField::PkwareUnix(_)
+                    => ExtraField::PkwareUnix(PkwareUnixExtraField::read_after_tag(reader).unwrap()),
+                ExtraField::ExtendedTimestamp(_)
+                    => ExtraField::ExtendedTimestamp(ExtendedTimestampExtraField::read_after_tag(reader).unwrap()),
+                ExtraField::InfoZipUnix(_)
+                    => ExtraField::InfoZipUnix(InfoZipUnixExtraField::read_after_tag(reader).unwrap()),
+                ExtraField::UnicodePath(_)
+                    => ExtraField::UnicodePath(UnicodePathExtraField::read_after_tag(reader).unwrap()),
+                ExtraField::UnicodeComment(_)
+                    => ExtraField::UnicodeComment(UnicodeCommentExtraField::read_after_tag(reader).unwrap()),
+                _ => unreachable!(),
+            };
+            assert_eq!(read, field);
+        }
+    }
+
+    #[test]
+    fn test_info_zip_unix_variable_sizes() {
+        let field = InfoZipUnixExtraField { version: 1, uid: 1000, gid: 0x1_0000_0000 };
+        let (_tag, length_and_data) = written(&ExtraField::InfoZipUnix(field));
+        // length, version, 4-byte UID, 8-byte GID
+        assert_eq!(length_and_data.len(), 2 + 1 + 1 + 4 + 1 + 8);
+
+        // 2-byte IDs as written by some tools are accepted too
+        let data = [1, 2, 0xE8, 0x03, 2, 0x64, 0x00];
+        let read = InfoZipUnixExtraField::read_after_tag(with_length_prefix(&data).as_slice()).unwrap();
+        assert_eq!(read, InfoZipUnixExtraField { version: 1, uid: 1000, gid: 100 });
+    }
+
+    #[test]
+    fn test_read_after_tag_bad_lengths() {
+        let bad_length = |result: Result<(), crate::Error>|
+            matches!(result, Err(crate::Error::UnexpectedExtraDataLength(_)));
+
+        let short = with_length_prefix(&[1, 2, 3]);
+        assert!(bad_length(NtfsExtraField::read_after_tag(short.as_slice()).map(|_| ())));
+        assert!(bad_length(PkwareUnixExtraField::read_after_tag(short.as_slice()).map(|_| ())));
+        assert!(bad_length(InfoZipUnixExtraField::read_after_tag(short.as_slice()).map(|_| ())));
+        assert!(bad_length(UnicodePathExtraField::read_after_tag(short.as_slice()).map(|_| ())));
+        assert!(bad_length(UnicodeCommentExtraField::read_after_tag(short.as_slice()).map(|_| ())));
+        assert!(bad_length(ExtendedTimestampExtraField::read_after_tag(with_length_prefix(&[]).as_slice()).map(|_| ())));
+
+        // NTFS timestamp attribute with the wrong length
+        let mut ntfs = vec![0, 0, 0, 0, 0x01, 0x00, 16, 0];
+        ntfs.extend_from_slice(&[0; 16]);
+        assert!(bad_length(NtfsExtraField::read_after_tag(with_length_prefix(&ntfs).as_slice()).map(|_| ())));
+
+        // NTFS attribute declaring more data than is available
+        let ntfs = [0, 0, 0, 0, 0x02, 0x00, 8, 0, 1, 2];
+        assert!(bad_length(NtfsExtraField::read_after_tag(with_length_prefix(&ntfs).as_slice()).map(|_| ())));
+
+        // NTFS field without a timestamp attribute
+        let ntfs = [0, 0, 0, 0, 0x02, 0x00, 2, 0, 1, 2];
+        assert!(NtfsExtraField::read_after_tag(with_length_prefix(&ntfs).as_slice()).unwrap().is_none());
+
+        // extended timestamp with trailing data that does not form a timestamp
+        let timestamp = [0b001, 1, 2, 3, 4, 5];
+        assert!(bad_length(ExtendedTimestampExtraField::read_after_tag(with_length_prefix(&timestamp).as_slice()).map(|_| ())));
+
+        // Info-ZIP Unix with a GID size that does not match the remaining data
+        let unix = [1, 4, 1, 2, 3, 4, 4, 1, 2];
+        assert!(bad_length(InfoZipUnixExtraField::read_after_tag(with_length_prefix(&unix).as_slice()).map(|_| ())));
+
+        // declared length exceeding the available data
+        let truncated = [10, 0, 1, 2, 3];
+        assert!(PkwareUnixExtraField::read_after_tag(&truncated[..]).is_err());
+    }
+
+    #[test]
+    fn test_raw_extra_field_iter() {
+        let mut data = Vec::new();
+        RawExtraField { tag: 0xCAFE, data: vec![1, 2, 3] }.write(&mut data).unwrap();
+        RawExtraField { tag: 0xBEEF, data: vec![] }.write(&mut data).unwrap();
+
+        let blocks: Vec<(u16, &[u8])> = RawExtraFieldIter::new(&data)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(blocks, vec![(0xCAFE, &[1u8, 2, 3][..]), (0xBEEF, &[][..])]);
+
+        assert_eq!(RawExtraFieldIter::new(&[]).count(), 0);
+    }
+
+    #[test]
+    fn test_raw_extra_field_iter_bad_declared_length() {
+        let mut data = Vec::new();
+        RawExtraField { tag: 0xCAFE, data: vec![1, 2, 3] }.write(&mut data).unwrap();
+        // declares 10 bytes but only 3 follow
+        data.extend_from_slice(&[0xEF, 0xBE, 10, 0, 1, 2, 3]);
+
+        let mut iter = RawExtraFieldIter::new(&data);
+        assert!(matches!(iter.next(), Some(Ok((0xCAFE, [1, 2, 3])))));
+        assert!(matches!(iter.next(), Some(Err(crate::Error::UnexpectedExtraDataLength(10)))));
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_raw_extra_field_iter_truncated_trailing_block() {
+        let mut data = Vec::new();
+        RawExtraField { tag: 0xCAFE, data: vec![1, 2, 3] }.write(&mut data).unwrap();
+        // too short for a tag and a length
+        data.extend_from_slice(&[0xEF, 0xBE, 10]);
+
+        let mut iter = RawExtraFieldIter::new(&data);
+        assert!(matches!(iter.next(), Some(Ok((0xCAFE, [1, 2, 3])))));
+        assert!(matches!(iter.next(), Some(Err(crate::Error::UnexpectedExtraDataLength(3)))));
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_extra_field_iter() {
+        let zip64 = ExtraField::Zip64(Zip64ExtraField {
+            uncompressed_size: Some(0x1_0000_0000),
+            compressed_size: None,
+            local_header_relative_offset: None,
+            disk_number_start: None,
+        });
+        let unknown = ExtraField::Unknown(RawExtraField { tag: 0xCAFE, data: vec![1, 2, 3] });
+
+        let mut fields = vec![zip64];
+        fields.extend(sample_fields());
+        fields.push(unknown);
+
+        let mut data = Vec::new();
+        for field in &fields {
+            field.write(&mut data).unwrap();
+        }
+
+        let read: Vec<ExtraField> = ExtraFieldIter::new(&data, 0xFFFF_FFFF, 1234, 0, 0)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(read, fields);
+    }
+
+    #[test]
+    fn test_extra_field_iter_continues_after_bad_field() {
+        let mut data = Vec::new();
+        // Unicode path field too short to be valid
+        RawExtraField { tag: UnicodePathExtraField::tag(), data: vec![1, 2] }.write(&mut data).unwrap();
+        let unknown = ExtraField::Unknown(RawExtraField { tag: 0xCAFE, data: vec![1, 2, 3] });
+        unknown.write(&mut data).unwrap();
+        // truncated trailing block
+        data.extend_from_slice(&[0xEF, 0xBE, 10, 0]);
+
+        let mut iter = ExtraFieldIter::new(&data, 0, 0, 0, 0);
+        assert!(matches!(iter.next(), Some(Err(crate::Error::UnexpectedExtraDataLength(2)))));
+        assert_eq!(iter.next().unwrap().unwrap(), unknown);
+        assert!(matches!(iter.next(), Some(Err(crate::Error::UnexpectedExtraDataLength(10)))));
+        assert!(iter.next().is_none());
+    }
+}
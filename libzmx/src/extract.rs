@@ -0,0 +1,656 @@
+//! Extracting the entries of ZIP archives into the file system.
+
+
+use std::cmp::Reverse;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+
+use flate2::Crc;
+use flate2::read::DeflateDecoder;
+
+use crate::{
+    EntryKind, Error, MAX_SYMLINK_TARGET_LENGTH, UNIX_MODE_MASK, UnixModeDefaults, ZipArchive,
+    ZipCentralDirectoryEntry, zip_read_local_header,
+};
+use crate::encoding::{CodePage, decode_entry_name};
+
+
+/// The bits of a Unix mode that are only retained if
+/// [`keep_special_bits`](ExtractOptions::keep_special_bits) is set: the setuid, setgid and sticky
+/// bits.
+const SPECIAL_MODE_BITS: u16 = 0o7000;
+
+/// The general-purpose bit flag marking an encrypted entry.
+const ENCRYPTION_FLAG: u16 = 1 << 0;
+
+
+/// Options influencing how the entries of an archive are extracted.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct ExtractOptions {
+    /// The permission bits that are removed from the mode of each extracted file and directory.
+    /// The default is 0o022.
+    pub umask: u16,
+
+    /// Whether to retain the setuid, setgid and sticky bits of the extracted files and
+    /// directories. The default is `false`.
+    pub keep_special_bits: bool,
+
+    /// The modes given to entries that do not carry Unix file attributes.
+    pub mode_defaults: UnixModeDefaults,
+
+    /// The code page in which to decode names that are not marked as UTF-8 (see
+    /// [`decode_entry_name`]). The default is `None`.
+    pub code_page: Option<CodePage>,
+}
+impl Default for ExtractOptions {
+    fn default() -> Self {
+        Self {
+            umask: 0o022,
+            keep_special_bits: false,
+            mode_defaults: UnixModeDefaults::default(),
+            code_page: None,
+        }
+    }
+}
+
+
+/// A writer that calculates the CRC-32 checksum and the length of the data written through it.
+struct ChecksumWriter<W> {
+    inner: W,
+    crc: Crc,
+    length: u64,
+}
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.crc.update(&buf[..written]);
+        self.length += u64::try_from(written).unwrap();
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+
+/// Reads the data of an entry, decompresses it and writes it to `output`.
+///
+/// The data may be stored or compressed using Deflate; other compression methods are refused with
+/// [`Error::UnsupportedCompressionMethod`] and encrypted entries with [`Error::EncryptedEntry`].
+/// Once all data has been written, its length and CRC-32 checksum are compared to those declared in
+/// the central directory entry, returning [`Error::UncompressedSizeMismatch`] or
+/// [`Error::ChecksumMismatch`] if they differ. As the data is written while it is being read,
+/// `output` may already have received data when an error is returned, but never more than one byte
+/// beyond the declared uncompressed size.
+///
+/// Returns the number of bytes written.
+pub fn zip_read_entry_data<F: Read + Seek, W: Write>(mut zip_file: F, entry: &ZipCentralDirectoryEntry, output: W) -> Result<u64, Error> {
+    if entry.entry.general_purpose_bit_flag & ENCRYPTION_FLAG != 0 {
+        return Err(Error::EncryptedEntry);
+    }
+    let local_header = zip_read_local_header(&mut zip_file, entry)?;
+    zip_file.seek(SeekFrom::Start(local_header.data_offset))?;
+    let compressed_data = (&mut zip_file).take(entry.compressed_size);
+
+    let mut writer = ChecksumWriter {
+        inner: output,
+        crc: Crc::new(),
+        length: 0,
+    };
+    // one byte more than declared suffices to detect a mismatch; this keeps small archives from
+    // expanding into unbounded amounts of data
+    let limit = entry.uncompressed_size.saturating_add(1);
+    match entry.entry.compression_method {
+        0 => {
+            io::copy(&mut compressed_data.take(limit), &mut writer)?;
+        },
+        8 => {
+            let decoder = DeflateDecoder::new(compressed_data);
+            io::copy(&mut decoder.take(limit), &mut writer)?;
+        },
+        other => return Err(Error::UnsupportedCompressionMethod(other)),
+    }
+    writer.flush()?;
+
+    if writer.length != entry.uncompressed_size {
+        return Err(Error::UncompressedSizeMismatch { declared: entry.uncompressed_size, found: writer.length });
+    }
+    let crc = writer.crc.sum();
+    if crc != entry.entry.crc32 {
+        return Err(Error::ChecksumMismatch { declared: entry.entry.crc32, found: crc });
+    }
+    Ok(writer.length)
+}
+
+
+/// Returns the path, relative to the destination directory, at which the entry with the given name
+/// is extracted.
+///
+/// Components of the name are separated by slashes; empty components (such as those resulting from
+/// a leading slash) and `.` are ignored, which places absolute names within the destination. Names
+/// containing `..` or components that the platform would interpret as a root, a drive or a further
+/// separator are refused with [`Error::UnsafeEntryName`].
+fn entry_path(name: &str) -> Result<PathBuf, Error> {
+    let mut path = PathBuf::new();
+    for component in name.split('/') {
+        match component {
+            ""|"." => {},
+            ".." => return Err(Error::UnsafeEntryName(name.to_owned())),
+            other => path.push(other),
+        }
+    }
+
+    // e.g. backslashes and drive letters on Windows
+    if !path.components().all(|c| matches!(c, Component::Normal(_))) {
+        return Err(Error::UnsafeEntryName(name.to_owned()));
+    }
+    Ok(path)
+}
+
+
+/// Returns whether the target of a symbolic link at the given relative path stays within the
+/// destination directory.
+///
+/// The target must be relative and may only ascend (`..`) at its start, by at most as many levels
+/// as the link is nested; once it descends into a directory, which might itself be a symbolic link,
+/// ascending is no longer allowed.
+fn symlink_target_is_safe(link_path: &Path, target: &Path) -> bool {
+    // the link itself is not a directory that can be ascended from
+    let mut depth = link_path.components().count().saturating_sub(1);
+    let mut descended = false;
+    for component in target.components() {
+        match component {
+            Component::Normal(_) => descended = true,
+            Component::CurDir => {},
+            Component::ParentDir => match depth.checked_sub(1) {
+                Some(d) if !descended => depth = d,
+                _ => return false,
+            },
+            Component::RootDir|Component::Prefix(_) => return false,
+        }
+    }
+    true
+}
+
+
+/// Converts the data of a symbolic link entry into a path.
+#[cfg(unix)]
+fn symlink_target_path(target: Vec<u8>) -> PathBuf {
+    use std::ffi::OsString;
+    use std::os::unix::ffi::OsStringExt;
+
+    PathBuf::from(OsString::from_vec(target))
+}
+
+#[cfg(not(unix))]
+fn symlink_target_path(target: Vec<u8>) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(&target).into_owned())
+}
+
+
+/// Creates the directory at the given path within the destination directory, along with the
+/// directories leading to it.
+///
+/// Existing symbolic links along the way are refused with [`Error::UnsafeEntryName`], as they might
+/// lead out of the destination directory.
+fn create_directories(destination: &Path, relative_path: &Path, name: &str) -> Result<(), Error> {
+    let mut path = destination.to_owned();
+    for component in relative_path.components() {
+        path.push(component);
+        match fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                return Err(Error::UnsafeEntryName(name.to_owned()));
+            },
+            Ok(metadata) if metadata.is_dir() => {},
+            Ok(_) => return Err(Error::Io(io::ErrorKind::AlreadyExists.into())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => fs::create_dir(&path)?,
+            Err(e) => return Err(Error::Io(e)),
+        }
+    }
+    Ok(())
+}
+
+
+/// Removes the file or symbolic link at the given path, if any, so that it is replaced instead of
+/// written through.
+fn remove_existing(path: &Path) -> Result<(), Error> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if !metadata.is_dir() => fs::remove_file(path)?,
+        Ok(_) => {},
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {},
+        Err(e) => return Err(Error::Io(e)),
+    }
+    Ok(())
+}
+
+
+/// Creates a regular file at the given path, which must not exist yet.
+///
+/// On Unix, the file is created with the permission bits of the given mode right away, so that it
+/// is not accessible to more users than intended while its data is being written. The setuid,
+/// setgid and sticky bits are left to [`set_mode`], as writing may clear them.
+#[cfg(unix)]
+fn create_file(path: &Path, mode: u16) -> Result<File, Error> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let file = File::options()
+        .write(true)
+        .create_new(true)
+        .mode(u32::from(mode & 0o777))
+        .open(path)?;
+    Ok(file)
+}
+
+#[cfg(not(unix))]
+fn create_file(path: &Path, _mode: u16) -> Result<File, Error> {
+    let file = File::options()
+        .write(true)
+        .create_new(true)
+        .open(path)?;
+    Ok(file)
+}
+
+
+/// Writes the data of the given entry into the given file and sets its time of last modification.
+fn write_file_data<F: Read + Seek>(zip_file: F, entry: &ZipCentralDirectoryEntry, file: File) -> Result<(), Error> {
+    let mut writer = BufWriter::new(file);
+    zip_read_entry_data(zip_file, entry, &mut writer)?;
+    let file = writer.into_inner()
+        .map_err(|e| e.into_error())?;
+    if let Some(modification_time) = entry.modification_time() {
+        file.set_modified(modification_time)?;
+    }
+    Ok(())
+}
+
+
+/// Sets the Unix mode of the file or directory at the given path.
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u16) -> Result<(), Error> {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::set_permissions(path, fs::Permissions::from_mode(mode.into()))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_mode(path: &Path, mode: u16) -> Result<(), Error> {
+    // only the write permission has an equivalent (the read-only attribute)
+    let mut permissions = fs::metadata(path)?.permissions();
+    permissions.set_readonly(mode & 0o200 == 0);
+    fs::set_permissions(path, permissions)?;
+    Ok(())
+}
+
+
+/// Sets the time of last modification of the directory at the given path.
+fn set_directory_modification_time(path: &Path, time: SystemTime) -> Result<(), Error> {
+    // opening a directory as a file is only possible on Unix
+    if cfg!(unix) {
+        File::open(path)?.set_modified(time)?;
+    }
+    Ok(())
+}
+
+
+/// Extracts the entries of an archive into the given directory, which is created if necessary.
+///
+/// `archive` must have been read from `zip_file`. The entries are extracted in the order of the
+/// central directory as follows, taking their kind from [`ZipCentralDirectoryEntry::kind`]:
+///
+/// * Directories are created along with the directories leading to them.
+/// * Symbolic links are created (on Unix) pointing to the target stored as their data. On other
+///   systems, a regular file containing the target is created instead.
+/// * All other entries, including special files, are extracted as regular files, whose data is
+///   decompressed and verified using [`zip_read_entry_data`].
+///
+/// Existing files and symbolic links are replaced. Files whose data cannot be extracted or fails
+/// verification are removed again. Files and directories are given the Unix mode
+/// of their entry (or, if it carries none, a mode synthesized from
+/// [`mode_defaults`](ExtractOptions::mode_defaults)) minus the [umask](ExtractOptions::umask), as
+/// well as the [time of last modification](ZipCentralDirectoryEntry::modification_time) of their
+/// entry. The modes and times of directories are applied after all entries have been extracted so
+/// that the directories remain writable in the meantime.
+///
+/// Before anything is extracted, the names of all entries are checked and [`Error::UnsafeEntryName`]
+/// is returned if any of them would lead out of the destination directory. The same error is
+/// returned when an entry would be extracted through a symbolic link or when the target of a
+/// symbolic link leads out of the destination directory. Extraction stops at the first error.
+pub fn zip_extract<F: Read + Seek>(mut zip_file: F, archive: &ZipArchive, destination: &Path, options: &ExtractOptions) -> Result<(), Error> {
+    let mut relative_paths = Vec::with_capacity(archive.entries.len());
+    for entry in &archive.entries {
+        let name = decode_entry_name(&entry.entry, options.code_page);
+        relative_paths.push((entry_path(&name)?, name));
+    }
+
+    fs::create_dir_all(destination)?;
+    let mut directories = Vec::new();
+    for (entry, (relative_path, name)) in archive.entries.iter().zip(relative_paths) {
+        // the destination directory itself is not modified
+        if relative_path.as_os_str().is_empty() {
+            continue;
+        }
+        let path = destination.join(&relative_path);
+
+        let mut mode = entry.unix_mode_or_default(&options.mode_defaults) & UNIX_MODE_MASK & !options.umask;
+        if !options.keep_special_bits {
+            mode &= !SPECIAL_MODE_BITS;
+        }
+
+        let kind = entry.kind();
+        if kind == EntryKind::Directory {
+            create_directories(destination, &relative_path, &name)?;
+            directories.push((path, mode, entry.modification_time()));
+            continue;
+        }
+
+        if let Some(parent) = relative_path.parent() {
+            create_directories(destination, parent, &name)?;
+        }
+        remove_existing(&path)?;
+
+        if kind == EntryKind::Symlink {
+            if entry.uncompressed_size > MAX_SYMLINK_TARGET_LENGTH {
+                return Err(Error::FieldTooLong);
+            }
+            let mut target = Vec::new();
+            zip_read_entry_data(&mut zip_file, entry, &mut target)?;
+            let target_path = symlink_target_path(target);
+            if !symlink_target_is_safe(&relative_path, &target_path) {
+                return Err(Error::UnsafeEntryName(name));
+            }
+
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(&target_path, &path)?;
+            #[cfg(not(unix))]
+            fs::write(&path, target_path.to_string_lossy().as_bytes())?;
+            continue;
+        }
+
+        let file = create_file(&path, mode)?;
+        if let Err(e) = write_file_data(&mut zip_file, entry, file) {
+            // do not leave incomplete or corrupt data behind
+            let _ = fs::remove_file(&path);
+            return Err(e);
+        }
+        set_mode(&path, mode)?;
+    }
+
+    // deepest directories first, in case their parents become read-only; the time of last
+    // modification is set first, as the mode might not allow opening the directory
+    directories.sort_by_key(|(path, _, _)| Reverse(path.components().count()));
+    for (path, mode, modification_time) in &directories {
+        if let Some(mt) = modification_time {
+            set_directory_modification_time(path, *mt)?;
+        }
+        set_mode(path, *mode)?;
+    }
+
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    use crate::{NewEntryOptions, ReadOptions, ZipWriter, zip_read_archive};
+
+    /// Returns the path of an empty directory for the given test, which the test should remove once
+    /// it is done.
+    fn test_directory(test_name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("libzmx-{}-{}", test_name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        path
+    }
+
+    fn archive_from(build: impl FnOnce(&mut ZipWriter<Cursor<Vec<u8>>>)) -> (Cursor<Vec<u8>>, ZipArchive) {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new())).unwrap();
+        build(&mut writer);
+        let mut zip_file = writer.finish().unwrap();
+        let archive = zip_read_archive(&mut zip_file, &ReadOptions::default()).unwrap();
+        (zip_file, archive)
+    }
+
+    #[test]
+    fn test_entry_path() {
+        assert_eq!(entry_path("a/b.txt").unwrap(), Path::new("a/b.txt"));
+        assert_eq!(entry_path("./a//b/").unwrap(), Path::new("a/b"));
+        assert_eq!(entry_path("").unwrap(), Path::new(""));
+
+        // absolute names are taken relative to the destination
+        assert_eq!(entry_path("/etc/passwd").unwrap(), Path::new("etc/passwd"));
+
+        assert!(matches!(entry_path(".."), Err(Error::UnsafeEntryName(_))));
+        assert!(matches!(entry_path("../a"), Err(Error::UnsafeEntryName(_))));
+        assert!(matches!(entry_path("a/../../b"), Err(Error::UnsafeEntryName(_))));
+        assert!(matches!(entry_path("a/../b"), Err(Error::UnsafeEntryName(_))));
+    }
+
+    #[test]
+    fn test_entry_path_backslashes() {
+        if cfg!(windows) {
+            assert!(matches!(entry_path("..\\a"), Err(Error::UnsafeEntryName(_))));
+            assert!(matches!(entry_path("a\\..\\..\\b"), Err(Error::UnsafeEntryName(_))));
+            assert!(matches!(entry_path("C:\\a"), Err(Error::UnsafeEntryName(_))));
+        } else {
+            // elsewhere, a backslash is an ordinary character of a file name
+            let path = entry_path("..\\a").unwrap();
+            assert_eq!(path.components().collect::<Vec<_>>(), [Component::Normal("..\\a".as_ref())]);
+        }
+    }
+
+    #[test]
+    fn test_symlink_target_is_safe() {
+        assert!(symlink_target_is_safe(Path::new("link"), Path::new("a/b")));
+        assert!(symlink_target_is_safe(Path::new("link"), Path::new("./a")));
+        assert!(symlink_target_is_safe(Path::new("d/link"), Path::new("../a")));
+        assert!(symlink_target_is_safe(Path::new("d/e/link"), Path::new("../../a")));
+        assert!(symlink_target_is_safe(Path::new("d/e/link"), Path::new("./../../a/b")));
+
+        // ascending further than the link is nested
+        assert!(!symlink_target_is_safe(Path::new("link"), Path::new("..")));
+        assert!(!symlink_target_is_safe(Path::new("link"), Path::new("../a")));
+        assert!(!symlink_target_is_safe(Path::new("d/link"), Path::new("../../a")));
+        assert!(!symlink_target_is_safe(Path::new("d/e/link"), Path::new("../../../a")));
+
+        // .. after a normal component, which might itself be a symbolic link
+        assert!(!symlink_target_is_safe(Path::new("d/link"), Path::new("a/../b")));
+        assert!(!symlink_target_is_safe(Path::new("d/e/link"), Path::new("../a/../b")));
+
+        // absolute targets
+        assert!(!symlink_target_is_safe(Path::new("link"), Path::new("/etc/passwd")));
+        assert!(!symlink_target_is_safe(Path::new("d/link"), Path::new("/")));
+    }
+
+    #[test]
+    fn test_read_entry_data_bounded() {
+        let data = vec![0u8; 1 << 20];
+        let (mut zip_file, archive) = archive_from(|writer| {
+            writer.add_file("zeros", data.as_slice(), &NewEntryOptions::default()).unwrap();
+        });
+
+        // pretend that the entry is much smaller than its data
+        let mut entry = archive.entries[0].clone();
+        entry.uncompressed_size = 10;
+        let mut output = Vec::new();
+        let result = zip_read_entry_data(&mut zip_file, &entry, &mut output);
+        assert!(matches!(result, Err(Error::UncompressedSizeMismatch { .. })), "{:?}", result);
+        assert_eq!(output.len(), 11);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_extract_through_existing_symlink() {
+        let directory = test_directory("extract-through-existing-symlink");
+        let destination = directory.join("destination");
+        let outside = directory.join("outside");
+        fs::create_dir_all(&destination).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        std::os::unix::fs::symlink(&outside, destination.join("sub")).unwrap();
+
+        let (mut zip_file, archive) = archive_from(|writer| {
+            writer.add_file("sub/file", b"data".as_slice(), &NewEntryOptions::default()).unwrap();
+        });
+        let result = zip_extract(&mut zip_file, &archive, &destination, &ExtractOptions::default());
+        assert!(matches!(result, Err(Error::UnsafeEntryName(_))), "{:?}", result);
+        assert!(!outside.join("file").exists());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_extract_through_extracted_symlink() {
+        let directory = test_directory("extract-through-extracted-symlink");
+        let destination = directory.join("destination");
+
+        // the target is safe, but the link must not be followed by later entries
+        let (mut zip_file, archive) = archive_from(|writer| {
+            let options = NewEntryOptions::default();
+            writer.add_directory("d/", &options).unwrap();
+            writer.add_symlink("link", b"d", &options).unwrap();
+            writer.add_file("link/file", b"data".as_slice(), &options).unwrap();
+        });
+        let result = zip_extract(&mut zip_file, &archive, &destination, &ExtractOptions::default());
+        assert!(matches!(result, Err(Error::UnsafeEntryName(_))), "{:?}", result);
+        assert!(!destination.join("d/file").exists());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_extract_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let directory = test_directory("extract-mode");
+        let (mut zip_file, archive) = archive_from(|writer| {
+            let options = NewEntryOptions {
+                unix_mode: 0o600,
+                ..Default::default()
+            };
+            writer.add_file("private", b"secret".as_slice(), &options).unwrap();
+        });
+        zip_extract(&mut zip_file, &archive, &directory, &ExtractOptions::default()).unwrap();
+        let metadata = fs::metadata(directory.join("private")).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o7777, 0o600);
+        assert_eq!(fs::read(directory.join("private")).unwrap(), b"secret");
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_extract_removes_corrupt_file() {
+        let directory = test_directory("extract-removes-corrupt-file");
+        let (mut zip_file, mut archive) = archive_from(|writer| {
+            writer.add_file("file", b"data".as_slice(), &NewEntryOptions::default()).unwrap();
+        });
+        archive.entries[0].entry.crc32 ^= 1;
+        let result = zip_extract(&mut zip_file, &archive, &directory, &ExtractOptions::default());
+        assert!(matches!(result, Err(Error::ChecksumMismatch { .. })), "{:?}", result);
+        assert!(!directory.join("file").exists());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+    #[test]
+    fn test_extract_deflated() {
+        let directory = test_directory("extract-deflated");
+        let data = "all work and no play makes jack a dull boy\n".repeat(1000);
+        let modified = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_600_000_000);
+        let (mut zip_file, archive) = archive_from(|writer| {
+            let options = NewEntryOptions {
+                modified: Some(modified),
+                ..Default::default()
+            };
+            writer.add_file("dir/text.txt", data.as_bytes(), &options).unwrap();
+        });
+        assert_eq!(archive.entries[0].entry.compression_method, 8);
+        assert!(archive.entries[0].compressed_size < archive.entries[0].uncompressed_size);
+
+        zip_extract(&mut zip_file, &archive, &directory, &ExtractOptions::default()).unwrap();
+        let path = directory.join("dir/text.txt");
+        assert_eq!(fs::read_to_string(&path).unwrap(), data);
+        assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), modified);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_extract_symlink() {
+        let directory = test_directory("extract-symlink");
+        let (mut zip_file, archive) = archive_from(|writer| {
+            let options = NewEntryOptions::default();
+            writer.add_file("d/file", b"data".as_slice(), &options).unwrap();
+            writer.add_symlink("link", b"d/file", &options).unwrap();
+            writer.add_symlink("d/up", b"../d", &options).unwrap();
+        });
+        zip_extract(&mut zip_file, &archive, &directory, &ExtractOptions::default()).unwrap();
+
+        assert!(fs::symlink_metadata(directory.join("link")).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_link(directory.join("link")).unwrap(), Path::new("d/file"));
+        assert_eq!(fs::read(directory.join("link")).unwrap(), b"data");
+        assert_eq!(fs::read_link(directory.join("d/up")).unwrap(), Path::new("../d"));
+
+        // extracting again replaces the links
+        zip_extract(&mut zip_file, &archive, &directory, &ExtractOptions::default()).unwrap();
+        assert_eq!(fs::read_link(directory.join("link")).unwrap(), Path::new("d/file"));
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_extract_directory_mode_and_time() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let directory = test_directory("extract-directory-mode-and-time");
+        let modified = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_500_000_000);
+        let (mut zip_file, archive) = archive_from(|writer| {
+            let options = NewEntryOptions {
+                modified: Some(modified),
+                ..Default::default()
+            };
+            // not readable by the owner, so the time cannot be set once the mode has been
+            writer.add_directory("locked/", &NewEntryOptions { unix_mode: 0o311, ..options }).unwrap();
+            writer.add_directory("locked/inner/", &NewEntryOptions { unix_mode: 0o750, ..options }).unwrap();
+            writer.add_file("locked/inner/file", b"data".as_slice(), &options).unwrap();
+        });
+        let options = ExtractOptions {
+            umask: 0,
+            ..Default::default()
+        };
+        zip_extract(&mut zip_file, &archive, &directory, &options).unwrap();
+
+        let locked = fs::metadata(directory.join("locked")).unwrap();
+        let inner = fs::metadata(directory.join("locked/inner")).unwrap();
+        assert_eq!(locked.permissions().mode() & 0o7777, 0o311);
+        assert_eq!(inner.permissions().mode() & 0o7777, 0o750);
+        assert_eq!(locked.modified().unwrap(), modified);
+        assert_eq!(inner.modified().unwrap(), modified);
+        assert_eq!(fs::read(directory.join("locked/inner/file")).unwrap(), b"data");
+
+        // the umask applies to directories as well
+        let umask_directory = directory.join("umask");
+        let options = ExtractOptions {
+            umask: 0o027,
+            ..Default::default()
+        };
+        zip_extract(&mut zip_file, &archive, &umask_directory, &options).unwrap();
+        let inner = fs::metadata(umask_directory.join("locked/inner")).unwrap();
+        assert_eq!(inner.permissions().mode() & 0o7777, 0o750);
+        let locked = fs::metadata(umask_directory.join("locked")).unwrap();
+        assert_eq!(locked.permissions().mode() & 0o7777, 0o310);
+
+        for path in [directory.join("locked"), umask_directory.join("locked")] {
+            fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
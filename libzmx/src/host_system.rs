@@ -0,0 +1,257 @@
+//! The host systems on which ZIP entries can be created.
+
+
+use std::fmt;
+
+use crate::UNIX_FILE_TYPE_MASK;
+
+
+/// The system on which a ZIP entry has been created, as stored in the upper byte of the "version
+/// made by" field.
+///
+/// The host system defines how the "external file attributes" field of the entry is to be
+/// interpreted.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum HostSystem {
+    /// MS-DOS and OS/2 (FAT, VFAT, FAT32 file systems). (0)
+    MsDos,
+
+    /// Amiga. (1)
+    Amiga,
+
+    /// OpenVMS. (2)
+    OpenVms,
+
+    /// Unix. (3)
+    Unix,
+
+    /// VM/CMS. (4)
+    VmCms,
+
+    /// Atari ST. (5)
+    AtariSt,
+
+    /// OS/2 HPFS. (6)
+    Os2Hpfs,
+
+    /// Classic Macintosh. (7)
+    Macintosh,
+
+    /// Z-System. (8)
+    ZSystem,
+
+    /// CP/M. (9)
+    CpM,
+
+    /// Windows NTFS. (10)
+    WindowsNtfs,
+
+    /// MVS (OS/390, z/OS). (11)
+    Mvs,
+
+    /// VSE. (12)
+    Vse,
+
+    /// Acorn RISC OS. (13)
+    AcornRisc,
+
+    /// VFAT. (14)
+    Vfat,
+
+    /// Alternate MVS. (15)
+    AlternateMvs,
+
+    /// BeOS. (16)
+    BeOs,
+
+    /// Tandem. (17)
+    Tandem,
+
+    /// OS/400. (18)
+    Os400,
+
+    /// OS X (Darwin). (19)
+    OsX,
+
+    /// A host system not known to this library.
+    Other(u8),
+}
+impl HostSystem {
+    /// Obtains the host system from its numeric value.
+    pub const fn from_byte(value: u8) -> Self {
+        match value {
+            0 => Self::MsDos,
+            1 => Self::Amiga,
+            2 => Self::OpenVms,
+            3 => Self::Unix,
+            4 => Self::VmCms,
+            5 => Self::AtariSt,
+            6 => Self::Os2Hpfs,
+            7 => Self::Macintosh,
+            8 => Self::ZSystem,
+            9 => Self::CpM,
+            10 => Self::WindowsNtfs,
+            11 => Self::Mvs,
+            12 => Self::Vse,
+            13 => Self::AcornRisc,
+            14 => Self::Vfat,
+            15 => Self::AlternateMvs,
+            16 => Self::BeOs,
+            17 => Self::Tandem,
+            18 => Self::Os400,
+            19 => Self::OsX,
+            other => Self::Other(other),
+        }
+    }
+
+    /// Obtains the numeric value of this host system.
+    pub const fn to_byte(&self) -> u8 {
+        match self {
+            Self::MsDos => 0,
+            Self::Amiga => 1,
+            Self::OpenVms => 2,
+            Self::Unix => 3,
+            Self::VmCms => 4,
+            Self::AtariSt => 5,
+            Self::Os2Hpfs => 6,
+            Self::Macintosh => 7,
+            Self::ZSystem => 8,
+            Self::CpM => 9,
+            Self::WindowsNtfs => 10,
+            Self::Mvs => 11,
+            Self::Vse => 12,
+            Self::AcornRisc => 13,
+            Self::Vfat => 14,
+            Self::AlternateMvs => 15,
+            Self::BeOs => 16,
+            Self::Tandem => 17,
+            Self::Os400 => 18,
+            Self::OsX => 19,
+            Self::Other(other) => *other,
+        }
+    }
+
+    /// Obtains the host system from the upper byte of a "version made by" field.
+    pub const fn from_creator_version(creator_version: u16) -> Self {
+        Self::from_byte(((creator_version >> 8) & 0xFF) as u8)
+    }
+
+    /// Replaces the upper byte of the given "version made by" field with this host system.
+    pub const fn apply_to_creator_version(&self, creator_version: u16) -> u16 {
+        (creator_version & 0x00FF) | ((self.to_byte() as u16) << 8)
+    }
+
+    /// Whether entries created on this host system always store Unix file attributes in the upper
+    /// half of the "external file attributes" field.
+    ///
+    /// This is the case for Unix, OS X and BeOS.
+    pub const fn uses_unix_attributes(&self) -> bool {
+        matches!(self, Self::Unix | Self::OsX | Self::BeOs)
+    }
+
+    /// Whether entries created on this host system may store Unix file attributes in the upper half
+    /// of the "external file attributes" field in addition to DOS attributes in the lower half.
+    ///
+    /// Some tools running on Windows do this for the MS-DOS, NTFS and VFAT host systems.
+    pub const fn may_carry_unix_attributes(&self) -> bool {
+        matches!(self, Self::MsDos | Self::WindowsNtfs | Self::Vfat)
+    }
+
+    /// Extracts the Unix file attributes (file type and mode) from the given "external file
+    /// attributes" field according to the conventions of this host system.
+    ///
+    /// For host systems that [always use Unix attributes](Self::uses_unix_attributes), the upper
+    /// half of the field is returned. For host systems that [may carry Unix
+    /// attributes](Self::may_carry_unix_attributes), the upper half is returned only if it
+    /// specifies a file type (the bits extracted using the mask 0o170000 are not 0o000000). In all
+    /// other cases, `None` is returned.
+    pub const fn unix_attributes(&self, external_attributes: u32) -> Option<u32> {
+        let upper_half = (external_attributes >> 16) & 0x0000FFFF;
+        let has_unix_attributes = self.uses_unix_attributes()
+            || (self.may_carry_unix_attributes() && upper_half & UNIX_FILE_TYPE_MASK != 0);
+        if has_unix_attributes {
+            Some(upper_half)
+        } else {
+            None
+        }
+    }
+}
+impl fmt::Display for HostSystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MsDos => write!(f, "MS-DOS"),
+            Self::Amiga => write!(f, "Amiga"),
+            Self::OpenVms => write!(f, "OpenVMS"),
+            Self::Unix => write!(f, "Unix"),
+            Self::VmCms => write!(f, "VM/CMS"),
+            Self::AtariSt => write!(f, "Atari ST"),
+            Self::Os2Hpfs => write!(f, "OS/2 HPFS"),
+            Self::Macintosh => write!(f, "Macintosh"),
+            Self::ZSystem => write!(f, "Z-System"),
+            Self::CpM => write!(f, "CP/M"),
+            Self::WindowsNtfs => write!(f, "Windows NTFS"),
+            Self::Mvs => write!(f, "MVS"),
+            Self::Vse => write!(f, "VSE"),
+            Self::AcornRisc => write!(f, "Acorn RISC OS"),
+            Self::Vfat => write!(f, "VFAT"),
+            Self::AlternateMvs => write!(f, "alternate MVS"),
+            Self::BeOs => write!(f, "BeOS"),
+            Self::Tandem => write!(f, "Tandem"),
+            Self::Os400 => write!(f, "OS/400"),
+            Self::OsX => write!(f, "OS X"),
+            Self::Other(other) => write!(f, "unknown host system {}", other),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_byte_round_trip() {
+        for value in 0..=u8::MAX {
+            assert_eq!(HostSystem::from_byte(value).to_byte(), value);
+        }
+        assert_eq!(HostSystem::from_byte(19), HostSystem::OsX);
+        assert_eq!(HostSystem::from_byte(20), HostSystem::Other(20));
+        assert_eq!(HostSystem::from_creator_version(0x0A2D), HostSystem::WindowsNtfs);
+        assert_eq!(HostSystem::Unix.apply_to_creator_version(0x0A2D), 0x032D);
+    }
+
+    #[test]
+    fn test_uses_unix_attributes() {
+        for host_system in [HostSystem::Unix, HostSystem::OsX, HostSystem::BeOs] {
+            assert!(host_system.uses_unix_attributes(), "{}", host_system);
+            assert!(!host_system.may_carry_unix_attributes(), "{}", host_system);
+            // even without a file type
+            assert_eq!(host_system.unix_attributes(0o644 << 16), Some(0o644));
+            assert_eq!(host_system.unix_attributes(0o100755 << 16 | 0x20), Some(0o100755));
+        }
+    }
+
+    #[test]
+    fn test_may_carry_unix_attributes() {
+        for host_system in [HostSystem::MsDos, HostSystem::WindowsNtfs, HostSystem::Vfat] {
+            assert!(!host_system.uses_unix_attributes(), "{}", host_system);
+            assert!(host_system.may_carry_unix_attributes(), "{}", host_system);
+            // only taken into account if the upper half specifies a file type
+            assert_eq!(host_system.unix_attributes(0o100644 << 16 | 0x20), Some(0o100644));
+            assert_eq!(host_system.unix_attributes(0o040755 << 16 | 0x10), Some(0o040755));
+            assert_eq!(host_system.unix_attributes(0o644 << 16 | 0x20), None);
+            assert_eq!(host_system.unix_attributes(0x20), None);
+        }
+        assert_eq!(HostSystem::from_byte(0x0A), HostSystem::WindowsNtfs);
+        assert_eq!(HostSystem::from_byte(0x0E), HostSystem::Vfat);
+    }
+
+    #[test]
+    fn test_no_unix_attributes() {
+        for host_system in [HostSystem::Amiga, HostSystem::OpenVms, HostSystem::Os2Hpfs, HostSystem::Macintosh, HostSystem::Other(42)] {
+            assert!(!host_system.uses_unix_attributes(), "{}", host_system);
+            assert!(!host_system.may_carry_unix_attributes(), "{}", host_system);
+            assert_eq!(host_system.unix_attributes(0o100644 << 16), None, "{}", host_system);
+        }
+    }
+}
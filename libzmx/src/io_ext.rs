@@ -149,3 +149,35 @@ impl<W: io::Write> WriteExt for W {
     implement_write!(write_u64_be, write_u64_le, u64, 8);
     implement_write!(write_u128_be, write_u128_le, u128, 16);
 }
+
+/// Files and file-like objects whose length can be changed.
+pub trait SetLen {
+    /// Truncates or extends the underlying file to the given length.
+    ///
+    /// If the file is extended, the new area is filled with zeroes. The position of the cursor is
+    /// not changed.
+    fn set_len(&mut self, len: u64) -> Result<(), io::Error>;
+}
+impl SetLen for std::fs::File {
+    fn set_len(&mut self, len: u64) -> Result<(), io::Error> {
+        std::fs::File::set_len(self, len)
+    }
+}
+impl SetLen for &std::fs::File {
+    fn set_len(&mut self, len: u64) -> Result<(), io::Error> {
+        std::fs::File::set_len(self, len)
+    }
+}
+impl SetLen for io::Cursor<Vec<u8>> {
+    fn set_len(&mut self, len: u64) -> Result<(), io::Error> {
+        let len = usize::try_from(len)
+            .map_err(|_| io::Error::new(io::ErrorKind::OutOfMemory, "length exceeds address space"))?;
+        self.get_mut().resize(len, 0);
+        Ok(())
+    }
+}
+impl<T: SetLen + ?Sized> SetLen for &mut T {
+    fn set_len(&mut self, len: u64) -> Result<(), io::Error> {
+        (**self).set_len(len)
+    }
+}
@@ -4,17 +4,50 @@
 //! origin to Unix and setting their external file attributes).
 
 
+mod check;
+mod encoding;
+mod end_records;
+mod extract;
+mod extra_field;
+mod host_system;
 mod io_ext;
+mod multi_volume;
+mod rewrite;
+mod timestamp;
+mod writer;
 mod zip_format;
 
 
 use std::fmt;
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::time::SystemTime;
 
+use crate::encoding::encode_entry_name;
+use crate::end_records::read_end_records;
+use crate::extra_field::RawExtraFieldIter;
 use crate::io_ext::{ReadExt, WriteExt};
-use crate::zip_format::{
-    CentralDirectoryEntry, EndOfCentralDirectory, Zip64EndOfCentralDirectory,
-    Zip64EndOfCentralDirectoryLocator,
+use crate::multi_volume::{disk_containing, disk_offset};
+use crate::rewrite::{move_data, set_local_header_offset, write_central_directory};
+use crate::timestamp::{dos_timestamp, system_time};
+use crate::writer::write_entry;
+
+pub use crate::check::{Finding, LocalHeaderField, zip_check, zip_check_disks};
+pub use crate::encoding::{
+    CodePage, decode_entry_comment, decode_entry_name, LANGUAGE_ENCODING_FLAG,
+    UnknownCodePageError,
+};
+pub use crate::extract::{ExtractOptions, zip_extract, zip_read_entry_data};
+pub use crate::extra_field::{
+    ExtendedTimestampExtraField, ExtraField, ExtraFieldIter, InfoZipUnixExtraField,
+    NtfsExtraField, PkwareUnixExtraField, RawExtraField, UnicodeCommentExtraField,
+    UnicodePathExtraField,
+};
+pub use crate::host_system::HostSystem;
+pub use crate::io_ext::SetLen;
+pub use crate::multi_volume::{find_volume_paths, MultiVolume, VolumeScheme};
+pub use crate::writer::{CompressionMethod, NewEntryOptions, ZipWriter};
+pub use crate::zip_format::{
+    CentralDirectoryEntry, DataDescriptor, LocalFileHeader, Zip64ExtraField,
 };
 
 
@@ -27,11 +60,20 @@ pub enum Error {
     /// Missing end-of-central-directory record.
     MissingEndOfCentralDirectory,
 
-    /// A ZIP archive spanning multiple disks/files is being read.
+    /// A ZIP archive spanning multiple disks/files is being read without all of its disks, or is
+    /// being modified in a way that is only supported for archives consisting of a single disk.
     ///
-    /// Spanned ZIP archives are currently not supported.
+    /// Spanned ZIP archives can be read by combining their volumes using [`MultiVolume`].
     SpannedArchive,
 
+    /// The end-of-central-directory record states that Zip64 records are to be consulted, but the
+    /// Zip64 end-of-central-directory locator or record is missing.
+    MissingZip64EndOfCentralDirectory,
+
+    /// The Zip64 end-of-central-directory record contradicts the end-of-central-directory record or
+    /// its locator.
+    InconsistentZip64EndOfCentralDirectory,
+
     /// A field is too long to be read/written.
     FieldTooLong,
 
@@ -41,10 +83,55 @@ pub enum Error {
     /// A record is smaller than its minimum size.
     RecordTooSmall,
 
+    /// A record declares a size that exceeds the data available for it.
+    RecordTooLarge,
+
     /// The extra data has an unexpected length.
     ///
     /// The contained value can be used to seek to the next extra data entry.
     UnexpectedExtraDataLength(u16),
+
+    /// The data of an entry is compressed using a method that is not supported for the requested
+    /// operation.
+    UnsupportedCompressionMethod(u16),
+
+    /// The requested operation cannot be performed on an entry of this kind.
+    UnsuitableEntryKind(EntryKind),
+
+    /// The number of entries in the central directory differs from the number declared in the
+    /// (Zip64) end-of-central-directory record.
+    EntryCountMismatch { declared: u64, found: u64 },
+
+    /// The size of the central directory differs from the size declared in the (Zip64)
+    /// end-of-central-directory record.
+    CentralDirectorySizeMismatch { declared: u64, found: u64 },
+
+    /// The local header, data or data descriptor of an entry to be removed overlaps with those of
+    /// an entry to be kept.
+    OverlappingEntries,
+
+    /// The data of an entry is encrypted, which is not supported.
+    EncryptedEntry,
+
+    /// The CRC-32 checksum of the extracted data of an entry differs from the one declared for it.
+    ChecksumMismatch { declared: u32, found: u32 },
+
+    /// The size of the extracted data of an entry differs from the uncompressed size declared for
+    /// it.
+    UncompressedSizeMismatch { declared: u64, found: u64 },
+
+    /// The name of an entry, or the target of a symbolic link, would lead out of the directory
+    /// into which the archive is being extracted.
+    UnsafeEntryName(String),
+
+    /// The data of a new entry turned out to be too large for the 32-bit size fields of its local
+    /// file header, for which its size hint had been small enough.
+    ///
+    /// `found` is the larger of the compressed and the uncompressed size.
+    SizeHintExceeded { hint: u64, found: u64 },
+
+    /// The archive has no entry with the given index.
+    NoSuchEntry(usize),
 }
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -55,14 +142,42 @@ impl fmt::Display for Error {
                 => write!(f, "missing end-of-central-directory record"),
             Self::SpannedArchive
                 => write!(f, "ZIP archive spans multiple files/disks"),
+            Self::MissingZip64EndOfCentralDirectory
+                => write!(f, "missing Zip64 end-of-central-directory record or locator"),
+            Self::InconsistentZip64EndOfCentralDirectory
+                => write!(f, "Zip64 end-of-central-directory record inconsistent with end-of-central-directory record"),
             Self::FieldTooLong
                 => write!(f, "field too long"),
             Self::IncorrectSignature
                 => write!(f, "incorrect signature for structure"),
             Self::RecordTooSmall
                 => write!(f, "record too small"),
+            Self::RecordTooLarge
+                => write!(f, "record too large"),
             Self::UnexpectedExtraDataLength(_)
                 => write!(f, "unexpected length of extra data"),
+            Self::UnsupportedCompressionMethod(m)
+                => write!(f, "unsupported compression method {}", m),
+            Self::UnsuitableEntryKind(k)
+                => write!(f, "operation not supported on entry of kind {}", k),
+            Self::EntryCountMismatch { declared, found }
+                => write!(f, "central directory declared to contain {} entries but {} found", declared, found),
+            Self::CentralDirectorySizeMismatch { declared, found }
+                => write!(f, "central directory declared to be {} bytes long but {} bytes found", declared, found),
+            Self::OverlappingEntries
+                => write!(f, "entries to be removed overlap with entries to be kept"),
+            Self::EncryptedEntry
+                => write!(f, "encrypted entries are not supported"),
+            Self::ChecksumMismatch { declared, found }
+                => write!(f, "CRC-32 checksum declared to be {:08X} but {:08X} found", declared, found),
+            Self::UncompressedSizeMismatch { declared, found }
+                => write!(f, "data declared to be {} bytes long but {} bytes found", declared, found),
+            Self::UnsafeEntryName(name)
+                => write!(f, "refusing to extract {:?} outside of the destination directory", name),
+            Self::SizeHintExceeded { hint, found }
+                => write!(f, "data expected to be {} bytes long but {} bytes found, which requires Zip64", hint, found),
+            Self::NoSuchEntry(index)
+                => write!(f, "no entry with index {}", index),
         }
     }
 }
@@ -73,6 +188,116 @@ impl From<std::io::Error> for Error {
 }
 
 
+/// The kind of file system item represented by a ZIP entry.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum EntryKind {
+    /// A regular file.
+    File,
+
+    /// A directory.
+    Directory,
+
+    /// A symbolic link. The target of the link is stored as the entry's data.
+    Symlink,
+
+    /// A special file such as a FIFO, a device or a socket.
+    Other,
+
+    /// The entry has been marked with a Unix file type that is not known.
+    Unknown,
+}
+impl EntryKind {
+    /// Derives the kind of the given central directory entry.
+    ///
+    /// If the entry carries Unix file attributes according to the conventions of its host system
+    /// (see [`HostSystem::unix_attributes`]) and these specify a file type (the bits extracted
+    /// using the mask 0o170000 are not 0o000000), the kind corresponds to this file type.
+    /// Otherwise, the entry is considered a directory if it has the DOS directory attribute (in the
+    /// lower half of the "external file attributes" field, the bit corresponding to the value 0x10
+    /// is set) and a file if not.
+    ///
+    /// In any case, regular files whose name ends with a slash are considered directories, as most
+    /// tools extract them as such.
+    pub fn from_entry(entry: &CentralDirectoryEntry) -> Self {
+        let name_is_directory = entry.file_name.ends_with(b"/");
+
+        let unix_file_type = HostSystem::from_creator_version(entry.creator_version)
+            .unix_attributes(entry.external_attributes)
+            .map(|ua| ua & UNIX_FILE_TYPE_MASK)
+            .unwrap_or(0);
+        match unix_file_type {
+            0 => {},
+            UNIX_FILE_TYPE_REGULAR => return if name_is_directory { Self::Directory } else { Self::File },
+            UNIX_FILE_TYPE_DIRECTORY => return Self::Directory,
+            UNIX_FILE_TYPE_SYMLINK => return Self::Symlink,
+            UNIX_FILE_TYPE_FIFO|UNIX_FILE_TYPE_CHARACTER_DEVICE|UNIX_FILE_TYPE_BLOCK_DEVICE|UNIX_FILE_TYPE_SOCKET
+                => return Self::Other,
+            _ => return Self::Unknown,
+        }
+
+        let dos_attribs = entry.external_attributes & 0x0000FFFF;
+        if dos_attribs & 0x10 != 0 || name_is_directory {
+            Self::Directory
+        } else {
+            Self::File
+        }
+    }
+}
+impl fmt::Display for EntryKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::File => write!(f, "file"),
+            Self::Directory => write!(f, "directory"),
+            Self::Symlink => write!(f, "symbolic link"),
+            Self::Other => write!(f, "special file"),
+            Self::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+
+/// The Unix modes given to entries that do not carry Unix file attributes yet when they are
+/// converted to Unix entries.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct UnixModeDefaults {
+    /// The mode (see [`UNIX_MODE_MASK`]) given to files. The default is 0o644.
+    pub file_mode: u16,
+
+    /// The mode (see [`UNIX_MODE_MASK`]) given to directories. The default is 0o755.
+    pub directory_mode: u16,
+}
+impl UnixModeDefaults {
+    /// Synthesizes a Unix mode for the given entry, which is assumed not to carry Unix file
+    /// attributes.
+    ///
+    /// The mode is taken from [`directory_mode`](Self::directory_mode) if the entry is of the kind
+    /// [`EntryKind::Directory`] and from [`file_mode`](Self::file_mode) otherwise. If the entry has
+    /// the DOS read-only attribute (in the lower half of the "external file attributes" field, the
+    /// bit corresponding to the value 0x01 is set), all write permissions are removed.
+    pub fn synthesize_mode(&self, entry: &CentralDirectoryEntry) -> u16 {
+        let mut mode = if EntryKind::from_entry(entry) == EntryKind::Directory {
+            self.directory_mode
+        } else {
+            self.file_mode
+        };
+        let dos_attribs = entry.external_attributes & 0x0000FFFF;
+        if dos_attribs & 0x01 != 0 {
+            // read-only
+            mode &= !0o222;
+        }
+        mode & UNIX_MODE_MASK
+    }
+}
+impl Default for UnixModeDefaults {
+    fn default() -> Self {
+        Self {
+            file_mode: 0o644,
+            directory_mode: 0o755,
+        }
+    }
+}
+
+
 /// An entry encountered in a ZIP archive's central directory. Represents a single file system item
 /// (file, folder, etc.).
 #[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
@@ -83,18 +308,166 @@ pub struct ZipCentralDirectoryEntry {
     /// The number of the disk containing this central directory entry.
     pub disk: u32,
 
-    /// The offset of this file's central directory entry from the beginning of its disk.
+    /// The offset of this file's central directory entry from the beginning of the archive.
+    ///
+    /// If the archive consists of multiple disks, this is the offset within their concatenation
+    /// (see [`MultiVolume`]).
     pub offset: u64,
+
+    /// The compressed size of this file.
+    ///
+    /// Unlike the equivalent field in [`entry`](Self::entry), this value has been taken from the
+    /// Zip64 extended information extra field if the central directory entry refers to it.
+    pub compressed_size: u64,
+
+    /// The uncompressed size of this file.
+    ///
+    /// Unlike the equivalent field in [`entry`](Self::entry), this value has been taken from the
+    /// Zip64 extended information extra field if the central directory entry refers to it.
+    pub uncompressed_size: u64,
+
+    /// The offset of this file's local header from the beginning of the archive.
+    ///
+    /// Unlike the equivalent field in [`entry`](Self::entry), this value has been taken from the
+    /// Zip64 extended information extra field if the central directory entry refers to it, and the
+    /// offset of the disk on which the file starts as well as the [length of the
+    /// preamble](ZipArchive::preamble_length) have been added to it.
+    pub local_header_offset: u64,
+
+    /// The number of the disk containing the first chunk of this file.
+    ///
+    /// Unlike the equivalent field in [`entry`](Self::entry), this value has been taken from the
+    /// Zip64 extended information extra field if the central directory entry refers to it.
+    pub disk_number_start: u32,
 }
 impl ZipCentralDirectoryEntry {
+    /// Wraps the central directory entry at the given offset, resolving the values that are stored
+    /// in its Zip64 extended information extra field and shifting its local header offset by the
+    /// offset of its disk and the length of the preamble.
+    ///
+    /// Returns [`Error::SpannedArchive`] if the entry starts on a disk whose offset is not given
+    /// and [`Error::RecordTooLarge`] if the shifted offset exceeds the range of 64-bit offsets.
+    pub(crate) fn resolve(entry: CentralDirectoryEntry, disk_offsets: &[u64], offset: u64, preamble_length: u64) -> Result<Self, Error> {
+        // be lenient so that a single malformed entry does not fail the whole listing; values that
+        // cannot be decoded are taken from the 32-bit fields
+        let zip64_extra = RawExtraFieldIter::new(&entry.extra_fields)
+            .filter_map(|b| b.ok())
+            .find(|(tag, _block)| *tag == Zip64ExtraField::tag())
+            .map(|(_tag, block)| Zip64ExtraField::from_data_lenient(
+                block,
+                entry.uncompressed_size,
+                entry.compressed_size,
+                entry.local_header_relative_offset,
+                entry.disk_number_start,
+            ))
+            .unwrap_or_default();
+
+        let compressed_size = zip64_extra.compressed_size
+            .unwrap_or(entry.compressed_size.into());
+        let uncompressed_size = zip64_extra.uncompressed_size
+            .unwrap_or(entry.uncompressed_size.into());
+        let disk_number_start = zip64_extra.disk_number_start
+            .unwrap_or(entry.disk_number_start.into());
+        // the 32-bit field is actually unsigned; only the Zip64 value is (nominally) signed
+        let local_header_offset = zip64_extra.local_header_relative_offset
+            .map(|o| o as u64)
+            .unwrap_or((entry.local_header_relative_offset as u32).into())
+            .checked_add(disk_offset(disk_offsets, disk_number_start)?)
+            .and_then(|o| o.checked_add(preamble_length))
+            .ok_or(Error::RecordTooLarge)?;
+
+        Ok(Self {
+            entry,
+            disk: disk_containing(disk_offsets, offset),
+            offset,
+            compressed_size,
+            uncompressed_size,
+            local_header_offset,
+            disk_number_start,
+        })
+    }
+
+    /// Returns the system on which this entry has been created.
+    pub const fn host_system(&self) -> HostSystem {
+        HostSystem::from_creator_version(self.entry.creator_version)
+    }
+
+    /// Returns the Unix file attributes (file type and mode) of this entry.
+    ///
+    /// Returns `None` if the entry does not carry Unix file attributes according to the conventions
+    /// of its host system (see [`HostSystem::unix_attributes`]).
+    pub const fn unix_attributes(&self) -> Option<u32> {
+        self.host_system().unix_attributes(self.entry.external_attributes)
+    }
+
+    /// Returns the Unix permission bits of this entry, including the setuid, setgid and sticky bits
+    /// (see [`UNIX_MODE_MASK`]).
+    ///
+    /// Returns `None` if the entry does not carry Unix file attributes (see
+    /// [`unix_attributes`](Self::unix_attributes)).
+    pub const fn unix_mode(&self) -> Option<u16> {
+        match self.unix_attributes() {
+            Some(unix_attribs) => Some((unix_attribs as u16) & UNIX_MODE_MASK),
+            None => None,
+        }
+    }
+
+    /// Returns the Unix permission bits of this entry or, if it does not carry Unix file attributes,
+    /// the permission bits synthesized from the given defaults (see
+    /// [`UnixModeDefaults::synthesize_mode`]).
+    pub fn unix_mode_or_default(&self, defaults: &UnixModeDefaults) -> u16 {
+        self.unix_mode()
+            .unwrap_or_else(|| defaults.synthesize_mode(&self.entry))
+    }
+
+    /// Returns the kind of file system item represented by this entry.
+    ///
+    /// See [`EntryKind::from_entry`] for details.
+    pub fn kind(&self) -> EntryKind {
+        EntryKind::from_entry(&self.entry)
+    }
+
+    /// Returns the time of last modification of this entry.
+    ///
+    /// The time is taken from the extended timestamp extra field if there is one that contains it.
+    /// Otherwise, it is taken from the MS-DOS date and time fields, which are interpreted as UTC;
+    /// `None` is returned if they do not denote a valid date and time.
+    pub fn modification_time(&self) -> Option<SystemTime> {
+        let extended_time = self.entry.extra_field_iter()
+            .filter_map(|f| match f {
+                Ok(ExtraField::ExtendedTimestamp(et)) => et.modification_time,
+                _ => None,
+            })
+            .next();
+        let timestamp = match extended_time {
+            Some(et) => i64::from(et),
+            None => dos_timestamp(self.entry.last_mod_file_time, self.entry.last_mod_file_date)?,
+        };
+        Some(system_time(timestamp))
+    }
+
+    /// Returns whether this entry is a symbolic link.
+    ///
+    /// An entry is considered a symbolic link if it carries Unix file attributes (see
+    /// [`unix_attributes`](Self::unix_attributes)) and, according to these, it is a symbolic link
+    /// (the bits extracted using the mask 0o170000 are 0o120000). The target of the link is stored
+    /// as the entry's data.
+    pub const fn is_symlink(&self) -> bool {
+        match self.unix_attributes() {
+            Some(unix_attribs) => unix_attribs & UNIX_FILE_TYPE_MASK == UNIX_FILE_TYPE_SYMLINK,
+            None => false,
+        }
+    }
+
     /// Returns whether this entry is executable.
     ///
     /// An entry is considered executable if all of the following conditions are met:
     ///
     /// * According to the DOS file attributes, the entry is not a directory. (In the lower half of
     ///   the "external file attributes" field, the bit corresponding to the value 0x10 is not set.)
-    /// * The file has been created on a Unix system. (The upper byte of the "version made by" field
-    ///   is 0x03.)
+    /// * The entry carries Unix file attributes according to the conventions of the system on which
+    ///   it has been created. (See [`HostSystem::unix_attributes`]; this is the case for entries
+    ///   created on Unix and OS X, for example.)
     /// * According to the Unix file attributes, the entry is a regular file. (In the top half of
     ///   the "external file attributes" field, the bits extracted using the mask 0o170000 are
     ///   0o100000.)
@@ -108,12 +481,13 @@ impl ZipCentralDirectoryEntry {
             return false;
         }
 
-        if ((self.entry.creator_version >> 8) & 0xFF) != 0x03 {
-            // entry does not come from Unix
-            return false;
-        }
-
-        let unix_attribs = (self.entry.external_attributes >> 16) & 0x0000FFFF;
+        let unix_attribs = match self.unix_attributes() {
+            Some(ua) => ua,
+            None => {
+                // entry does not carry Unix attributes
+                return false;
+            },
+        };
         if unix_attribs & 0o170000 != 0o100000 {
             // not a regular file
             return false;
@@ -124,21 +498,11 @@ impl ZipCentralDirectoryEntry {
 }
 
 
-fn lookback_for_signature<F: Read + Seek>(mut file: F, signature: u32) -> Result<bool, Error> {
-    loop {
-        let possible_signature = file.read_u32_le()?;
-        if possible_signature == signature {
-            return Ok(true);
-        }
-        let new_loc = file.seek(SeekFrom::Current(-5))?;
-        if new_loc == 0 {
-            return Ok(false);
-        }
-    }
-}
-
 /// Attempts to decode the given byte slice as UTF-8; if this fails, stubbornly decodes it as
 /// ISO-8859-1 instead.
+///
+/// To decode the name of an entry according to the rules of the ZIP format, use
+/// [`decode_entry_name`] instead.
 pub fn best_effort_decode(bytes: &[u8]) -> String {
     match String::from_utf8(Vec::from(bytes)) {
         Ok(s) => s,
@@ -151,82 +515,552 @@ pub fn best_effort_decode(bytes: &[u8]) -> String {
 }
 
 
+/// Options influencing how the central directory of a ZIP archive is read.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct ReadOptions {
+    /// Whether to accept an entry count in the end-of-central-directory record that has wrapped
+    /// around.
+    ///
+    /// Some tools write archives with more than 65535 entries without adding Zip64 records,
+    /// truncating the entry count to its lowest 16 bits. If this option is set and the archive has
+    /// no Zip64 end-of-central-directory record, the central directory is read until its declared
+    /// size is exhausted and the number of entries found only has to agree with the declared count
+    /// modulo 65536. The default is `false`.
+    pub lenient_entry_count: bool,
+}
+
+
+/// The central directory of a ZIP archive, as read by [`zip_read_archive`].
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct ZipArchive {
+    /// The entries in the central directory.
+    pub entries: Vec<ZipCentralDirectoryEntry>,
+
+    /// The number of bytes preceding the archive that are not accounted for by the offsets stored
+    /// within it.
+    ///
+    /// This is nonzero if data such as a self-extractor stub or a shebang line has been prepended
+    /// to the archive without adjusting its offsets. The length of the preamble is determined by
+    /// comparing the offset at which the central directory is actually located with the offset
+    /// declared in the end-of-central-directory record. All offsets in [`entries`](Self::entries)
+    /// have already been adjusted accordingly.
+    pub preamble_length: u64,
+
+    /// The offset of the central directory from the beginning of the archive.
+    pub central_directory_offset: u64,
+
+    /// The archive comment.
+    pub comment: Vec<u8>,
+}
+
+
 /// Obtains the list of file names in the archive.
-pub fn zip_get_files<F: Read + Seek>(mut zip_file: F) -> Result<Vec<ZipCentralDirectoryEntry>, Error> {
-    // start at the last possible location of the End of Central Directory record
-    let eocd_start = -i64::try_from(EndOfCentralDirectory::min_len()).unwrap();
-    zip_file.seek(SeekFrom::End(eocd_start))?;
+///
+/// The central directory must contain exactly as many entries and be exactly as long as declared
+/// by the end-of-central-directory record (or its Zip64 counterpart); otherwise,
+/// [`Error::EntryCountMismatch`] or [`Error::CentralDirectorySizeMismatch`] is returned.
+pub fn zip_get_files<F: Read + Seek>(zip_file: F) -> Result<Vec<ZipCentralDirectoryEntry>, Error> {
+    zip_get_files_with_options(zip_file, &ReadOptions::default())
+}
 
-    // look for EoCD
-    let eocd_found = lookback_for_signature(&mut zip_file, EndOfCentralDirectory::signature())?;
-    if !eocd_found {
-        return Err(Error::MissingEndOfCentralDirectory);
+
+/// Obtains the list of file names in the archive, reading it according to the given options.
+pub fn zip_get_files_with_options<F: Read + Seek>(zip_file: F, options: &ReadOptions) -> Result<Vec<ZipCentralDirectoryEntry>, Error> {
+    zip_read_archive(zip_file, options)
+        .map(|archive| archive.entries)
+}
+
+
+/// Reads the central directory of the archive according to the given options.
+///
+/// The central directory must contain exactly as many entries and be exactly as long as declared
+/// by the end-of-central-directory record (or its Zip64 counterpart), subject to
+/// [`ReadOptions::lenient_entry_count`]; otherwise, [`Error::EntryCountMismatch`] or
+/// [`Error::CentralDirectorySizeMismatch`] is returned.
+pub fn zip_read_archive<F: Read + Seek>(zip_file: F, options: &ReadOptions) -> Result<ZipArchive, Error> {
+    zip_read_archive_disks(zip_file, &[0], options)
+}
+
+
+/// Reads the central directory of an archive spanning multiple disks according to the given
+/// options.
+///
+/// `zip_file` is the concatenation of all disks of the archive (see [`MultiVolume`]) and
+/// `disk_offsets` contains the offset of the start of each disk within it (see
+/// [`MultiVolume::disk_offsets`]). The central directory may extend across multiple disks. All
+/// offsets in the result are relative to the start of `zip_file`; otherwise, this function behaves
+/// like [`zip_read_archive`].
+pub fn zip_read_archive_disks<F: Read + Seek>(mut zip_file: F, disk_offsets: &[u64], options: &ReadOptions) -> Result<ZipArchive, Error> {
+    let end_records = read_end_records(&mut zip_file, disk_offsets)?;
+    let central_directory_offset = end_records.central_directory_offset();
+    let declared_entries = end_records.total_entries();
+    let declared_size = end_records.central_directory_size();
+
+    // only a 16-bit entry count can have wrapped around
+    let lenient = options.lenient_entry_count && end_records.zip64_eocd.is_none();
+
+    // now we can read out the files
+    zip_file.seek(SeekFrom::Start(central_directory_offset))?;
+    let mut file_names = Vec::new();
+    let mut file_header_loc = central_directory_offset;
+    loop {
+        let found_size = file_header_loc - central_directory_offset;
+        let found_entries: u64 = file_names.len().try_into().unwrap();
+        if lenient {
+            if found_size >= declared_size {
+                break;
+            }
+        } else if found_entries == declared_entries {
+            break;
+        }
+
+        let signature = zip_file.read_u32_le()?;
+        if signature != CentralDirectoryEntry::signature() {
+            if lenient {
+                return Err(Error::CentralDirectorySizeMismatch {
+                    declared: declared_size,
+                    found: found_size,
+                });
+            }
+            return Err(Error::EntryCountMismatch {
+                declared: declared_entries,
+                found: found_entries,
+            });
+        }
+        let cdh = CentralDirectoryEntry::read_after_signature(&mut zip_file)?;
+        file_names.push(ZipCentralDirectoryEntry::resolve(cdh, disk_offsets, file_header_loc, end_records.preamble_length)?);
+        file_header_loc = zip_file.stream_position()?;
     }
 
-    // read EoCD
-    let eocd = EndOfCentralDirectory::read_after_signature(&mut zip_file)?;
-    if eocd.disk_no != 0 {
+    let found_entries: u64 = file_names.len().try_into().unwrap();
+    let entries_match = if lenient {
+        found_entries & 0xFFFF == declared_entries & 0xFFFF
+    } else {
+        found_entries == declared_entries
+    };
+    if !entries_match {
+        return Err(Error::EntryCountMismatch {
+            declared: declared_entries,
+            found: found_entries,
+        });
+    }
+    let found_size = file_header_loc - central_directory_offset;
+    if found_size != declared_size {
+        return Err(Error::CentralDirectorySizeMismatch {
+            declared: declared_size,
+            found: found_size,
+        });
+    }
+
+    Ok(ZipArchive {
+        entries: file_names,
+        preamble_length: end_records.preamble_length,
+        central_directory_offset,
+        comment: end_records.eocd.comment,
+    })
+}
+
+
+/// Prepends data, such as a shebang line or a self-extractor stub, to an archive.
+///
+/// The archive is read from `input` and the result is written to `output`. The offsets stored in the
+/// central directory and end records are adjusted so that the result is a valid archive in which
+/// the prepended data is not considered [a preamble](ZipArchive::preamble_length); Zip64 records
+/// and extra fields are added if the offsets no longer fit into their 32-bit fields. An existing
+/// preamble of the input archive is retained and its length is taken into account as well.
+pub fn zip_prepend<R: Read + Seek, W: Write>(mut input: R, mut output: W, stub: &[u8], options: &ReadOptions) -> Result<(), Error> {
+    let archive = zip_read_archive(&mut input, options)?;
+    let shift: u64 = stub.len().try_into().unwrap();
+
+    let entries = entries_with_local_header_offsets(&archive.entries, shift)?;
+    let new_central_directory_offset = archive.central_directory_offset.checked_add(shift)
+        .ok_or(Error::FieldTooLong)?;
+
+    // write out the stub, everything up to the central directory, and the new central directory
+    output.write_all(stub)?;
+    input.seek(SeekFrom::Start(0))?;
+    let copied = io::copy(&mut (&mut input).take(archive.central_directory_offset), &mut output)?;
+    if copied != archive.central_directory_offset {
+        return Err(Error::Io(io::ErrorKind::UnexpectedEof.into()));
+    }
+    write_central_directory(&mut output, &entries, new_central_directory_offset, &archive.comment)?;
+    output.flush()?;
+
+    Ok(())
+}
+
+
+/// Returns the central directory entries of the given entries, with their local header offsets set
+/// to their [resolved values](ZipCentralDirectoryEntry::local_header_offset) plus `shift`.
+fn entries_with_local_header_offsets(entries: &[ZipCentralDirectoryEntry], shift: u64) -> Result<Vec<CentralDirectoryEntry>, Error> {
+    let mut new_entries = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let new_offset = entry.local_header_offset.checked_add(shift)
+            .ok_or(Error::FieldTooLong)?;
+        let mut new_entry = entry.entry.clone();
+        set_local_header_offset(&mut new_entry, new_offset)?;
+        new_entries.push(new_entry);
+    }
+    Ok(new_entries)
+}
+
+
+/// Replaces the central directory and the end records of an archive.
+///
+/// The entries of `archive`, which may have been modified, reordered or removed since it has been
+/// read, are written as a new central directory at the archive's [central directory
+/// offset](ZipArchive::central_directory_offset), i.e. directly after the data of the entries. It
+/// is followed by the end records, which carry the archive's comment, and the file is truncated or
+/// extended to end directly after them. The data of the entries is not touched; the data of
+/// removed entries remains in the file.
+///
+/// The local header offset of each entry is taken from
+/// [`ZipCentralDirectoryEntry::local_header_offset`], which means that a
+/// [preamble](ZipArchive::preamble_length) becomes part of the archive; all other values are taken
+/// from [`ZipCentralDirectoryEntry::entry`]. Zip64 records and extra fields are written if the
+/// values no longer fit into their regular fields. Afterwards, the offsets in `archive` no longer
+/// refer to the central directory entries; read the archive again if they are needed.
+///
+/// Only archives consisting of a single disk can be rewritten; if an entry or its central directory
+/// entry is located on a different disk, [`Error::SpannedArchive`] is returned.
+pub fn zip_rewrite_central_directory<F: Seek + Write + SetLen>(mut zip_file: F, archive: &ZipArchive) -> Result<(), Error> {
+    ensure_single_disk(archive)?;
+    let entries = entries_with_local_header_offsets(&archive.entries, 0)?;
+
+    zip_file.seek(SeekFrom::Start(archive.central_directory_offset))?;
+    let mut writer = BufWriter::new(&mut zip_file);
+    let written = write_central_directory(&mut writer, &entries, archive.central_directory_offset, &archive.comment)?;
+    writer.flush()?;
+    drop(writer);
+
+    zip_file.set_len(archive.central_directory_offset + written)?;
+    zip_file.flush()?;
+    Ok(())
+}
+
+
+/// Returns [`Error::SpannedArchive`] if any of the entries of the archive or their central directory
+/// entries are located on a disk other than the first.
+fn ensure_single_disk(archive: &ZipArchive) -> Result<(), Error> {
+    if archive.entries.iter().any(|e| e.disk != 0 || e.disk_number_start != 0) {
         return Err(Error::SpannedArchive);
     }
-    let mut zip64_central_directory_loc: Option<u64> = None;
-    if eocd.should_check_zip64() {
-        // go back to EoCD start
-        lookback_for_signature(&mut zip_file, EndOfCentralDirectory::signature())?;
-
-        // try to find Zip64 EoCD locator
-        let zip64_eocd_loc_found = lookback_for_signature(&mut zip_file, Zip64EndOfCentralDirectoryLocator::signature())?;
-        if zip64_eocd_loc_found {
-            let zip64_eocd_loc = Zip64EndOfCentralDirectoryLocator::read_after_signature(&mut zip_file)?;
-            if zip64_eocd_loc.disk_no != 0 || zip64_eocd_loc.total_disks != 1 {
-                return Err(Error::SpannedArchive);
+    Ok(())
+}
+
+
+/// Adds a new file to an archive.
+///
+/// The data of the new entry is read from `data` until its end, compressed as specified in
+/// `options` and written, preceded by its local file header, where the central directory of
+/// `archive`, which must have been read from `zip_file`, is currently located. The entry is given
+/// the name `new_name`, which is encoded in the same manner as by [`zip_rename`], the Unix mode
+/// and time of last modification from `options` as well as the CRC-32 checksum and the sizes of
+/// the data. Zip64 extra fields are written if the sizes or the offset do not fit into their
+/// 32-bit fields; the local header reserves room for one unless the
+/// [size hint](NewEntryOptions::size_hint) in `options` rules this out.
+///
+/// The central directory, including the new entry and retaining all attributes of the existing
+/// ones, is then rewritten directly after the new entry using [`zip_rewrite_central_directory`]
+/// and `archive` is updated by reading the archive anew. If writing the new entry fails, an
+/// attempt is made to restore the previous central directory.
+///
+/// The time of last modification is stored in the MS-DOS date and time fields in UTC, as well as in
+/// an extended timestamp extra field. No check is performed whether another entry already has the
+/// new name. Only archives consisting of a single disk can be appended to; otherwise,
+/// [`Error::SpannedArchive`] is returned before the file is modified.
+pub fn zip_append<F, R>(mut zip_file: F, archive: &mut ZipArchive, new_name: &str, data: R, options: &NewEntryOptions) -> Result<(), Error>
+    where
+        F: Read + Seek + Write + SetLen,
+        R: Read,
+{
+    ensure_single_disk(archive)?;
+
+    let (new_entry, end_offset) = match write_entry(&mut zip_file, archive.central_directory_offset, new_name, UNIX_FILE_TYPE_REGULAR, data, options) {
+        Ok(ee) => ee,
+        Err(e) => {
+            // the new entry has overwritten the central directory; restore it on a best-effort basis
+            let _ = zip_rewrite_central_directory(&mut zip_file, archive);
+            return Err(e);
+        },
+    };
+    archive.entries.push(new_entry);
+    archive.central_directory_offset = end_offset;
+    zip_rewrite_central_directory(&mut zip_file, archive)?;
+
+    *archive = zip_read_archive(&mut zip_file, &ReadOptions::default())?;
+    Ok(())
+}
+
+
+/// Renames an entry of an archive.
+///
+/// The entry with the given index in `archive`, which must have been read from `zip_file`, is
+/// given the new name in both its central directory entry and its local file header. The name is
+/// stored in UTF-8; if it contains characters beyond ASCII, the entry is marked as such (see
+/// [`LANGUAGE_ENCODING_FLAG`]) and an Info-ZIP Unicode path extra field containing the name is
+/// stored as well. Otherwise, Unicode path extra fields are removed, as the name reads the same in
+/// every encoding. All other attributes of the entry are retained.
+///
+/// If the length of the local file header changes, the data following it is moved accordingly. The
+/// central directory is rewritten using [`zip_rewrite_central_directory`] and `archive` is updated
+/// by reading the archive anew.
+///
+/// No check is performed whether another entry already has the new name. If `index` is out of
+/// bounds, [`Error::NoSuchEntry`] is returned. Only archives consisting of a single disk can be
+/// modified; otherwise, [`Error::SpannedArchive`] is returned before the file is modified.
+///
+/// The operation is not atomic. Errors that can be detected up front, such as a name or extra
+/// field that is too long for the new local header or central directory, are returned before the
+/// file is modified; however, if an I/O error occurs while data is being moved, the archive is left
+/// in an inconsistent state.
+pub fn zip_rename<F: Read + Seek + Write + SetLen>(mut zip_file: F, archive: &mut ZipArchive, index: usize, new_name: &str) -> Result<(), Error> {
+    ensure_single_disk(archive)?;
+
+    let entry = archive.entries.get(index)
+        .ok_or(Error::NoSuchEntry(index))?;
+    let local_header = zip_read_local_header(&mut zip_file, entry)?;
+    let central_name = encode_entry_name(new_name, entry.entry.general_purpose_bit_flag, &entry.entry.extra_fields)?;
+    let local_name = encode_entry_name(new_name, local_header.header.general_purpose_bit_flag, &local_header.header.extra_fields)?;
+
+    let mut new_local_header = local_header.header.clone();
+    new_local_header.file_name = local_name.file_name;
+    new_local_header.general_purpose_bit_flag = local_name.general_purpose_bit_flag;
+    new_local_header.extra_fields = local_name.extra_fields;
+    let mut new_local_header_bytes = Vec::new();
+    new_local_header.write(&mut new_local_header_bytes)?;
+    let old_header_length = local_header.header.record_len();
+    let new_header_length = new_local_header.record_len();
+
+    // determine the new state of the archive before modifying the file
+    let mut new_archive = archive.clone();
+    let data_length = archive.central_directory_offset.checked_sub(local_header.data_offset)
+        .ok_or(Error::RecordTooLarge)?;
+    if new_header_length != old_header_length {
+        for other in &mut new_archive.entries {
+            if other.local_header_offset >= local_header.data_offset {
+                other.local_header_offset = other.local_header_offset - old_header_length + new_header_length;
             }
+        }
+        new_archive.central_directory_offset = new_archive.central_directory_offset - old_header_length + new_header_length;
+    }
+    let new_entry = &mut new_archive.entries[index];
+    new_entry.entry.file_name = central_name.file_name;
+    new_entry.entry.general_purpose_bit_flag = central_name.general_purpose_bit_flag;
+    new_entry.entry.extra_fields = central_name.extra_fields;
+
+    // make sure that the new central directory and end records can be written
+    let new_entries = entries_with_local_header_offsets(&new_archive.entries, 0)?;
+    write_central_directory(io::sink(), &new_entries, new_archive.central_directory_offset, &new_archive.comment)?;
 
-            // try to find Zip64 EoCD
-            zip_file.seek(SeekFrom::Start(zip64_eocd_loc.offset_on_disk))?;
+    if new_header_length != old_header_length {
+        // move everything between the local header and the central directory
+        let new_data_offset = local_header.offset + new_header_length;
+        move_data(&mut zip_file, local_header.data_offset, new_data_offset, data_length)?;
+    }
+    zip_file.seek(SeekFrom::Start(local_header.offset))?;
+    zip_file.write_all(&new_local_header_bytes)?;
+    zip_rewrite_central_directory(&mut zip_file, &new_archive)?;
+
+    *archive = zip_read_archive(&mut zip_file, &ReadOptions::default())?;
+    Ok(())
+}
+
+
+/// Returns the area of the file occupied by the given entry: the offset of its local file header
+/// and the offset directly after its data or, if it has one, its data descriptor.
+fn entry_extent<F: Read + Seek>(mut zip_file: F, entry: &ZipCentralDirectoryEntry) -> Result<(u64, u64), Error> {
+    let local_header = zip_read_local_header(&mut zip_file, entry)?;
+    let mut end = local_header.data_offset.checked_add(entry.compressed_size)
+        .ok_or(Error::RecordTooLarge)?;
+    if local_header.header.has_data_descriptor() {
+        let data_descriptor = zip_read_data_descriptor(&mut zip_file, entry, &local_header)?;
+        end += data_descriptor.record_len(has_zip64_data_descriptor(entry, &local_header));
+    }
+    Ok((local_header.offset, end))
+}
+
+
+/// Removes entries from an archive.
+///
+/// The entries of `archive`, which must have been read from `zip_file`, for which `remove` returns
+/// `true` are removed from the central directory, which is rewritten using
+/// [`zip_rewrite_central_directory`]. `archive` is then updated by reading the archive anew.
+///
+/// If `compact` is `false`, the local headers and data of the removed entries remain in the file
+/// without being referenced. If it is `true`, they are removed as well: the data following them is
+/// moved towards the start of the file, the local header offsets of the remaining entries are
+/// adjusted and the file shrinks accordingly. Data that does not belong to any entry, such as a
+/// preamble, is retained. If an entry to be removed overlaps with an entry to be kept,
+/// [`Error::OverlappingEntries`] is returned before the file is modified.
+///
+/// Only archives consisting of a single disk can be modified; otherwise, [`Error::SpannedArchive`]
+/// is returned before the file is modified.
+pub fn zip_delete<F, P>(mut zip_file: F, archive: &mut ZipArchive, mut remove: P, compact: bool) -> Result<(), Error>
+    where
+        F: Read + Seek + Write + SetLen,
+        P: FnMut(&ZipCentralDirectoryEntry) -> bool,
+{
+    // check this up front, as compacting moves data before the central directory is rewritten
+    ensure_single_disk(archive)?;
+
+    let (removed, mut kept): (Vec<ZipCentralDirectoryEntry>, Vec<ZipCentralDirectoryEntry>) = archive.entries
+        .iter()
+        .cloned()
+        .partition(|e| remove(e));
+    if removed.is_empty() {
+        return Ok(());
+    }
 
-            // try to read Zip64 EoCD
-            let zip64_eocd_sig = zip_file.read_u32_le()?;
-            if zip64_eocd_sig == Zip64EndOfCentralDirectory::signature() {
-                let zip64_eocd = Zip64EndOfCentralDirectory::read_after_signature(&mut zip_file)?;
-                if zip64_eocd.total_central_dir_entries != zip64_eocd.total_central_dir_entries_this_disk {
-                    return Err(Error::SpannedArchive);
+    let mut central_directory_offset = archive.central_directory_offset;
+    if compact {
+        // collect the areas to remove, merging adjacent and overlapping ones
+        let mut removed_extents = Vec::with_capacity(removed.len());
+        for entry in &removed {
+            let (start, end) = entry_extent(&mut zip_file, entry)?;
+            if end > central_directory_offset {
+                return Err(Error::RecordTooLarge);
+            }
+            removed_extents.push((start, end));
+        }
+        removed_extents.sort_unstable();
+        let mut merged_extents: Vec<(u64, u64)> = Vec::with_capacity(removed_extents.len());
+        for (start, end) in removed_extents {
+            match merged_extents.last_mut() {
+                Some((_, last_end)) if start <= *last_end => *last_end = (*last_end).max(end),
+                _ => merged_extents.push((start, end)),
+            }
+        }
+
+        // the entries to keep must not lose any of their bytes
+        for entry in &kept {
+            let (start, end) = entry_extent(&mut zip_file, entry)?;
+            let first_candidate = merged_extents.partition_point(|(_, e)| *e <= start);
+            if let Some((removed_start, _)) = merged_extents.get(first_candidate) {
+                if *removed_start < end {
+                    return Err(Error::OverlappingEntries);
                 }
-                zip64_central_directory_loc = Some(zip64_eocd.central_dir_offset_on_disk);
             }
         }
-    }
-    let central_directory_loc: u64 = if let Some(zcdl) = zip64_central_directory_loc {
-        zcdl
-    } else {
-        if eocd.total_central_dir_entries != eocd.total_central_dir_entries_this_disk {
-            return Err(Error::SpannedArchive);
+
+        // move the data between the removed areas towards the start of the file
+        let mut write_offset = merged_extents[0].0;
+        for (i, (_, end)) in merged_extents.iter().enumerate() {
+            let next_start = merged_extents.get(i + 1)
+                .map(|(s, _)| *s)
+                .unwrap_or(central_directory_offset);
+            move_data(&mut zip_file, *end, write_offset, next_start - end)?;
+            write_offset += next_start - end;
         }
-        eocd.central_dir_offset_on_disk.into()
-    };
-    zip_file.seek(SeekFrom::Start(central_directory_loc))?;
 
-    // now we can read out the files
-    let mut file_names = Vec::new();
-    loop {
-        let file_header_loc = zip_file.seek(SeekFrom::Current(0))?;
-        let signature = zip_file.read_u32_le()?;
-        if signature != CentralDirectoryEntry::signature() {
-            break;
+        // each offset shrinks by the total length of the areas removed before it
+        let mut cumulative_lengths = Vec::with_capacity(merged_extents.len());
+        let mut total_length: u64 = 0;
+        for (start, end) in &merged_extents {
+            total_length += end - start;
+            cumulative_lengths.push(total_length);
         }
-        let cdh = CentralDirectoryEntry::read_after_signature(&mut zip_file)?;
-        file_names.push(ZipCentralDirectoryEntry {
-            entry: cdh,
-            disk: 0,
-            offset: file_header_loc,
-        });
+        let shift_offset = |offset: u64| -> u64 {
+            match merged_extents.partition_point(|(_, end)| *end <= offset) {
+                0 => offset,
+                count => offset - cumulative_lengths[count - 1],
+            }
+        };
+        for entry in &mut kept {
+            entry.local_header_offset = shift_offset(entry.local_header_offset);
+        }
+        central_directory_offset = shift_offset(central_directory_offset);
     }
 
-    Ok(file_names)
+    let new_archive = ZipArchive {
+        entries: kept,
+        preamble_length: archive.preamble_length,
+        central_directory_offset,
+        comment: archive.comment.clone(),
+    };
+    zip_rewrite_central_directory(&mut zip_file, &new_archive)?;
+
+    *archive = zip_read_archive(&mut zip_file, &ReadOptions::default())?;
+    Ok(())
 }
 
 
-/// Modifies the attributes of a ZIP file entry to make it executable.
-pub fn zip_make_executable<F: Read + Seek + Write>(mut zip_file: F, entry_header_offset: u64) -> Result<(), Error> {
+/// The offset of the "version made by" field from the start of a central directory entry.
+const CREATOR_VERSION_OFFSET: u64 = 4; // signature
+
+/// The offset of the "external file attributes" field from the start of a central directory entry.
+const EXTERNAL_ATTRIBUTES_OFFSET: u64 =
+    4 // signature
+    + 2 // creator_version
+    + 2 // required_version
+    + 2 // general_purpose_bit_flag
+    + 2 // compression_method
+    + 2 // last_mod_file_time
+    + 2 // last_mod_file_date
+    + 4 // crc32
+    + 4 // compressed_size
+    + 4 // uncompressed_size
+    + 2 // file_name length
+    + 2 // extra_fields length
+    + 2 // file_comment length
+    + 2 // disk_number_start
+    + 2 // internal_attributes
+;
+
+/// The Unix file type bits of a mode.
+const UNIX_FILE_TYPE_MASK: u32 = 0o170000;
+
+/// The Unix file type value of a regular file.
+const UNIX_FILE_TYPE_REGULAR: u32 = 0o100000;
+
+/// The Unix file type value of a symbolic link.
+const UNIX_FILE_TYPE_SYMLINK: u32 = 0o120000;
+
+/// The Unix file type value of a directory.
+const UNIX_FILE_TYPE_DIRECTORY: u32 = 0o040000;
+
+/// The Unix file type value of a FIFO (named pipe).
+const UNIX_FILE_TYPE_FIFO: u32 = 0o010000;
+
+/// The Unix file type value of a character device.
+const UNIX_FILE_TYPE_CHARACTER_DEVICE: u32 = 0o020000;
+
+/// The Unix file type value of a block device.
+const UNIX_FILE_TYPE_BLOCK_DEVICE: u32 = 0o060000;
+
+/// The Unix file type value of a socket.
+const UNIX_FILE_TYPE_SOCKET: u32 = 0o140000;
+
+/// The longest symbolic link target that is read from an archive.
+const MAX_SYMLINK_TARGET_LENGTH: u64 = 0xFFFF;
+
+/// The bits of a Unix mode that can be changed using [`zip_set_unix_mode`]: the permission bits as
+/// well as the setuid, setgid and sticky bits.
+pub const UNIX_MODE_MASK: u16 = 0o7777;
+
+
+/// Returns the "version made by" field of an entry that has been modified to carry Unix file
+/// attributes.
+///
+/// The host system is retained if it always uses Unix file attributes (e.g. OS X) and changed to
+/// Unix otherwise.
+const fn unix_creator_version(creator_version: u16) -> u16 {
+    if HostSystem::from_creator_version(creator_version).uses_unix_attributes() {
+        creator_version
+    } else {
+        HostSystem::Unix.apply_to_creator_version(creator_version)
+    }
+}
+
+
+/// Reads the central directory entry at the given offset and passes it to the given function. If
+/// the function returns new values for the "version made by" and "external file attributes"
+/// fields, they are written back.
+fn modify_creator_and_attributes<F, M>(mut zip_file: F, entry_header_offset: u64, modify: M) -> Result<(), Error>
+    where
+        F: Read + Seek + Write,
+        M: FnOnce(&CentralDirectoryEntry) -> Result<Option<(u16, u32)>, Error>,
+{
     // seek to the given offset
     zip_file.seek(SeekFrom::Start(entry_header_offset))?;
 
@@ -235,90 +1069,904 @@ pub fn zip_make_executable<F: Read + Seek + Write>(mut zip_file: F, entry_header
     if signature != CentralDirectoryEntry::signature() {
         return Err(Error::IncorrectSignature);
     }
+    let entry = CentralDirectoryEntry::read_after_signature(&mut zip_file)?;
+
+    let (creator_version, external_attributes) = match modify(&entry)? {
+        Some(ce) => ce,
+        None => return Ok(()),
+    };
+
+    if creator_version != entry.creator_version {
+        zip_file.seek(SeekFrom::Start(entry_header_offset + CREATOR_VERSION_OFFSET))?;
+        zip_file.write_u16_le(creator_version)?;
+    }
+    if external_attributes != entry.external_attributes {
+        zip_file.seek(SeekFrom::Start(entry_header_offset + EXTERNAL_ATTRIBUTES_OFFSET))?;
+        zip_file.write_u32_le(external_attributes)?;
+    }
 
-    // set upper byte of creator version to 0x03 (Unix)
-    let mut creator_version = zip_file.read_u16_le()?;
-    creator_version = (creator_version & 0x00FF) | 0x0300;
-    zip_file.seek(SeekFrom::Current(-2))?;
-    zip_file.write_u16_le(creator_version)?;
-
-    // skip the intervening fields
-    zip_file.seek(SeekFrom::Current(
-        2 // required_version
-        + 2 // general_purpose_bit_flag
-        + 2 // compression_method
-        + 2 // last_mod_file_time
-        + 2 // last_mod_file_date
-        + 4 // crc32
-        + 4 // compressed_size
-        + 4 // uncompressed_size
-        + 2 // file_name length
-        + 2 // extra_fields length
-        + 2 // file_comment length
-        + 2 // disk_number_start
-        + 2 // internal_attributes
-    ))?;
-
-    // perform this change to upper byte pair of external attributes:
-    // 1. ensure bytes 0o170000 are set to 0o100000
-    // 2. ensure bits 0o000111 are set
-    let mut external_attributes = zip_file.read_u32_le()?;
-    external_attributes =
-        (external_attributes & ((0o170000 << 16) ^ 0xFFFF_FFFF))
-        | (0o100000 << 16)
-    ;
-    external_attributes |= 0o000111 << 16;
-    zip_file.seek(SeekFrom::Current(-4))?;
-    zip_file.write_u32_le(external_attributes)?;
-
-    // done
     Ok(())
 }
 
 
+/// Modifies the attributes of a ZIP file entry to make it executable.
+///
+/// Only entries of the kind [`EntryKind::File`] can be made executable; for other entries,
+/// [`Error::UnsuitableEntryKind`] is returned.
+///
+/// If the entry does not carry Unix file attributes yet, its mode is synthesized from the default
+/// [`UnixModeDefaults`] before the execute bits are added. Use
+/// [`zip_make_executable_with_defaults`] to choose different defaults.
+pub fn zip_make_executable<F: Read + Seek + Write>(zip_file: F, entry_header_offset: u64) -> Result<(), Error> {
+    zip_make_executable_with_defaults(zip_file, entry_header_offset, &UnixModeDefaults::default())
+}
+
+
+/// Modifies the attributes of a ZIP file entry to make it executable.
+///
+/// Only entries of the kind [`EntryKind::File`] can be made executable; for other entries,
+/// [`Error::UnsuitableEntryKind`] is returned.
+///
+/// If the entry does not carry Unix file attributes yet, its mode is synthesized from the given
+/// defaults (see [`UnixModeDefaults::synthesize_mode`]) before the execute bits are added.
+pub fn zip_make_executable_with_defaults<F: Read + Seek + Write>(zip_file: F, entry_header_offset: u64, defaults: &UnixModeDefaults) -> Result<(), Error> {
+    modify_creator_and_attributes(zip_file, entry_header_offset, |entry| {
+        let kind = EntryKind::from_entry(entry);
+        if kind != EntryKind::File {
+            return Err(Error::UnsuitableEntryKind(kind));
+        }
+
+        let host_system = HostSystem::from_creator_version(entry.creator_version);
+        let mut external_attributes = entry.external_attributes;
+        if host_system.unix_attributes(entry.external_attributes).is_none() {
+            // synthesize a full mode; otherwise, nobody could read the file
+            let mode = u32::from(defaults.synthesize_mode(entry));
+            external_attributes = (external_attributes & 0x0000FFFF) | (mode << 16);
+        }
+
+        // mark the entry as coming from Unix
+        let creator_version = unix_creator_version(entry.creator_version);
+
+        // perform this change to upper byte pair of external attributes:
+        // 1. ensure bytes 0o170000 are set to 0o100000
+        // 2. ensure bits 0o000111 are set
+        external_attributes =
+            (external_attributes & ((UNIX_FILE_TYPE_MASK << 16) ^ 0xFFFF_FFFF))
+            | (UNIX_FILE_TYPE_REGULAR << 16)
+        ;
+        external_attributes |= 0o000111 << 16;
+
+        Ok(Some((creator_version, external_attributes)))
+    })
+}
+
+
 /// Modifies the attributes of a ZIP file entry to make it not executable.
-pub fn zip_make_not_executable<F: Read + Seek + Write>(mut zip_file: F, entry_header_offset: u64) -> Result<(), Error> {
-    // seek to the given offset
-    zip_file.seek(SeekFrom::Start(entry_header_offset))?;
+pub fn zip_make_not_executable<F: Read + Seek + Write>(zip_file: F, entry_header_offset: u64) -> Result<(), Error> {
+    modify_creator_and_attributes(zip_file, entry_header_offset, |entry| {
+        let host_system = HostSystem::from_creator_version(entry.creator_version);
+        if host_system.unix_attributes(entry.external_attributes).is_none() {
+            // no Unix attributes, cannot be executable
+            return Ok(None);
+        }
 
-    // check for central directory entry
+        // remove 0o000111 from upper byte pair of external attributes (if necessary)
+        let external_attributes = entry.external_attributes & !(0o000111 << 16);
+        Ok(Some((entry.creator_version, external_attributes)))
+    })
+}
+
+
+/// Modifies the attributes of a ZIP file entry to give it the given Unix mode.
+///
+/// The entry is marked as having been created on Unix unless its host system already uses Unix
+/// file attributes. Of the given mode, only the bits in
+/// [`UNIX_MODE_MASK`] (permissions as well as setuid, setgid and sticky bits) are used; the file
+/// type stored in the entry is retained. If the entry does not have a Unix file type yet, it is
+/// marked as a directory if it is of the kind [`EntryKind::Directory`] and as a regular file
+/// otherwise.
+pub fn zip_set_unix_mode<F: Read + Seek + Write>(zip_file: F, entry_header_offset: u64, mode: u16) -> Result<(), Error> {
+    modify_creator_and_attributes(zip_file, entry_header_offset, |entry| {
+        let host_system = HostSystem::from_creator_version(entry.creator_version);
+        let creator_version = unix_creator_version(entry.creator_version);
+
+        let mut file_type = host_system.unix_attributes(entry.external_attributes)
+            .map(|ua| ua & UNIX_FILE_TYPE_MASK)
+            .unwrap_or(0);
+        if file_type == 0 {
+            file_type = if EntryKind::from_entry(entry) == EntryKind::Directory {
+                UNIX_FILE_TYPE_DIRECTORY
+            } else {
+                UNIX_FILE_TYPE_REGULAR
+            };
+        }
+
+        let unix_attributes = file_type | u32::from(mode & UNIX_MODE_MASK);
+        let external_attributes = (entry.external_attributes & 0x0000FFFF) | (unix_attributes << 16);
+        Ok(Some((creator_version, external_attributes)))
+    })
+}
+
+
+/// The local file header of a ZIP entry, along with the location of the entry's data.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct ZipLocalFileHeader {
+    /// The actual local file header.
+    pub header: LocalFileHeader,
+
+    /// The offset of the local file header from the beginning of the archive.
+    pub offset: u64,
+
+    /// The offset of the entry's data from the beginning of the archive, i.e. the offset directly
+    /// after the local file header.
+    pub data_offset: u64,
+}
+
+
+/// Reads the local file header of an entry.
+///
+/// Returns [`Error::IncorrectSignature`] if no local file header is found at the location given by
+/// the central directory entry.
+pub fn zip_read_local_header<F: Read + Seek>(mut zip_file: F, entry: &ZipCentralDirectoryEntry) -> Result<ZipLocalFileHeader, Error> {
+    zip_file.seek(SeekFrom::Start(entry.local_header_offset))?;
     let signature = zip_file.read_u32_le()?;
-    if signature != CentralDirectoryEntry::signature() {
+    if signature != LocalFileHeader::signature() {
         return Err(Error::IncorrectSignature);
     }
+    let header = LocalFileHeader::read_after_signature(&mut zip_file)?;
+    let data_offset = entry.local_header_offset + header.record_len();
 
-    // check upper byte of creator version against 0x03 (Unix)
-    let creator_version = zip_file.read_u16_le()?;
-    if (creator_version & 0xFF00) != 0x0300 {
-        // not Unix, cannot be executable
-        return Ok(());
+    Ok(ZipLocalFileHeader {
+        header,
+        offset: entry.local_header_offset,
+        data_offset,
+    })
+}
+
+
+/// Returns whether the data descriptor of the given entry contains 64-bit sizes, which is assumed to
+/// be the case if the central directory entry or the local file header has a Zip64 extended
+/// information extra field.
+fn has_zip64_data_descriptor(entry: &ZipCentralDirectoryEntry, local_header: &ZipLocalFileHeader) -> bool {
+    let has_zip64_extra = |extra_fields: &[u8]| RawExtraFieldIter::new(extra_fields)
+        .filter_map(|b| b.ok())
+        .any(|(tag, _block)| tag == Zip64ExtraField::tag());
+    has_zip64_extra(&entry.entry.extra_fields)
+        || has_zip64_extra(&local_header.header.extra_fields)
+}
+
+
+/// Reads the data descriptor following the data of an entry.
+///
+/// The local file header must have been obtained using [`zip_read_local_header`]. The data
+/// descriptor is assumed to contain 64-bit sizes if the central directory entry or the local file
+/// header has a Zip64 extended information extra field. No check is performed whether the entry
+/// is actually [marked](LocalFileHeader::has_data_descriptor) as having a data descriptor.
+pub fn zip_read_data_descriptor<F: Read + Seek>(
+    mut zip_file: F,
+    entry: &ZipCentralDirectoryEntry,
+    local_header: &ZipLocalFileHeader,
+) -> Result<DataDescriptor, Error> {
+    let zip64 = has_zip64_data_descriptor(entry, local_header);
+    let descriptor_offset = local_header.data_offset.checked_add(entry.compressed_size)
+        .ok_or(Error::RecordTooLarge)?;
+    zip_file.seek(SeekFrom::Start(descriptor_offset))?;
+    DataDescriptor::read(&mut zip_file, zip64)
+}
+
+
+/// Reads the target of a symbolic link entry.
+///
+/// The target is the data of the entry, which is read from behind its local file header. Only
+/// uncompressed ("stored") entries are supported.
+///
+/// This function does not verify whether the entry is actually marked as a symbolic link; use
+/// [`ZipCentralDirectoryEntry::is_symlink`] to check this.
+pub fn zip_read_symlink_target<F: Read + Seek>(mut zip_file: F, entry: &ZipCentralDirectoryEntry) -> Result<Vec<u8>, Error> {
+    if entry.entry.compression_method != 0 {
+        return Err(Error::UnsupportedCompressionMethod(entry.entry.compression_method));
+    }
+    if entry.compressed_size > MAX_SYMLINK_TARGET_LENGTH {
+        return Err(Error::FieldTooLong);
     }
 
-    // skip the intervening fields
-    zip_file.seek(SeekFrom::Current(
-        2 // required_version
-        + 2 // general_purpose_bit_flag
-        + 2 // compression_method
-        + 2 // last_mod_file_time
-        + 2 // last_mod_file_date
-        + 4 // crc32
-        + 4 // compressed_size
-        + 4 // uncompressed_size
-        + 2 // file_name length
-        + 2 // extra_fields length
-        + 2 // file_comment length
-        + 2 // disk_number_start
-        + 2 // internal_attributes
-    ))?;
-
-    // remove 0o000111 from upper byte pair of external attributes (if necessary)
-    let mut external_attributes = zip_file.read_u32_le()?;
-    if (external_attributes & (0o000111 << 16)) != 0 {
-        external_attributes &= !(0o000111 << 16);
-        zip_file.seek(SeekFrom::Current(-4))?;
-        zip_file.write_u32_le(external_attributes)?;
+    let local_header = zip_read_local_header(&mut zip_file, entry)?;
+
+    // read the target
+    zip_file.seek(SeekFrom::Start(local_header.data_offset))?;
+    let mut target = vec![0u8; entry.compressed_size.try_into().unwrap()];
+    zip_file.read_exact(&mut target)?;
+    Ok(target)
+}
+
+
+/// Modifies the attributes of a ZIP file entry to make it a symbolic link.
+///
+/// The data of the entry is then interpreted as the target of the link. Only uncompressed
+/// ("stored") entries can be made into symbolic links, as many extractors expect this. Entries that
+/// are neither of the kind [`EntryKind::File`] nor [`EntryKind::Symlink`] are refused with
+/// [`Error::UnsuitableEntryKind`].
+pub fn zip_make_symlink<F: Read + Seek + Write>(zip_file: F, entry_header_offset: u64) -> Result<(), Error> {
+    modify_creator_and_attributes(zip_file, entry_header_offset, |entry| {
+        let kind = EntryKind::from_entry(entry);
+        if kind != EntryKind::File && kind != EntryKind::Symlink {
+            return Err(Error::UnsuitableEntryKind(kind));
+        }
+        if entry.compression_method != 0 {
+            return Err(Error::UnsupportedCompressionMethod(entry.compression_method));
+        }
+
+        // mark the entry as coming from Unix
+        let creator_version = unix_creator_version(entry.creator_version);
+
+        // symbolic links are generally marked as accessible by everyone
+        let unix_attributes = UNIX_FILE_TYPE_SYMLINK | 0o777;
+        let external_attributes = (entry.external_attributes & 0x0000FFFF) | (unix_attributes << 16);
+        Ok(Some((creator_version, external_attributes)))
+    })
+}
+
+
+/// Modifies the attributes of a ZIP file entry to make it a regular file instead of a symbolic
+/// link.
+///
+/// The data of the entry (the former target of the link) becomes the contents of the file. The
+/// file is given the mode 0o644. Entries that are not symbolic links are not modified.
+pub fn zip_make_not_symlink<F: Read + Seek + Write>(zip_file: F, entry_header_offset: u64) -> Result<(), Error> {
+    modify_creator_and_attributes(zip_file, entry_header_offset, |entry| {
+        let host_system = HostSystem::from_creator_version(entry.creator_version);
+        let unix_attributes = match host_system.unix_attributes(entry.external_attributes) {
+            Some(ua) => ua,
+            None => {
+                // no Unix attributes, cannot be a symbolic link
+                return Ok(None);
+            },
+        };
+        if unix_attributes & UNIX_FILE_TYPE_MASK != UNIX_FILE_TYPE_SYMLINK {
+            return Ok(None);
+        }
+
+        let unix_attributes = UNIX_FILE_TYPE_REGULAR | 0o644;
+        let external_attributes = (entry.external_attributes & 0x0000FFFF) | (unix_attributes << 16);
+        Ok(Some((entry.creator_version, external_attributes)))
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use crate::extra_field::with_length_prefix;
+
+    const PREAMBLE: &[u8] = b"#!/bin/sh\nexit 0\n";
+
+    fn entry_contents(index: usize) -> Vec<u8> {
+        format!("contents of file {} ", index).repeat(10 * (index + 1)).into_bytes()
     }
 
-    // done
-    Ok(())
+    /// Writes an archive with the given number of entries, alternately stored and deflated, after a
+    /// preamble.
+    fn archive_with_entries(count: usize) -> Cursor<Vec<u8>> {
+        let mut cursor = Cursor::new(PREAMBLE.to_vec());
+        cursor.seek(SeekFrom::End(0)).unwrap();
+        write_entries(cursor, count)
+    }
+
+    /// Writes the entries of [`archive_with_entries`] at the current position of the cursor.
+    fn write_entries(cursor: Cursor<Vec<u8>>, count: usize) -> Cursor<Vec<u8>> {
+        let mut writer = ZipWriter::new(cursor).unwrap();
+        for i in 0..count {
+            let options = NewEntryOptions {
+                compression_method: if i % 2 == 0 { CompressionMethod::Stored } else { CompressionMethod::Deflated },
+                ..Default::default()
+            };
+            writer.add_file(&format!("file{}.txt", i), entry_contents(i).as_slice(), &options).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    fn entry_names(archive: &ZipArchive) -> Vec<String> {
+        archive.entries.iter()
+            .map(|e| best_effort_decode(&e.entry.file_name))
+            .collect()
+    }
+
+    fn assert_entries_intact(zip_file: &mut Cursor<Vec<u8>>, archive: &ZipArchive) {
+        assert_eq!(zip_check(&mut *zip_file).unwrap(), Vec::new());
+        for entry in &archive.entries {
+            let name = best_effort_decode(&entry.entry.file_name);
+            let index: usize = name.chars().filter(char::is_ascii_digit).collect::<String>().parse().unwrap();
+            let mut data = Vec::new();
+            zip_read_entry_data(&mut *zip_file, entry, &mut data).unwrap();
+            assert_eq!(data, entry_contents(index), "{}", name);
+        }
+        assert!(zip_file.get_ref().starts_with(PREAMBLE));
+    }
+
+    /// Writes an archive with the given number of entries like [`archive_with_entries`] and prepends
+    /// the preamble afterwards without adjusting any offsets, like `cat stub archive.zip`.
+    fn concatenated_archive_with_entries(count: usize) -> Cursor<Vec<u8>> {
+        let mut concatenated = PREAMBLE.to_vec();
+        concatenated.extend_from_slice(&write_entries(Cursor::new(Vec::new()), count).into_inner());
+        Cursor::new(concatenated)
+    }
+
+    #[test]
+    fn test_read_concatenated_preamble() {
+        let mut zip_file = concatenated_archive_with_entries(3);
+        let archive = zip_read_archive(&mut zip_file, &ReadOptions::default()).unwrap();
+        assert_eq!(archive.preamble_length, PREAMBLE.len() as u64);
+        assert_eq!(entry_names(&archive), ["file0.txt", "file1.txt", "file2.txt"]);
+        assert_eq!(archive.entries[0].local_header_offset, PREAMBLE.len() as u64);
+        assert_entries_intact(&mut zip_file, &archive);
+
+        let entries = zip_get_files(&mut zip_file).unwrap();
+        assert_eq!(entries, archive.entries);
+    }
+
+    #[test]
+    fn test_read_overflowing_preamble() {
+        let mut zip_file = archive_with_entries(1);
+        let mut archive = zip_read_archive(&mut zip_file, &ReadOptions::default()).unwrap();
+        archive.entries[0].local_header_offset = u64::MAX - 5;
+        zip_rewrite_central_directory(&mut zip_file, &archive).unwrap();
+
+        // the offsets are shifted by the preamble length, which makes the entry's offset overflow
+        let mut concatenated = PREAMBLE.to_vec();
+        concatenated.extend_from_slice(zip_file.get_ref());
+        let result = zip_read_archive(Cursor::new(concatenated), &ReadOptions::default());
+        assert!(matches!(result, Err(Error::RecordTooLarge)), "{:?}", result);
+    }
+
+    #[test]
+    fn test_rewrite_central_directory() {
+        let mut zip_file = archive_with_entries(4);
+        let mut archive = zip_read_archive(&mut zip_file, &ReadOptions::default()).unwrap();
+        let original = archive.clone();
+
+        // reorder, remove and modify entries
+        archive.entries.reverse();
+        archive.entries.remove(1);
+        archive.entries[0].entry.file_comment = b"the last one".to_vec();
+        archive.comment = b"rewritten".to_vec();
+        zip_rewrite_central_directory(&mut zip_file, &archive).unwrap();
+
+        let rewritten = zip_read_archive(&mut zip_file, &ReadOptions::default()).unwrap();
+        assert_eq!(entry_names(&rewritten), ["file3.txt", "file1.txt", "file0.txt"]);
+        assert_eq!(rewritten.entries[0].entry.file_comment, b"the last one");
+        assert_eq!(rewritten.comment, b"rewritten");
+        for (entry, original_index) in rewritten.entries.iter().zip([3, 1, 0]) {
+            assert_eq!(entry.local_header_offset, original.entries[original_index].local_header_offset);
+            assert_eq!(entry.entry.crc32, original.entries[original_index].entry.crc32);
+        }
+
+        // the data is not touched and the file ends after the new end records
+        assert_eq!(rewritten.central_directory_offset, original.central_directory_offset);
+        let central_directory_length: usize = rewritten.entries.iter()
+            .map(|e| 46 + e.entry.file_name.len() + e.entry.extra_fields.len() + e.entry.file_comment.len())
+            .sum();
+        let expected_length = original.central_directory_offset as usize + central_directory_length + 22 + 9;
+        assert_eq!(zip_file.get_ref().len(), expected_length);
+        assert_entries_intact(&mut zip_file, &rewritten);
+
+        // rewriting without changes is idempotent
+        let before = zip_file.get_ref().clone();
+        zip_rewrite_central_directory(&mut zip_file, &rewritten).unwrap();
+        assert_eq!(zip_file.get_ref(), &before);
+    }
+
+    #[test]
+    fn test_delete_compact() {
+        let mut zip_file = archive_with_entries(5);
+        let mut archive = zip_read_archive(&mut zip_file, &ReadOptions::default()).unwrap();
+        let original = archive.clone();
+        let original_length = zip_file.get_ref().len();
+
+        zip_delete(&mut zip_file, &mut archive, |e| e.entry.file_name == b"file1.txt" || e.entry.file_name == b"file3.txt", true).unwrap();
+        assert_eq!(entry_names(&archive), ["file0.txt", "file2.txt", "file4.txt"]);
+        assert_entries_intact(&mut zip_file, &archive);
+
+        // the remaining entries move back by the lengths of the removed ones before them
+        let extent_length = |i: usize| original.entries[i + 1].local_header_offset - original.entries[i].local_header_offset;
+        assert_eq!(archive.entries[0].local_header_offset, original.entries[0].local_header_offset);
+        assert_eq!(archive.entries[0].local_header_offset, PREAMBLE.len() as u64);
+        assert_eq!(archive.entries[1].local_header_offset, original.entries[2].local_header_offset - extent_length(1));
+        assert_eq!(archive.entries[2].local_header_offset, original.entries[4].local_header_offset - extent_length(1) - extent_length(3));
+        assert!(zip_file.get_ref().len() < original_length - usize::try_from(extent_length(1) + extent_length(3)).unwrap());
+
+        // removing the first and last entries as well leaves only the preamble and the end records
+        zip_delete(&mut zip_file, &mut archive, |e| e.entry.file_name != b"file2.txt", true).unwrap();
+        assert_eq!(entry_names(&archive), ["file2.txt"]);
+        assert_eq!(archive.entries[0].local_header_offset, PREAMBLE.len() as u64);
+        assert_entries_intact(&mut zip_file, &archive);
+    }
+
+    #[test]
+    fn test_delete_without_compacting() {
+        let mut zip_file = archive_with_entries(3);
+        let mut archive = zip_read_archive(&mut zip_file, &ReadOptions::default()).unwrap();
+        let original = archive.clone();
+
+        zip_delete(&mut zip_file, &mut archive, |e| e.entry.file_name == b"file1.txt", false).unwrap();
+        assert_eq!(entry_names(&archive), ["file0.txt", "file2.txt"]);
+        assert_eq!(archive.entries[0].local_header_offset, original.entries[0].local_header_offset);
+        assert_eq!(archive.entries[1].local_header_offset, original.entries[2].local_header_offset);
+        assert_eq!(archive.central_directory_offset, original.central_directory_offset);
+        assert_entries_intact(&mut zip_file, &archive);
+
+        // nothing to remove
+        let before = zip_file.get_ref().clone();
+        zip_delete(&mut zip_file, &mut archive, |_| false, true).unwrap();
+        assert_eq!(zip_file.get_ref(), &before);
+    }
+    fn unicode_path(extra_fields: &[u8]) -> Option<UnicodePathExtraField> {
+        RawExtraFieldIter::new(extra_fields)
+            .map(|block_res| block_res.unwrap())
+            .find(|(tag, _)| *tag == UnicodePathExtraField::tag())
+            .map(|(_, block)| UnicodePathExtraField::read_after_tag(with_length_prefix(block).as_slice()).unwrap())
+    }
+
+    #[test]
+    fn test_rename_changing_length() {
+        let mut zip_file = archive_with_entries(3);
+        let mut archive = zip_read_archive(&mut zip_file, &ReadOptions::default()).unwrap();
+        let original = archive.clone();
+
+        // the entries after the renamed one move by the difference in length
+        zip_rename(&mut zip_file, &mut archive, 1, "longer/name/of/file1.txt").unwrap();
+        assert_eq!(entry_names(&archive), ["file0.txt", "longer/name/of/file1.txt", "file2.txt"]);
+        assert_eq!(archive.entries[0].local_header_offset, original.entries[0].local_header_offset);
+        assert_eq!(archive.entries[1].local_header_offset, original.entries[1].local_header_offset);
+        assert_eq!(archive.entries[2].local_header_offset, original.entries[2].local_header_offset + 15);
+        assert_eq!(archive.central_directory_offset, original.central_directory_offset + 15);
+        let local_header = zip_read_local_header(&mut zip_file, &archive.entries[1]).unwrap();
+        assert_eq!(local_header.header.file_name, b"longer/name/of/file1.txt");
+        assert_entries_intact(&mut zip_file, &archive);
+
+        zip_rename(&mut zip_file, &mut archive, 1, "f1").unwrap();
+        assert_eq!(entry_names(&archive), ["file0.txt", "f1", "file2.txt"]);
+        assert_eq!(archive.entries[2].local_header_offset, original.entries[2].local_header_offset - 7);
+        assert_eq!(archive.central_directory_offset, original.central_directory_offset - 7);
+        let local_header = zip_read_local_header(&mut zip_file, &archive.entries[1]).unwrap();
+        assert_eq!(local_header.header.file_name, b"f1");
+        assert_entries_intact(&mut zip_file, &archive);
+
+        // renaming the last entry only moves the central directory
+        zip_rename(&mut zip_file, &mut archive, 2, "file2.text").unwrap();
+        assert_eq!(entry_names(&archive), ["file0.txt", "f1", "file2.text"]);
+        assert_eq!(archive.entries[2].local_header_offset, original.entries[2].local_header_offset - 7);
+        assert_entries_intact(&mut zip_file, &archive);
+    }
+
+    #[test]
+    fn test_rename_non_ascii() {
+        let mut zip_file = archive_with_entries(2);
+        let mut archive = zip_read_archive(&mut zip_file, &ReadOptions::default()).unwrap();
+
+        zip_rename(&mut zip_file, &mut archive, 0, "dätei0.txt").unwrap();
+        let entry = &archive.entries[0];
+        let local_header = zip_read_local_header(&mut zip_file, entry).unwrap();
+        assert_eq!(entry.entry.file_name, "dätei0.txt".as_bytes());
+        assert_eq!(local_header.header.file_name, "dätei0.txt".as_bytes());
+        assert_ne!(entry.entry.general_purpose_bit_flag & LANGUAGE_ENCODING_FLAG, 0);
+        assert_ne!(local_header.header.general_purpose_bit_flag & LANGUAGE_ENCODING_FLAG, 0);
+        assert_eq!(unicode_path(&entry.entry.extra_fields).unwrap().unicode_name, "dätei0.txt".as_bytes());
+        assert_eq!(unicode_path(&local_header.header.extra_fields).unwrap().unicode_name, "dätei0.txt".as_bytes());
+        assert_eq!(decode_entry_name(&entry.entry, None), "dätei0.txt");
+        assert_entries_intact(&mut zip_file, &archive);
+
+        // back to ASCII, which drops the Unicode path extra field
+        zip_rename(&mut zip_file, &mut archive, 0, "file0.txt").unwrap();
+        let entry = &archive.entries[0];
+        let local_header = zip_read_local_header(&mut zip_file, entry).unwrap();
+        assert_eq!(unicode_path(&entry.entry.extra_fields), None);
+        assert_eq!(unicode_path(&local_header.header.extra_fields), None);
+        assert_entries_intact(&mut zip_file, &archive);
+    }
+
+    #[test]
+    fn test_rename_out_of_bounds() {
+        let mut zip_file = archive_with_entries(2);
+        let mut archive = zip_read_archive(&mut zip_file, &ReadOptions::default()).unwrap();
+        let before = zip_file.get_ref().clone();
+
+        let result = zip_rename(&mut zip_file, &mut archive, 2, "file2.txt");
+        assert!(matches!(result, Err(Error::NoSuchEntry(2))), "{:?}", result);
+        assert_eq!(zip_file.get_ref(), &before);
+    }
+
+    /// Writes a bare central directory with the given number of entries, without any local headers
+    /// or data, and returns it along with a matching end-of-central-directory record.
+    fn bare_central_directory(count: usize) -> (Vec<u8>, crate::zip_format::EndOfCentralDirectory) {
+        let mut central_directory = Vec::new();
+        for i in 0..count {
+            let entry = CentralDirectoryEntry {
+                file_name: format!("file{}.txt", i).into_bytes(),
+                ..Default::default()
+            };
+            entry.write(&mut central_directory).unwrap();
+        }
+        let eocd = crate::zip_format::EndOfCentralDirectory {
+            total_central_dir_entries_this_disk: count as u16,
+            total_central_dir_entries: count as u16,
+            central_directory_size: central_directory.len().try_into().unwrap(),
+            ..Default::default()
+        };
+        (central_directory, eocd)
+    }
+
+    fn with_end_record(central_directory: &[u8], eocd: &crate::zip_format::EndOfCentralDirectory) -> Cursor<Vec<u8>> {
+        let mut zip_file = central_directory.to_vec();
+        eocd.write(&mut zip_file).unwrap();
+        Cursor::new(zip_file)
+    }
+
+    #[test]
+    fn test_read_declared_entry_count_and_size() {
+        let (central_directory, eocd) = bare_central_directory(3);
+        let size = u64::from(eocd.central_directory_size);
+        let archive = zip_read_archive(with_end_record(&central_directory, &eocd), &ReadOptions::default()).unwrap();
+        assert_eq!(entry_names(&archive), ["file0.txt", "file1.txt", "file2.txt"]);
+
+        // more entries declared than present: the end record is reached instead of another entry
+        let mut more = eocd.clone();
+        more.total_central_dir_entries_this_disk = 4;
+        more.total_central_dir_entries = 4;
+        let result = zip_read_archive(with_end_record(&central_directory, &more), &ReadOptions::default());
+        assert!(matches!(result, Err(Error::EntryCountMismatch { declared: 4, found: 3 })), "{:?}", result);
+
+        // fewer entries declared than present: parsing stops at the declared count
+        let mut fewer = eocd.clone();
+        fewer.total_central_dir_entries_this_disk = 2;
+        fewer.total_central_dir_entries = 2;
+        let result = zip_read_archive(with_end_record(&central_directory, &fewer), &ReadOptions::default());
+        let entry_length = size / 3;
+        assert!(
+            matches!(result, Err(Error::CentralDirectorySizeMismatch { declared, found }) if declared == size && found == 2 * entry_length),
+            "{:?}", result,
+        );
+
+        // the right number of entries but the wrong size
+        let mut larger = eocd;
+        larger.central_directory_size += 1;
+        let result = zip_read_archive(with_end_record(&central_directory, &larger), &ReadOptions::default());
+        assert!(
+            matches!(result, Err(Error::CentralDirectorySizeMismatch { declared, found }) if declared == size + 1 && found == size),
+            "{:?}", result,
+        );
+    }
+
+    #[test]
+    fn test_read_lenient_entry_count() {
+        // the entry count has wrapped around from 65537 to 1
+        let (central_directory, eocd) = bare_central_directory(0x1_0001);
+        assert_eq!(eocd.total_central_dir_entries, 1);
+        let lenient = ReadOptions { lenient_entry_count: true };
+
+        let result = zip_read_archive(with_end_record(&central_directory, &eocd), &ReadOptions::default());
+        assert!(matches!(result, Err(Error::CentralDirectorySizeMismatch { .. })), "{:?}", result);
+
+        let archive = zip_read_archive(with_end_record(&central_directory, &eocd), &lenient).unwrap();
+        assert_eq!(archive.entries.len(), 0x1_0001);
+        assert_eq!(best_effort_decode(&archive.entries[0x1_0000].entry.file_name), "file65536.txt");
+
+        // the count must still agree in its lowest 16 bits
+        let mut wrong = eocd;
+        wrong.total_central_dir_entries_this_disk = 2;
+        wrong.total_central_dir_entries = 2;
+        let result = zip_read_archive(with_end_record(&central_directory, &wrong), &lenient);
+        assert!(matches!(result, Err(Error::EntryCountMismatch { declared: 2, found: 0x1_0001 })), "{:?}", result);
+    }
+
+    #[test]
+    fn test_read_central_directory_running_to_eof() {
+        let (mut central_directory, eocd) = bare_central_directory(3);
+
+        // make the name of the last entry extend past the end of the file
+        let last_entry_start = central_directory.len() / 3 * 2;
+        central_directory[last_entry_start + 28..last_entry_start + 30].copy_from_slice(&u16::MAX.to_le_bytes());
+
+        for options in [ReadOptions::default(), ReadOptions { lenient_entry_count: true }] {
+            let result = zip_read_archive(with_end_record(&central_directory, &eocd), &options);
+            assert!(
+                matches!(&result, Err(Error::Io(e)) if e.kind() == io::ErrorKind::UnexpectedEof),
+                "{:?}", result,
+            );
+        }
+    }
+
+    /// A file consisting of `padding` zero bytes followed by `data`, without keeping the zeros in
+    /// memory.
+    ///
+    /// Writing is only supported sequentially from the start; bytes written within the padding are
+    /// assumed to be zeros and are dropped.
+    struct ZeroPadded {
+        padding: u64,
+        data: Vec<u8>,
+        position: u64,
+    }
+    impl ZeroPadded {
+        fn new(padding: u64, data: Vec<u8>) -> Self {
+            Self { padding, data, position: 0 }
+        }
+
+        fn len(&self) -> u64 {
+            self.padding + self.data.len() as u64
+        }
+    }
+    impl Read for ZeroPadded {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = if self.position < self.padding {
+                let n = buf.len().min(usize::try_from(self.padding - self.position).unwrap_or(usize::MAX));
+                buf[..n].fill(0);
+                n
+            } else {
+                let start = usize::try_from(self.position - self.padding).unwrap().min(self.data.len());
+                (&self.data[start..]).read(buf)?
+            };
+            self.position += n as u64;
+            Ok(n)
+        }
+    }
+    impl Seek for ZeroPadded {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.position = match pos {
+                SeekFrom::Start(p) => p,
+                SeekFrom::End(d) => self.len().checked_add_signed(d).unwrap(),
+                SeekFrom::Current(d) => self.position.checked_add_signed(d).unwrap(),
+            };
+            Ok(self.position)
+        }
+    }
+    impl Write for ZeroPadded {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            assert!(
+                (self.data.is_empty() && self.position <= self.padding) || self.position == self.len(),
+                "only sequential writes are supported",
+            );
+            let skipped = usize::try_from(self.padding.saturating_sub(self.position)).unwrap_or(usize::MAX).min(buf.len());
+            self.data.extend_from_slice(&buf[skipped..]);
+            self.position += buf.len() as u64;
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn zip64_extra_field(entry: &ZipCentralDirectoryEntry) -> Option<Zip64ExtraField> {
+        let mut fields = RawExtraFieldIter::new(&entry.entry.extra_fields)
+            .map(Result::unwrap)
+            .filter(|(tag, _block)| *tag == Zip64ExtraField::tag());
+        let field = fields.next()
+            .map(|(_tag, block)| Zip64ExtraField::read_after_tag(
+                with_length_prefix(block).as_slice(),
+                entry.entry.uncompressed_size,
+                entry.entry.compressed_size,
+                entry.entry.local_header_relative_offset,
+                entry.entry.disk_number_start,
+            ).unwrap());
+        assert!(fields.next().is_none(), "more than one Zip64 extra field");
+        field
+    }
+
+    fn assert_prepended_entries_intact<F: Read + Seek>(mut zip_file: F, archive: &ZipArchive, original: &ZipArchive, shift: u64) {
+        assert_eq!(archive.preamble_length, 0);
+        assert_eq!(entry_names(archive), entry_names(original));
+        for (index, (entry, original_entry)) in archive.entries.iter().zip(&original.entries).enumerate() {
+            assert_eq!(entry.local_header_offset, original_entry.local_header_offset + shift);
+            let mut data = Vec::new();
+            zip_read_entry_data(&mut zip_file, entry, &mut data).unwrap();
+            assert_eq!(data, entry_contents(index));
+        }
+    }
+
+    #[test]
+    fn test_prepend() {
+        const STUB: &[u8] = b"MZ self-extractor stub\n";
+        let shift = STUB.len() as u64;
+        for mut zip_file in [archive_with_entries(3), concatenated_archive_with_entries(3)] {
+            let original = zip_read_archive(&mut zip_file, &ReadOptions::default()).unwrap();
+
+            let mut prepended = Cursor::new(Vec::new());
+            zip_prepend(&mut zip_file, &mut prepended, STUB, &ReadOptions::default()).unwrap();
+            assert!(prepended.get_ref().starts_with(STUB));
+            assert_eq!(&prepended.get_ref()[STUB.len()..][..PREAMBLE.len()], PREAMBLE);
+
+            let archive = zip_read_archive(&mut prepended, &ReadOptions::default()).unwrap();
+            assert_prepended_entries_intact(&mut prepended, &archive, &original, shift);
+            assert_eq!(archive.central_directory_offset, original.central_directory_offset + shift);
+            assert_eq!(zip_check(&mut prepended).unwrap(), Vec::new());
+            assert!(archive.entries.iter().all(|e| zip64_extra_field(e).is_none()));
+
+            // extract the result
+            let directory = std::env::temp_dir().join(format!("libzmx-prepend-{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&directory);
+            std::fs::create_dir_all(&directory).unwrap();
+            zip_extract(&mut prepended, &archive, &directory, &ExtractOptions::default()).unwrap();
+            for index in 0..3 {
+                let data = std::fs::read(directory.join(format!("file{}.txt", index))).unwrap();
+                assert_eq!(data, entry_contents(index));
+            }
+            std::fs::remove_dir_all(&directory).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_prepend_past_4_gib() {
+        // a concatenated preamble puts the entries just below 4 GiB
+        let preamble_length = 0xFFFF_FF00;
+        let archive_data = write_entries(Cursor::new(Vec::new()), 2).into_inner();
+        let mut zip_file = ZeroPadded::new(preamble_length, archive_data);
+        let original = zip_read_archive(&mut zip_file, &ReadOptions::default()).unwrap();
+        assert_eq!(original.preamble_length, preamble_length);
+        assert!(original.entries.iter().all(|e| zip64_extra_field(e).is_none()));
+
+        // a stub of zeros pushes them (and the central directory) past it
+        let stub = vec![0u8; 0x200];
+        let mut prepended = ZeroPadded::new(preamble_length + 0x200, Vec::new());
+        zip_prepend(&mut zip_file, &mut prepended, &stub, &ReadOptions::default()).unwrap();
+        prepended.seek(SeekFrom::Start(0)).unwrap();
+
+        let end_records = read_end_records(&mut prepended, &[0]).unwrap();
+        assert!(end_records.zip64_eocd.is_some());
+        assert_eq!(end_records.eocd.central_dir_offset_on_disk, u32::MAX);
+        let archive = zip_read_archive(&mut prepended, &ReadOptions::default()).unwrap();
+        assert_prepended_entries_intact(&mut prepended, &archive, &original, 0x200);
+        assert!(archive.central_directory_offset > u32::MAX.into());
+        for entry in &archive.entries {
+            assert_eq!(entry.entry.local_header_relative_offset, -1);
+            assert_eq!(zip64_extra_field(entry).unwrap().local_header_relative_offset, Some(entry.local_header_offset as i64));
+        }
+
+        // prepending again updates the existing Zip64 extra fields
+        let mut prepended_again = ZeroPadded::new(preamble_length + 0x300, Vec::new());
+        zip_prepend(&mut prepended, &mut prepended_again, &stub[..0x100], &ReadOptions::default()).unwrap();
+        prepended_again.seek(SeekFrom::Start(0)).unwrap();
+        let archive_again = zip_read_archive(&mut prepended_again, &ReadOptions::default()).unwrap();
+        assert_prepended_entries_intact(&mut prepended_again, &archive_again, &original, 0x300);
+        for entry in &archive_again.entries {
+            assert_eq!(zip64_extra_field(entry).unwrap().local_header_relative_offset, Some(entry.local_header_offset as i64));
+        }
+    }
+
+    #[test]
+    fn test_append() {
+        // existing entries with attributes that differ from the defaults
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new())).unwrap();
+        for i in 0..2 {
+            let options = NewEntryOptions {
+                unix_mode: 0o600 + 0o40 * i as u16,
+                modified: Some(UNIX_EPOCH + Duration::from_secs(1_500_000_000 + 1000 * i as u64)),
+                owner: Some((1000, 100)),
+                ..Default::default()
+            };
+            writer.add_file(&format!("file{}.txt", i), entry_contents(i).as_slice(), &options).unwrap();
+        }
+        let mut concatenated = PREAMBLE.to_vec();
+        concatenated.extend_from_slice(&writer.finish().unwrap().into_inner());
+        let mut zip_file = Cursor::new(concatenated);
+
+        let mut archive = zip_read_archive(&mut zip_file, &ReadOptions::default()).unwrap();
+        assert_eq!(archive.preamble_length, PREAMBLE.len() as u64);
+        let original = archive.clone();
+
+        let modified = UNIX_EPOCH + Duration::from_secs(1_650_000_000);
+        let options = NewEntryOptions {
+            unix_mode: 0o755,
+            modified: Some(modified),
+            ..Default::default()
+        };
+        let data = entry_contents(2);
+        zip_append(&mut zip_file, &mut archive, "file2.txt", data.as_slice(), &options).unwrap();
+        assert_eq!(entry_names(&archive), ["file0.txt", "file1.txt", "file2.txt"]);
+        assert_entries_intact(&mut zip_file, &archive);
+
+        // the existing entries keep their attributes; only the offsets now account for the preamble
+        for (entry, original_entry) in archive.entries.iter().zip(&original.entries) {
+            assert_eq!(entry.local_header_offset, original_entry.local_header_offset);
+            assert_eq!(entry.unix_mode(), original_entry.unix_mode());
+            assert_eq!(entry.modification_time(), original_entry.modification_time());
+            let mut original_fields = original_entry.entry.clone();
+            original_fields.local_header_relative_offset = entry.entry.local_header_relative_offset;
+            assert_eq!(entry.entry, original_fields);
+        }
+
+        let new_entry = &archive.entries[2];
+        assert_eq!(new_entry.kind(), EntryKind::File);
+        assert_eq!(new_entry.unix_mode(), Some(0o755));
+        assert_eq!(new_entry.modification_time(), Some(modified));
+        let mut crc = flate2::Crc::new();
+        crc.update(&data);
+        assert_eq!(new_entry.entry.crc32, crc.sum());
+        assert_eq!(new_entry.uncompressed_size, data.len() as u64);
+        assert_eq!(new_entry.local_header_offset, original.central_directory_offset);
+    }
+
+    fn entry_from(host_system: HostSystem, name: &[u8], external_attributes: u32) -> CentralDirectoryEntry {
+        CentralDirectoryEntry {
+            creator_version: host_system.apply_to_creator_version(20),
+            file_name: name.to_vec(),
+            external_attributes,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_entry_kind() {
+        let unix = |name: &[u8], file_type: u32| EntryKind::from_entry(&entry_from(HostSystem::Unix, name, (file_type | 0o644) << 16));
+        assert_eq!(unix(b"file", UNIX_FILE_TYPE_REGULAR), EntryKind::File);
+        assert_eq!(unix(b"directory/", UNIX_FILE_TYPE_DIRECTORY), EntryKind::Directory);
+        assert_eq!(unix(b"link", UNIX_FILE_TYPE_SYMLINK), EntryKind::Symlink);
+        assert_eq!(unix(b"fifo", UNIX_FILE_TYPE_FIFO), EntryKind::Other);
+        assert_eq!(unix(b"tty", UNIX_FILE_TYPE_CHARACTER_DEVICE), EntryKind::Other);
+        assert_eq!(unix(b"disk", UNIX_FILE_TYPE_BLOCK_DEVICE), EntryKind::Other);
+        assert_eq!(unix(b"socket", UNIX_FILE_TYPE_SOCKET), EntryKind::Other);
+        assert_eq!(unix(b"mystery", 0o030000), EntryKind::Unknown);
+        assert_eq!(unix(b"whiteout", 0o160000), EntryKind::Unknown);
+
+        // a regular file whose name ends with a slash is extracted as a directory
+        assert_eq!(unix(b"directory/", UNIX_FILE_TYPE_REGULAR), EntryKind::Directory);
+        // but a symbolic link is not
+        assert_eq!(unix(b"link/", UNIX_FILE_TYPE_SYMLINK), EntryKind::Symlink);
+    }
+
+    #[test]
+    fn test_entry_kind_without_unix_attributes() {
+        let dos = |name: &[u8], external_attributes| EntryKind::from_entry(&entry_from(HostSystem::MsDos, name, external_attributes));
+        assert_eq!(dos(b"file", 0x20), EntryKind::File);
+        assert_eq!(dos(b"directory", 0x10), EntryKind::Directory);
+        // the trailing slash suffices even without the DOS directory attribute
+        assert_eq!(dos(b"directory/", 0x00), EntryKind::Directory);
+        assert_eq!(dos(b"directory/", 0x20), EntryKind::Directory);
+        // DOS attributes carrying Unix attributes in the upper half
+        assert_eq!(dos(b"link", (UNIX_FILE_TYPE_SYMLINK | 0o777) << 16 | 0x20), EntryKind::Symlink);
+        assert_eq!(dos(b"fifo", (UNIX_FILE_TYPE_FIFO | 0o644) << 16), EntryKind::Other);
+
+        // Unix file type 0 falls back to the DOS attributes
+        let unix = |name: &[u8], external_attributes| EntryKind::from_entry(&entry_from(HostSystem::Unix, name, external_attributes));
+        assert_eq!(unix(b"directory", 0o755 << 16 | 0x10), EntryKind::Directory);
+        assert_eq!(unix(b"directory/", 0o755 << 16), EntryKind::Directory);
+        assert_eq!(unix(b"file", 0o644 << 16), EntryKind::File);
+
+        // host systems that do not carry Unix attributes ignore the upper half
+        let vms = EntryKind::from_entry(&entry_from(HostSystem::OpenVms, b"link", (UNIX_FILE_TYPE_SYMLINK | 0o777) << 16));
+        assert_eq!(vms, EntryKind::File);
+    }
+
+    #[test]
+    fn test_make_executable_synthesizes_mode() {
+        let mut zip_file = archive_with_entries(4);
+        let mut archive = zip_read_archive(&mut zip_file, &ReadOptions::default()).unwrap();
+        // DOS and NTFS entries whose upper half is zero, one of them read-only
+        let attributes = [(HostSystem::MsDos, 0x20), (HostSystem::WindowsNtfs, 0x20), (HostSystem::Vfat, 0x21), (HostSystem::MsDos, 0x20)];
+        for (entry, (host_system, external_attributes)) in archive.entries.iter_mut().zip(attributes) {
+            entry.entry.creator_version = host_system.apply_to_creator_version(20);
+            entry.entry.external_attributes = external_attributes;
+        }
+        zip_rewrite_central_directory(&mut zip_file, &archive).unwrap();
+        let archive = zip_read_archive(&mut zip_file, &ReadOptions::default()).unwrap();
+        assert!(archive.entries.iter().all(|e| e.unix_attributes().is_none()));
+
+        for entry in &archive.entries[..3] {
+            zip_make_executable_with_defaults(&mut zip_file, entry.offset, &UnixModeDefaults::default()).unwrap();
+        }
+        let restrictive = UnixModeDefaults { file_mode: 0o600, directory_mode: 0o700 };
+        zip_make_executable_with_defaults(&mut zip_file, archive.entries[3].offset, &restrictive).unwrap();
+
+        let archive = zip_read_archive(&mut zip_file, &ReadOptions::default()).unwrap();
+        let modes: Vec<Option<u32>> = archive.entries.iter()
+            .map(|e| e.unix_attributes())
+            .collect();
+        // not 0o100111, which nobody could read
+        assert_eq!(modes, [Some(0o100755), Some(0o100755), Some(0o100555), Some(0o100711)]);
+        assert!(archive.entries.iter().all(|e| e.host_system() == HostSystem::Unix));
+        // the DOS attributes are retained
+        assert_eq!(archive.entries[2].entry.external_attributes & 0xFFFF, 0x21);
+        assert_entries_intact(&mut zip_file, &archive);
+    }
 }
@@ -0,0 +1,507 @@
+//! Archives that have been split into multiple files (volumes).
+
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::Error;
+use crate::io_ext::SetLen;
+
+
+/// The manner in which an archive has been split into volumes.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum VolumeScheme {
+    /// Each volume is a disk of a spanned archive (e.g. `archive.z01`, `archive.z02`, …,
+    /// `archive.zip`); the offsets stored in the archive are relative to the start of the disk to
+    /// which they refer.
+    ///
+    /// An ordinary archive consisting of a single file is a spanned archive with a single disk.
+    Spanned,
+
+    /// The volumes are consecutive pieces of a single-disk archive that has been cut up without
+    /// regard to its structure (e.g. `archive.zip.001`, `archive.zip.002`, …).
+    Split,
+}
+
+
+/// A source that presents the volumes of an archive as one contiguous stream.
+///
+/// Reads and writes that cross the boundary between two volumes are distributed accordingly.
+/// Writing beyond the end of the stream extends the last volume.
+#[derive(Debug)]
+pub struct MultiVolume<F> {
+    volumes: Vec<F>,
+    scheme: VolumeScheme,
+    volume_offsets: Vec<u64>,
+    length: u64,
+    position: u64,
+}
+impl<F: Seek> MultiVolume<F> {
+    /// Combines the given volumes, which must be in order and must not be empty.
+    pub fn new(mut volumes: Vec<F>, scheme: VolumeScheme) -> io::Result<Self> {
+        if volumes.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "no volumes given"));
+        }
+
+        let mut volume_offsets = Vec::with_capacity(volumes.len());
+        let mut length: u64 = 0;
+        for volume in &mut volumes {
+            volume_offsets.push(length);
+            let volume_length = volume.seek(SeekFrom::End(0))?;
+            length = length.checked_add(volume_length)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "volumes too long"))?;
+        }
+
+        Ok(Self {
+            volumes,
+            scheme,
+            volume_offsets,
+            length,
+            position: 0,
+        })
+    }
+}
+impl<F> MultiVolume<F> {
+    /// The manner in which the archive has been split into volumes.
+    pub fn scheme(&self) -> VolumeScheme {
+        self.scheme
+    }
+
+    /// The offset of the start of each volume within the stream.
+    pub fn volume_offsets(&self) -> &[u64] {
+        &self.volume_offsets
+    }
+
+    /// The offset of the start of each disk of the archive within the stream.
+    ///
+    /// For [spanned](VolumeScheme::Spanned) archives, each volume is a disk; for
+    /// [split](VolumeScheme::Split) archives, the whole stream is a single disk.
+    pub fn disk_offsets(&self) -> &[u64] {
+        match self.scheme {
+            VolumeScheme::Spanned => &self.volume_offsets,
+            VolumeScheme::Split => &self.volume_offsets[..1],
+        }
+    }
+
+    /// Returns the volumes.
+    pub fn into_inner(self) -> Vec<F> {
+        self.volumes
+    }
+
+    /// Returns the index of the volume containing the current position and the offset of the
+    /// current position within this volume, or `None` if the current position is at or beyond the
+    /// end of the stream.
+    ///
+    /// Empty volumes are skipped.
+    fn locate(&self) -> Option<(usize, u64)> {
+        if self.position >= self.length {
+            return None;
+        }
+        let index = self.volume_offsets.partition_point(|&o| o <= self.position) - 1;
+        Some((index, self.position - self.volume_offsets[index]))
+    }
+
+    /// Returns the length of the volume with the given index.
+    fn volume_length(&self, index: usize) -> u64 {
+        let end = self.volume_offsets.get(index + 1)
+            .copied()
+            .unwrap_or(self.length);
+        end - self.volume_offsets[index]
+    }
+}
+impl MultiVolume<File> {
+    /// Opens the volumes of the archive at the given path using the given options.
+    ///
+    /// The volumes are found using [`find_volume_paths`].
+    pub fn open<P: AsRef<Path>>(path: P, options: &OpenOptions) -> io::Result<Self> {
+        let (scheme, paths) = find_volume_paths(path.as_ref());
+        let mut volumes = Vec::with_capacity(paths.len());
+        for volume_path in &paths {
+            volumes.push(options.open(volume_path)?);
+        }
+        Self::new(volumes, scheme)
+    }
+}
+impl<F: Read + Seek> Read for MultiVolume<F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let (index, volume_position) = match self.locate() {
+            Some(l) => l,
+            None => return Ok(0),
+        };
+        let remaining = self.volume_length(index) - volume_position;
+        let read_length = usize::try_from(remaining).unwrap_or(usize::MAX).min(buf.len());
+
+        let volume = &mut self.volumes[index];
+        volume.seek(SeekFrom::Start(volume_position))?;
+        let read = volume.read(&mut buf[..read_length])?;
+        self.position += u64::try_from(read).unwrap();
+        Ok(read)
+    }
+}
+impl<F: Seek + Write> Write for MultiVolume<F> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let last_index = self.volumes.len() - 1;
+        let (index, volume_position, write_length) = match self.locate() {
+            Some((index, volume_position)) if index < last_index => {
+                let remaining = self.volume_length(index) - volume_position;
+                let write_length = usize::try_from(remaining).unwrap_or(usize::MAX).min(buf.len());
+                (index, volume_position, write_length)
+            },
+            _ => {
+                // the last volume may grow
+                (last_index, self.position - self.volume_offsets[last_index], buf.len())
+            },
+        };
+
+        let volume = &mut self.volumes[index];
+        volume.seek(SeekFrom::Start(volume_position))?;
+        let written = volume.write(&buf[..write_length])?;
+        self.position += u64::try_from(written).unwrap();
+        self.length = self.length.max(self.position);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        for volume in &mut self.volumes {
+            volume.flush()?;
+        }
+        Ok(())
+    }
+}
+impl<F: SetLen> SetLen for MultiVolume<F> {
+    /// Truncates or extends the last volume so that the stream has the given length.
+    ///
+    /// Fails with [`io::ErrorKind::Unsupported`] if the other volumes would have to be truncated.
+    fn set_len(&mut self, len: u64) -> io::Result<()> {
+        let last_index = self.volumes.len() - 1;
+        let last_offset = self.volume_offsets[last_index];
+        if len < last_offset {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, "only the last volume can be truncated"));
+        }
+        self.volumes[last_index].set_len(len - last_offset)?;
+        self.length = len;
+        Ok(())
+    }
+}
+impl<F> Seek for MultiVolume<F> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.length.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.position.checked_add_signed(delta),
+        };
+        match new_position {
+            Some(np) => {
+                self.position = np;
+                Ok(np)
+            },
+            None => Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position")),
+        }
+    }
+}
+
+
+/// Finds the volumes of the archive at the given path.
+///
+/// The following naming conventions are recognized:
+///
+/// * `archive.z01`, `archive.z02`, …, `archive.zip` is a [spanned](VolumeScheme::Spanned) archive.
+///   Any of its volumes may be given.
+/// * `archive.zip.001`, `archive.zip.002`, … is a [split](VolumeScheme::Split) archive. Any of its
+///   volumes may be given, as may `archive.zip` if no such file exists.
+///
+/// Volumes are collected until the first number that does not exist. If none of these conventions
+/// applies, the given path is returned as the only volume of a spanned archive.
+pub fn find_volume_paths(path: &Path) -> (VolumeScheme, Vec<PathBuf>) {
+    let single = || (VolumeScheme::Spanned, vec![path.to_owned()]);
+    let file_name = match path.file_name().and_then(|fn_os| fn_os.to_str()) {
+        Some(fn_str) => fn_str,
+        None => return single(),
+    };
+
+    // archive.zip.001
+    let (stem, extension) = match file_name.rsplit_once('.') {
+        Some(se) => se,
+        None => return single(),
+    };
+    let split_stem = if extension.len() >= 3 && extension.bytes().all(|b| b.is_ascii_digit()) {
+        Some(stem.to_owned())
+    } else if !path.exists() && path.with_file_name(format!("{}.001", file_name)).exists() {
+        Some(file_name.to_owned())
+    } else {
+        None
+    };
+    if let Some(split_stem) = split_stem {
+        let mut paths = Vec::new();
+        for number in 1.. {
+            let volume_path = path.with_file_name(format!("{}.{:03}", split_stem, number));
+            if !volume_path.exists() {
+                break;
+            }
+            paths.push(volume_path);
+        }
+        if paths.is_empty() {
+            return single();
+        }
+        return (VolumeScheme::Split, paths);
+    }
+
+    // archive.z01 … archive.zip
+    let (z, digits) = extension.split_at(extension.len().min(1));
+    let last_extension = if z.eq_ignore_ascii_case("z") && digits.len() >= 2 && digits.bytes().all(|b| b.is_ascii_digit()) {
+        if z == "Z" { "ZIP" } else { "zip" }
+    } else if extension.eq_ignore_ascii_case("zip") {
+        extension
+    } else {
+        return single();
+    };
+    let z = if last_extension == "ZIP" { "Z" } else { "z" };
+    let mut paths = Vec::new();
+    for number in 1.. {
+        let volume_path = path.with_file_name(format!("{}.{}{:02}", stem, z, number));
+        if !volume_path.exists() {
+            break;
+        }
+        paths.push(volume_path);
+    }
+    paths.push(path.with_file_name(format!("{}.{}", stem, last_extension)));
+    (VolumeScheme::Spanned, paths)
+}
+
+
+/// Returns the offset of the start of the given disk, or [`Error::SpannedArchive`] if the disk is
+/// not available.
+pub(crate) fn disk_offset(disk_offsets: &[u64], disk: u32) -> Result<u64, Error> {
+    usize::try_from(disk).ok()
+        .and_then(|d| disk_offsets.get(d))
+        .copied()
+        .ok_or(Error::SpannedArchive)
+}
+
+
+/// Returns the number of the disk containing the given offset.
+pub(crate) fn disk_containing(disk_offsets: &[u64], offset: u64) -> u32 {
+    let disk = disk_offsets.partition_point(|&o| o <= offset).saturating_sub(1);
+    disk.try_into().unwrap_or(u32::MAX)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Cursor;
+
+    use crate::{
+        CompressionMethod, NewEntryOptions, ReadOptions, zip_check_disks, zip_read_archive,
+        zip_read_archive_disks, zip_read_entry_data, ZipWriter,
+    };
+    use crate::zip_format::EndOfCentralDirectory;
+
+    fn test_directory(test_name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("libzmx-{}-{}", test_name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        path
+    }
+
+    fn touch(directory: &Path, names: &[&str]) {
+        for name in names {
+            fs::write(directory.join(name), b"").unwrap();
+        }
+    }
+
+    fn file_names(paths: &[PathBuf]) -> Vec<String> {
+        paths.iter()
+            .map(|p| p.file_name().unwrap().to_str().unwrap().to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_find_spanned_volume_paths() {
+        let directory = test_directory("find-spanned-volume-paths");
+        touch(&directory, &["archive.z01", "archive.z02", "archive.zip", "archive.z04"]);
+
+        for given in ["archive.z01", "archive.z02", "archive.zip"] {
+            let (scheme, paths) = find_volume_paths(&directory.join(given));
+            assert_eq!(scheme, VolumeScheme::Spanned);
+            assert_eq!(file_names(&paths), ["archive.z01", "archive.z02", "archive.zip"], "{}", given);
+        }
+
+        touch(&directory, &["UPPER.Z01", "UPPER.ZIP", "single.zip", "other.txt"]);
+        let (scheme, paths) = find_volume_paths(&directory.join("UPPER.Z01"));
+        assert_eq!(scheme, VolumeScheme::Spanned);
+        assert_eq!(file_names(&paths), ["UPPER.Z01", "UPPER.ZIP"]);
+        let (scheme, paths) = find_volume_paths(&directory.join("single.zip"));
+        assert_eq!(scheme, VolumeScheme::Spanned);
+        assert_eq!(file_names(&paths), ["single.zip"]);
+        let (scheme, paths) = find_volume_paths(&directory.join("other.txt"));
+        assert_eq!(scheme, VolumeScheme::Spanned);
+        assert_eq!(file_names(&paths), ["other.txt"]);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_find_split_volume_paths() {
+        let directory = test_directory("find-split-volume-paths");
+        touch(&directory, &["archive.zip.001", "archive.zip.002", "archive.zip.003", "archive.zip.005"]);
+
+        for given in ["archive.zip.001", "archive.zip.003", "archive.zip"] {
+            let (scheme, paths) = find_volume_paths(&directory.join(given));
+            assert_eq!(scheme, VolumeScheme::Split);
+            assert_eq!(file_names(&paths), ["archive.zip.001", "archive.zip.002", "archive.zip.003"], "{}", given);
+        }
+
+        // an existing archive.zip is not a split archive
+        touch(&directory, &["archive.zip"]);
+        let (scheme, paths) = find_volume_paths(&directory.join("archive.zip"));
+        assert_eq!(scheme, VolumeScheme::Spanned);
+        assert_eq!(file_names(&paths), ["archive.zip"]);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_read_write_across_volumes() {
+        let volumes = vec![
+            Cursor::new(b"abc".to_vec()),
+            Cursor::new(Vec::new()),
+            Cursor::new(b"defg".to_vec()),
+            Cursor::new(b"hi".to_vec()),
+        ];
+        let mut multi_volume = MultiVolume::new(volumes, VolumeScheme::Spanned).unwrap();
+        assert_eq!(multi_volume.volume_offsets(), [0, 3, 3, 7]);
+        assert_eq!(multi_volume.disk_offsets(), [0, 3, 3, 7]);
+
+        let mut data = Vec::new();
+        multi_volume.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"abcdefghi");
+
+        multi_volume.seek(SeekFrom::Start(2)).unwrap();
+        let mut buf = [0u8; 6];
+        multi_volume.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"cdefgh");
+
+        // overwrite across boundaries and extend the last volume
+        multi_volume.seek(SeekFrom::Start(1)).unwrap();
+        multi_volume.write_all(b"BCDEFGHIJK").unwrap();
+        let volumes: Vec<Vec<u8>> = multi_volume.into_inner().into_iter()
+            .map(Cursor::into_inner)
+            .collect();
+        assert_eq!(volumes, [b"aBC".to_vec(), Vec::new(), b"DEFG".to_vec(), b"HIJK".to_vec()]);
+
+        let split = MultiVolume::new(vec![Cursor::new(b"abc".to_vec()), Cursor::new(b"def".to_vec())], VolumeScheme::Split).unwrap();
+        assert_eq!(split.disk_offsets(), [0]);
+    }
+
+    #[test]
+    fn test_disk_offsets() {
+        let disk_offsets = [0, 100, 250];
+        assert_eq!(disk_offset(&disk_offsets, 0).unwrap(), 0);
+        assert_eq!(disk_offset(&disk_offsets, 2).unwrap(), 250);
+        assert!(matches!(disk_offset(&disk_offsets, 3), Err(Error::SpannedArchive)));
+
+        assert_eq!(disk_containing(&disk_offsets, 0), 0);
+        assert_eq!(disk_containing(&disk_offsets, 99), 0);
+        assert_eq!(disk_containing(&disk_offsets, 100), 1);
+        assert_eq!(disk_containing(&disk_offsets, 1000), 2);
+    }
+
+    /// Writes an archive with two stored entries and splits it into two disks in the middle of the
+    /// data of the first entry. The second entry and the central directory are on the second disk.
+    fn spanned_archive() -> (Vec<u8>, Vec<u8>, [&'static [u8]; 2]) {
+        const CONTENTS: [&[u8]; 2] = [b"the first entry spans both disks", b"the second entry is on the second disk"];
+        let options = NewEntryOptions {
+            compression_method: CompressionMethod::Stored,
+            ..Default::default()
+        };
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new())).unwrap();
+        writer.add_file("first.txt", CONTENTS[0], &options).unwrap();
+        writer.add_file("second.txt", CONTENTS[1], &options).unwrap();
+        let mut zip_file = writer.finish().unwrap();
+        let archive = zip_read_archive(&mut zip_file, &ReadOptions::default()).unwrap();
+        let data = zip_file.into_inner();
+
+        let split_point = usize::try_from(archive.entries[1].local_header_offset).unwrap() - 10;
+        let central_directory_offset = usize::try_from(archive.central_directory_offset).unwrap();
+        let first_disk = data[..split_point].to_vec();
+        let mut second_disk = data[split_point..central_directory_offset].to_vec();
+
+        let mut central_directory = Vec::new();
+        for (index, entry) in archive.entries.iter().enumerate() {
+            let mut entry = entry.entry.clone();
+            if index == 1 {
+                entry.disk_number_start = 1;
+                entry.local_header_relative_offset -= i32::try_from(split_point).unwrap();
+            }
+            entry.write(&mut central_directory).unwrap();
+        }
+        let central_directory_offset_on_disk = second_disk.len();
+        second_disk.extend_from_slice(&central_directory);
+        EndOfCentralDirectory {
+            disk_no: 1,
+            start_central_dir_disk_no: 1,
+            total_central_dir_entries_this_disk: 2,
+            total_central_dir_entries: 2,
+            central_directory_size: central_directory.len().try_into().unwrap(),
+            central_dir_offset_on_disk: central_directory_offset_on_disk.try_into().unwrap(),
+            ..Default::default()
+        }.write(&mut second_disk).unwrap();
+
+        (first_disk, second_disk, CONTENTS)
+    }
+
+    #[test]
+    fn test_read_spanned_archive() {
+        let (first_disk, second_disk, contents) = spanned_archive();
+        let directory = test_directory("read-spanned-archive");
+        fs::write(directory.join("archive.z01"), &first_disk).unwrap();
+        fs::write(directory.join("archive.zip"), &second_disk).unwrap();
+
+        let mut multi_volume = MultiVolume::open(directory.join("archive.zip"), OpenOptions::new().read(true)).unwrap();
+        assert_eq!(multi_volume.scheme(), VolumeScheme::Spanned);
+        let disk_offsets = multi_volume.disk_offsets().to_vec();
+        assert_eq!(disk_offsets, [0, u64::try_from(first_disk.len()).unwrap()]);
+
+        let archive = zip_read_archive_disks(&mut multi_volume, &disk_offsets, &ReadOptions::default()).unwrap();
+        assert_eq!(archive.entries.len(), 2);
+        assert_eq!(archive.entries[1].disk_number_start, 1);
+        assert_eq!(
+            archive.entries[1].local_header_offset,
+            disk_offsets[1] + u64::from(archive.entries[1].entry.local_header_relative_offset as u32),
+        );
+        assert!(archive.entries.iter().all(|e| e.disk == 1));
+        for (entry, expected) in archive.entries.iter().zip(contents) {
+            let mut data = Vec::new();
+            zip_read_entry_data(&mut multi_volume, entry, &mut data).unwrap();
+            assert_eq!(data, expected);
+        }
+        assert_eq!(zip_check_disks(&mut multi_volume, &disk_offsets).unwrap(), Vec::new());
+
+        // without the first disk, the archive cannot be read
+        let result = zip_read_archive(Cursor::new(second_disk), &ReadOptions::default());
+        assert!(matches!(result, Err(Error::SpannedArchive)), "{:?}", result);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_read_split_archive() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new())).unwrap();
+        writer.add_file("file.txt", &b"split right through the middle of this entry"[..], &NewEntryOptions::default()).unwrap();
+        let data = writer.finish().unwrap().into_inner();
+
+        // cut into pieces regardless of the structure
+        let volumes: Vec<Cursor<Vec<u8>>> = data.chunks(17)
+            .map(|c| Cursor::new(c.to_vec()))
+            .collect();
+        let mut multi_volume = MultiVolume::new(volumes, VolumeScheme::Split).unwrap();
+        let disk_offsets = multi_volume.disk_offsets().to_vec();
+        let archive = zip_read_archive_disks(&mut multi_volume, &disk_offsets, &ReadOptions::default()).unwrap();
+        let mut read = Vec::new();
+        zip_read_entry_data(&mut multi_volume, &archive.entries[0], &mut read).unwrap();
+        assert_eq!(read, b"split right through the middle of this entry");
+    }
+}
@@ -0,0 +1,378 @@
+//! Rewriting the central directory and end records of ZIP archives.
+
+
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use crate::Error;
+use crate::extra_field::RawExtraFieldIter;
+use crate::zip_format::{
+    CentralDirectoryEntry, EndOfCentralDirectory, Zip64EndOfCentralDirectory,
+    Zip64EndOfCentralDirectoryLocator, Zip64ExtraField,
+};
+
+
+/// The ZIP version (4.5) that introduced Zip64 records.
+pub(crate) const ZIP64_VERSION: u16 = 45;
+
+/// The size of the buffer used when moving data within a file.
+const MOVE_BUFFER_SIZE: usize = 64 * 1024;
+
+
+/// Updates the local header offset of a central directory entry.
+///
+/// Offsets that do not fit into the 32-bit field are stored in the entry's Zip64 extended
+/// information extra field, which is created if necessary; conversely, offsets that fit are
+/// removed from the extra field, which is dropped if it becomes empty. Other extra fields are
+/// retained in their original order.
+pub(crate) fn set_local_header_offset(entry: &mut CentralDirectoryEntry, offset: u64) -> Result<(), Error> {
+    // decode the existing Zip64 extra field and keep the others as they are
+    let mut zip64_extra: Option<Zip64ExtraField> = None;
+    let mut zip64_index = 0;
+    let mut other_fields: Vec<(u16, &[u8])> = Vec::new();
+    for block_res in RawExtraFieldIter::new(&entry.extra_fields) {
+        let (tag, block) = block_res?;
+        if tag == Zip64ExtraField::tag() && zip64_extra.is_none() {
+            let field = Zip64ExtraField::from_data_lenient(
+                block,
+                entry.uncompressed_size,
+                entry.compressed_size,
+                entry.local_header_relative_offset,
+                entry.disk_number_start,
+            );
+            zip64_extra = Some(field);
+            zip64_index = other_fields.len();
+        } else {
+            other_fields.push((tag, block));
+        }
+    }
+    let mut zip64_extra = zip64_extra.unwrap_or_default();
+
+    let local_header_relative_offset = match u32::try_from(offset) {
+        Ok(o) if o != u32::MAX => {
+            zip64_extra.local_header_relative_offset = None;
+            o as i32
+        },
+        _ => {
+            zip64_extra.local_header_relative_offset = Some(offset as i64);
+            -1
+        },
+    };
+    let uses_zip64 = zip64_extra != Zip64ExtraField::default();
+
+    let mut extra_fields = Vec::with_capacity(entry.extra_fields.len() + 8);
+    for (i, (tag, block)) in other_fields.iter().enumerate() {
+        if i == zip64_index && uses_zip64 {
+            zip64_extra.write(&mut extra_fields)?;
+        }
+        let length: u16 = block.len().try_into().unwrap();
+        extra_fields.extend_from_slice(&tag.to_le_bytes());
+        extra_fields.extend_from_slice(&length.to_le_bytes());
+        extra_fields.extend_from_slice(block);
+    }
+    if zip64_index >= other_fields.len() && uses_zip64 {
+        zip64_extra.write(&mut extra_fields)?;
+    }
+    if extra_fields.len() > 0xFFFF {
+        return Err(Error::FieldTooLong);
+    }
+
+    entry.extra_fields = extra_fields;
+    entry.local_header_relative_offset = local_header_relative_offset;
+    if uses_zip64 && entry.required_version & 0x00FF < ZIP64_VERSION {
+        entry.required_version = (entry.required_version & 0xFF00) | ZIP64_VERSION;
+    }
+    Ok(())
+}
+
+
+/// Writes a central directory consisting of the given entries, followed by the end records.
+///
+/// `central_directory_offset` is the offset at which the central directory is being written. Zip64
+/// records are written if the number of entries, the size of the central directory or its offset
+/// do not fit into the end-of-central-directory record. The archive is assumed to consist of a
+/// single disk.
+///
+/// Returns the number of bytes written.
+pub(crate) fn write_central_directory<W: Write>(
+    mut writer: W,
+    entries: &[CentralDirectoryEntry],
+    central_directory_offset: u64,
+    comment: &[u8],
+) -> Result<u64, Error> {
+    let mut central_directory_size: u64 = 0;
+    for entry in entries {
+        entry.write(&mut writer)?;
+        central_directory_size += CentralDirectoryEntry::min_len()
+            + u64::try_from(entry.file_name.len()).unwrap()
+            + u64::try_from(entry.extra_fields.len()).unwrap()
+            + u64::try_from(entry.file_comment.len()).unwrap();
+    }
+    let entry_count: u64 = entries.len().try_into().unwrap();
+    let end_records_length = write_end_records(&mut writer, entry_count, central_directory_size, central_directory_offset, comment)?;
+    Ok(central_directory_size + end_records_length)
+}
+
+
+/// Writes the end records for a central directory with the given number of entries, size and
+/// offset.
+///
+/// Zip64 records are written if any of these values does not fit into the
+/// end-of-central-directory record. Returns the number of bytes written.
+fn write_end_records<W: Write>(
+    mut writer: W,
+    entry_count: u64,
+    central_directory_size: u64,
+    central_directory_offset: u64,
+    comment: &[u8],
+) -> Result<u64, Error> {
+    let mut written = 0;
+
+    let narrow_entry_count: u16 = entry_count.try_into().unwrap_or(u16::MAX);
+    let narrow_size: u32 = central_directory_size.try_into().unwrap_or(u32::MAX);
+    let narrow_offset: u32 = central_directory_offset.try_into().unwrap_or(u32::MAX);
+    let needs_zip64 = narrow_entry_count == u16::MAX
+        || narrow_size == u32::MAX
+        || narrow_offset == u32::MAX;
+
+    if needs_zip64 {
+        let zip64_eocd_offset = central_directory_offset + central_directory_size;
+        let zip64_eocd = Zip64EndOfCentralDirectory {
+            creator_version: ZIP64_VERSION,
+            required_version: ZIP64_VERSION,
+            disk_no: 0,
+            start_central_dir_disk_no: 0,
+            total_central_dir_entries_this_disk: entry_count,
+            total_central_dir_entries: entry_count,
+            central_directory_size,
+            central_dir_offset_on_disk: central_directory_offset,
+            extensible_data_sector: Vec::new(),
+        };
+        zip64_eocd.write(&mut writer)?;
+        written += Zip64EndOfCentralDirectory::min_len();
+
+        let zip64_eocd_locator = Zip64EndOfCentralDirectoryLocator {
+            disk_no: 0,
+            offset_on_disk: zip64_eocd_offset,
+            total_disks: 1,
+        };
+        zip64_eocd_locator.write(&mut writer)?;
+        written += Zip64EndOfCentralDirectoryLocator::min_len();
+    }
+
+    let eocd = EndOfCentralDirectory {
+        disk_no: 0,
+        start_central_dir_disk_no: 0,
+        total_central_dir_entries_this_disk: narrow_entry_count,
+        total_central_dir_entries: narrow_entry_count,
+        central_directory_size: narrow_size,
+        central_dir_offset_on_disk: narrow_offset,
+        comment: comment.to_vec(),
+    };
+    eocd.write(&mut writer)?;
+    written += EndOfCentralDirectory::min_len() + u64::try_from(comment.len()).unwrap();
+
+    Ok(written)
+}
+
+
+/// Moves `length` bytes within a file from offset `from` to offset `to`.
+///
+/// The source and destination areas may overlap; the data is copied in the appropriate direction.
+/// If the destination extends beyond the end of the file, the file grows accordingly.
+pub(crate) fn move_data<F: Read + Seek + Write>(mut file: F, from: u64, to: u64, length: u64) -> Result<(), Error> {
+    if from == to || length == 0 {
+        return Ok(());
+    }
+
+    let mut buffer = vec![0u8; MOVE_BUFFER_SIZE];
+    let mut moved: u64 = 0;
+    while moved < length {
+        let chunk_length = (length - moved).min(MOVE_BUFFER_SIZE as u64);
+        // when moving towards the end, start with the last chunk so as not to overwrite source data
+        let chunk_start = if to > from {
+            length - moved - chunk_length
+        } else {
+            moved
+        };
+        let chunk = &mut buffer[..usize::try_from(chunk_length).unwrap()];
+
+        file.seek(SeekFrom::Start(from + chunk_start))?;
+        file.read_exact(chunk)?;
+        file.seek(SeekFrom::Start(to + chunk_start))?;
+        file.write_all(chunk)?;
+
+        moved += chunk_length;
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    use crate::{ReadOptions, zip_read_archive};
+    use crate::extra_field::with_length_prefix;
+    use crate::io_ext::ReadExt;
+
+    /// Reads end records as written by `write_end_records`.
+    fn read_written_end_records(mut data: &[u8]) -> (Option<(Zip64EndOfCentralDirectory, Zip64EndOfCentralDirectoryLocator)>, EndOfCentralDirectory) {
+        let mut signature = data.read_u32_le().unwrap();
+        let mut zip64_records = None;
+        if signature == Zip64EndOfCentralDirectory::signature() {
+            let zip64_eocd = Zip64EndOfCentralDirectory::read_after_signature(&mut data, u64::MAX).unwrap();
+            assert_eq!(data.read_u32_le().unwrap(), Zip64EndOfCentralDirectoryLocator::signature());
+            let zip64_eocd_locator = Zip64EndOfCentralDirectoryLocator::read_after_signature(&mut data).unwrap();
+            zip64_records = Some((zip64_eocd, zip64_eocd_locator));
+            signature = data.read_u32_le().unwrap();
+        }
+        assert_eq!(signature, EndOfCentralDirectory::signature());
+        let eocd = EndOfCentralDirectory::read_after_signature(&mut data).unwrap();
+        assert!(data.is_empty());
+        (zip64_records, eocd)
+    }
+
+    #[test]
+    fn test_end_records_zip64_thresholds() {
+        // the largest values that fit
+        let mut buffer = Vec::new();
+        let written = write_end_records(&mut buffer, 0xFFFE, 0xFFFF_FFFE, 0xFFFF_FFFE, b"comment").unwrap();
+        assert_eq!(written, u64::try_from(buffer.len()).unwrap());
+        let (zip64_records, eocd) = read_written_end_records(&buffer);
+        assert_eq!(zip64_records, None);
+        assert_eq!(eocd.total_central_dir_entries, 0xFFFE);
+        assert_eq!(eocd.central_directory_size, 0xFFFF_FFFE);
+        assert_eq!(eocd.central_dir_offset_on_disk, 0xFFFF_FFFE);
+        assert_eq!(eocd.comment, b"comment");
+
+        // each value on its own requires Zip64 records once it reaches the maximum of its field
+        let cases: [(u64, u64, u64); 6] = [
+            (0xFFFF, 100, 200),
+            (0x1_0000, 100, 200),
+            (3, 0xFFFF_FFFF, 200),
+            (3, 0x1_0000_0000, 200),
+            (3, 100, 0xFFFF_FFFF),
+            (3, 100, 0x1_2345_6789),
+        ];
+        for (entry_count, size, offset) in cases {
+            let mut buffer = Vec::new();
+            let written = write_end_records(&mut buffer, entry_count, size, offset, b"").unwrap();
+            assert_eq!(written, u64::try_from(buffer.len()).unwrap());
+            let (zip64_records, eocd) = read_written_end_records(&buffer);
+            let (zip64_eocd, zip64_eocd_locator) = zip64_records
+                .unwrap_or_else(|| panic!("no Zip64 records for {:?}", (entry_count, size, offset)));
+            assert_eq!(zip64_eocd.total_central_dir_entries, entry_count);
+            assert_eq!(zip64_eocd.total_central_dir_entries_this_disk, entry_count);
+            assert_eq!(zip64_eocd.central_directory_size, size);
+            assert_eq!(zip64_eocd.central_dir_offset_on_disk, offset);
+            assert_eq!(zip64_eocd_locator.offset_on_disk, offset + size);
+            assert_eq!(zip64_eocd_locator.total_disks, 1);
+
+            // values that do not fit are replaced by their maximum
+            assert_eq!(u64::from(eocd.total_central_dir_entries), entry_count.min(0xFFFF));
+            assert_eq!(u64::from(eocd.central_directory_size), size.min(0xFFFF_FFFF));
+            assert_eq!(u64::from(eocd.central_dir_offset_on_disk), offset.min(0xFFFF_FFFF));
+        }
+    }
+
+    #[test]
+    fn test_central_directory_entry_count() {
+        let entry = CentralDirectoryEntry {
+            file_name: b"f".to_vec(),
+            ..Default::default()
+        };
+        let entry_length = CentralDirectoryEntry::min_len() + 1;
+        for (count, zip64) in [(0xFFFE, false), (0xFFFF, true)] {
+            let entries = vec![entry.clone(); count];
+            let mut buffer = Vec::new();
+            let written = write_central_directory(&mut buffer, &entries, 0, b"").unwrap();
+            assert_eq!(written, u64::try_from(buffer.len()).unwrap());
+
+            let end_records = &buffer[count * usize::try_from(entry_length).unwrap()..];
+            let (zip64_records, _eocd) = read_written_end_records(end_records);
+            assert_eq!(zip64_records.is_some(), zip64);
+
+            let archive = zip_read_archive(Cursor::new(buffer), &ReadOptions::default()).unwrap();
+            assert_eq!(archive.entries.len(), count);
+        }
+    }
+
+    fn zip64_extra_field(entry: &CentralDirectoryEntry) -> Option<Zip64ExtraField> {
+        RawExtraFieldIter::new(&entry.extra_fields)
+            .map(|block_res| block_res.unwrap())
+            .find(|(tag, _)| *tag == Zip64ExtraField::tag())
+            .map(|(_, block)| Zip64ExtraField::read_after_tag(
+                with_length_prefix(block).as_slice(),
+                entry.uncompressed_size,
+                entry.compressed_size,
+                entry.local_header_relative_offset,
+                entry.disk_number_start,
+            ).unwrap())
+    }
+
+    #[test]
+    fn test_set_local_header_offset() {
+        let other_field = [0xFE, 0xCA, 0x02, 0x00, 0x12, 0x34];
+        let original = CentralDirectoryEntry {
+            required_version: 20,
+            extra_fields: other_field.to_vec(),
+            ..Default::default()
+        };
+
+        // large offsets move into the Zip64 extra field, which is added before the other fields
+        let mut entry = original.clone();
+        set_local_header_offset(&mut entry, 0x1_0000_0000).unwrap();
+        assert_eq!(entry.local_header_relative_offset, -1);
+        assert_eq!(entry.required_version, ZIP64_VERSION);
+        assert!(entry.extra_fields.ends_with(&other_field));
+        let zip64_extra = zip64_extra_field(&entry).unwrap();
+        assert_eq!(zip64_extra.local_header_relative_offset, Some(0x1_0000_0000));
+        assert_eq!(zip64_extra.uncompressed_size, None);
+
+        // 0xFFFFFFFF itself marks the value as stored in the extra field
+        set_local_header_offset(&mut entry, 0xFFFF_FFFF).unwrap();
+        assert_eq!(entry.local_header_relative_offset, -1);
+        assert_eq!(zip64_extra_field(&entry).unwrap().local_header_relative_offset, Some(0xFFFF_FFFF));
+
+        // small offsets move back, dropping the then empty extra field
+        set_local_header_offset(&mut entry, 1234).unwrap();
+        assert_eq!(entry.local_header_relative_offset, 1234);
+        assert_eq!(entry.extra_fields, other_field);
+
+        // other values in the Zip64 extra field are retained
+        let mut entry = CentralDirectoryEntry {
+            uncompressed_size: u32::MAX,
+            ..original.clone()
+        };
+        entry.extra_fields.clear();
+        Zip64ExtraField { uncompressed_size: Some(0x2_0000_0000), ..Default::default() }
+            .write(&mut entry.extra_fields).unwrap();
+        set_local_header_offset(&mut entry, 0x3_0000_0000).unwrap();
+        let zip64_extra = zip64_extra_field(&entry).unwrap();
+        assert_eq!(zip64_extra.uncompressed_size, Some(0x2_0000_0000));
+        assert_eq!(zip64_extra.local_header_relative_offset, Some(0x3_0000_0000));
+        set_local_header_offset(&mut entry, 0).unwrap();
+        let zip64_extra = zip64_extra_field(&entry).unwrap();
+        assert_eq!(zip64_extra.uncompressed_size, Some(0x2_0000_0000));
+        assert_eq!(zip64_extra.local_header_relative_offset, None);
+    }
+
+    #[test]
+    fn test_move_data() {
+        let length = MOVE_BUFFER_SIZE * 2 + 100;
+        let data: Vec<u8> = (0..length).map(|i| (i % 251) as u8).collect();
+
+        // towards the end, overlapping and growing the file
+        let mut file = Cursor::new(data.clone());
+        move_data(&mut file, 0, 1000, length as u64).unwrap();
+        assert_eq!(file.get_ref().len(), length + 1000);
+        assert_eq!(&file.get_ref()[1000..], data.as_slice());
+
+        // towards the start, overlapping
+        let mut file = Cursor::new(data.clone());
+        move_data(&mut file, 1000, 10, (length - 1000) as u64).unwrap();
+        assert_eq!(&file.get_ref()[10..length - 990], &data[1000..]);
+        assert_eq!(&file.get_ref()[..10], &data[..10]);
+    }
+}
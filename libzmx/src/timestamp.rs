@@ -0,0 +1,100 @@
+//! Conversion between Unix timestamps and the MS-DOS date and time fields.
+
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+
+/// The earliest time that can be represented in the MS-DOS date and time fields
+/// (1980-01-01T00:00:00), in seconds since the Unix epoch.
+const MIN_DOS_TIMESTAMP: i64 = 315_532_800;
+
+/// The latest time that can be represented in the MS-DOS date and time fields
+/// (2107-12-31T23:59:58), in seconds since the Unix epoch.
+const MAX_DOS_TIMESTAMP: i64 = 4_354_819_198;
+
+
+/// Returns the given time as the number of seconds since the Unix epoch, rounded down.
+pub(crate) fn unix_timestamp(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(after) => after.as_secs().try_into().unwrap_or(i64::MAX),
+        Err(before) => {
+            let duration = before.duration();
+            let seconds: i64 = duration.as_secs().try_into().unwrap_or(i64::MAX);
+            if duration.subsec_nanos() > 0 {
+                seconds.saturating_neg().saturating_sub(1)
+            } else {
+                seconds.saturating_neg()
+            }
+        },
+    }
+}
+
+
+/// Converts the given number of seconds since the Unix epoch into the values of the MS-DOS time and
+/// date fields.
+///
+/// The time is interpreted as UTC. Times outside of the representable range (1980 to 2107) are
+/// clamped; as the MS-DOS format has a resolution of two seconds, odd seconds are rounded down.
+pub(crate) fn dos_time_and_date(timestamp: i64) -> (u16, u16) {
+    let timestamp = timestamp.clamp(MIN_DOS_TIMESTAMP, MAX_DOS_TIMESTAMP);
+    let days = timestamp.div_euclid(24 * 60 * 60);
+    let seconds_of_day = timestamp.rem_euclid(24 * 60 * 60);
+
+    // convert days since the epoch into a proleptic Gregorian date (Howard Hinnant's algorithm)
+    let shifted_days = days + 719_468;
+    let era = shifted_days.div_euclid(146_097);
+    let day_of_era = shifted_days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    let hour = seconds_of_day / 3600;
+    let minute = (seconds_of_day / 60) % 60;
+    let second = seconds_of_day % 60;
+
+    let time = (hour << 11) | (minute << 5) | (second / 2);
+    let date = ((year - 1980) << 9) | (month << 5) | day;
+    (time.try_into().unwrap(), date.try_into().unwrap())
+}
+
+
+/// Converts the values of the MS-DOS time and date fields into the number of seconds since the Unix
+/// epoch.
+///
+/// The time is interpreted as UTC. Returns `None` if the fields do not denote a valid date and time.
+pub(crate) fn dos_timestamp(time: u16, date: u16) -> Option<i64> {
+    let year = 1980 + i64::from(date >> 9);
+    let month = i64::from((date >> 5) & 0x0F);
+    let day = i64::from(date & 0x1F);
+    let hour = i64::from(time >> 11);
+    let minute = i64::from((time >> 5) & 0x3F);
+    let second = 2 * i64::from(time & 0x1F);
+    if !(1..=12).contains(&month) || day < 1 || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+
+    // convert the proleptic Gregorian date into days since the epoch (Howard Hinnant's algorithm)
+    let shifted_year = if month <= 2 { year - 1 } else { year };
+    let era = shifted_year.div_euclid(400);
+    let year_of_era = shifted_year.rem_euclid(400);
+    let shifted_month = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * shifted_month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    Some(days * 24 * 60 * 60 + hour * 3600 + minute * 60 + second)
+}
+
+
+/// Returns the point in time denoted by the given number of seconds since the Unix epoch.
+pub(crate) fn system_time(timestamp: i64) -> SystemTime {
+    let duration = Duration::from_secs(timestamp.unsigned_abs());
+    if timestamp >= 0 {
+        UNIX_EPOCH + duration
+    } else {
+        UNIX_EPOCH - duration
+    }
+}
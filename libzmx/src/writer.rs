@@ -0,0 +1,732 @@
+//! Writing new entries into ZIP archives.
+
+
+use std::fmt;
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::time::SystemTime;
+
+use flate2::{Compression, Crc};
+use flate2::write::DeflateEncoder;
+
+use crate::{
+    Error, UNIX_FILE_TYPE_DIRECTORY, UNIX_FILE_TYPE_REGULAR, UNIX_FILE_TYPE_SYMLINK, UNIX_MODE_MASK,
+    ZipCentralDirectoryEntry,
+};
+use crate::encoding::encode_entry_name;
+use crate::extra_field::{ExtendedTimestampExtraField, InfoZipUnixExtraField};
+use crate::host_system::HostSystem;
+use crate::rewrite::{ZIP64_VERSION, set_local_header_offset, write_central_directory};
+use crate::timestamp::{dos_time_and_date, unix_timestamp};
+use crate::zip_format::{CentralDirectoryEntry, LocalFileHeader, Zip64ExtraField};
+
+
+/// The ZIP version (2.0) that introduced the Deflate compression method.
+const DEFLATE_VERSION: u16 = 20;
+
+/// The ZIP version (1.0) sufficient for stored entries.
+const STORED_VERSION: u16 = 10;
+
+/// The DOS file attribute marking a directory.
+const DOS_DIRECTORY_ATTRIBUTE: u32 = 0x10;
+
+/// The method with which the data of a new entry is compressed.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum CompressionMethod {
+    /// The data is stored without compression. (0)
+    Stored,
+
+    /// The data is compressed using Deflate. (8)
+    #[default]
+    Deflated,
+}
+impl CompressionMethod {
+    /// The value of the "compression method" field corresponding to this method.
+    pub const fn to_u16(&self) -> u16 {
+        match self {
+            Self::Stored => 0,
+            Self::Deflated => 8,
+        }
+    }
+
+    /// The ZIP version required to extract entries compressed using this method.
+    const fn required_version(&self) -> u16 {
+        match self {
+            Self::Stored => STORED_VERSION,
+            Self::Deflated => DEFLATE_VERSION,
+        }
+    }
+}
+impl fmt::Display for CompressionMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stored => write!(f, "stored"),
+            Self::Deflated => write!(f, "deflated"),
+        }
+    }
+}
+
+
+/// Options for an entry that is added to an archive.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct NewEntryOptions {
+    /// The method with which the data is compressed. The default is
+    /// [`CompressionMethod::Deflated`].
+    pub compression_method: CompressionMethod,
+
+    /// The Unix mode (see [`UNIX_MODE_MASK`]) of the entry. The default is 0o644.
+    pub unix_mode: u16,
+
+    /// The time of last modification of the entry. The default is `None`, which means that the
+    /// current time is used.
+    pub modified: Option<SystemTime>,
+
+    /// The time of last access of the entry, which is only stored if
+    /// [`extended_timestamp`](Self::extended_timestamp) is set. The default is `None`.
+    pub accessed: Option<SystemTime>,
+
+    /// Whether to store the timestamps in an extended timestamp extra field.
+    ///
+    /// The MS-DOS date and time fields, in which the time of last modification is always stored,
+    /// have a resolution of two seconds and carry no time zone; they are written in UTC. The
+    /// default is `true`.
+    pub extended_timestamp: bool,
+
+    /// The numeric IDs of the user and group owning the entry, which are stored in an Info-ZIP Unix
+    /// extra field. The default is `None`.
+    pub owner: Option<(u32, u32)>,
+
+    /// The size of the (uncompressed) data of the entry, if known in advance.
+    ///
+    /// The local file header is written before the data, so room for a Zip64 extended information
+    /// extra field must be reserved in it unless the sizes are known to fit into their 32-bit
+    /// fields. If the size is given and small enough, no such room is reserved, and
+    /// [`Error::SizeHintExceeded`] is returned if the data turns out to require it nonetheless.
+    /// The default is `None`, which always reserves the room.
+    pub size_hint: Option<u64>,
+}
+impl Default for NewEntryOptions {
+    fn default() -> Self {
+        Self {
+            compression_method: CompressionMethod::default(),
+            unix_mode: 0o644,
+            modified: None,
+            accessed: None,
+            extended_timestamp: true,
+            owner: None,
+            size_hint: None,
+        }
+    }
+}
+
+
+/// A reader that calculates the CRC-32 checksum and the length of the data read through it.
+struct ChecksumReader<R> {
+    inner: R,
+    crc: Crc,
+    length: u64,
+}
+impl<R: Read> Read for ChecksumReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.crc.update(&buf[..read]);
+        self.length += u64::try_from(read).unwrap();
+        Ok(read)
+    }
+}
+
+
+/// Returns the extra fields carrying the timestamps and the owner of a new entry in its local
+/// header and in its central directory entry.
+fn entry_extra_fields(modification_timestamp: i64, options: &NewEntryOptions) -> Result<(Vec<u8>, Vec<u8>), Error> {
+    let mut local_extra_fields = Vec::new();
+    let mut central_extra_fields = Vec::new();
+
+    // timestamps beyond 2038 cannot be stored in the extended timestamp extra field; the central
+    // directory entry only carries the time of last modification
+    if options.extended_timestamp {
+        let modification_time = i32::try_from(modification_timestamp).ok();
+        let access_time = options.accessed
+            .and_then(|a| i32::try_from(unix_timestamp(a)).ok());
+        let flags =
+            if modification_time.is_some() { 0x01 } else { 0x00 }
+            | if access_time.is_some() { 0x02 } else { 0x00 };
+        if flags != 0x00 {
+            let local_field = ExtendedTimestampExtraField {
+                flags,
+                modification_time,
+                access_time,
+                creation_time: None,
+            };
+            local_field.write(&mut local_extra_fields)?;
+            let central_field = ExtendedTimestampExtraField {
+                access_time: None,
+                ..local_field
+            };
+            central_field.write(&mut central_extra_fields)?;
+        }
+    }
+
+    if let Some((uid, gid)) = options.owner {
+        let owner_field = InfoZipUnixExtraField {
+            version: 1,
+            uid: uid.into(),
+            gid: gid.into(),
+        };
+        owner_field.write(&mut local_extra_fields)?;
+        owner_field.write(&mut central_extra_fields)?;
+    }
+
+    Ok((local_extra_fields, central_extra_fields))
+}
+
+
+/// Returns whether the sizes of an entry whose uncompressed data has the given expected size might
+/// not fit into the 32-bit fields of its local file header.
+fn might_need_zip64(size_hint: Option<u64>, compression_method: CompressionMethod) -> bool {
+    let size = match size_hint {
+        Some(s) => s,
+        None => return true,
+    };
+    let max_compressed_size = match compression_method {
+        CompressionMethod::Stored => size,
+        // Deflate stores incompressible data in blocks with a few bytes of overhead
+        CompressionMethod::Deflated => size.saturating_add(size / 1024 + 1024),
+    };
+    max_compressed_size.max(size) >= u64::from(u32::MAX)
+}
+
+
+/// Writes the given local file header in one go.
+fn write_local_header<W: Write>(mut writer: W, local_header: &LocalFileHeader) -> Result<(), Error> {
+    let mut header_bytes = Vec::with_capacity(local_header.record_len().try_into().unwrap());
+    local_header.write(&mut header_bytes)?;
+    writer.write_all(&header_bytes)?;
+    Ok(())
+}
+
+
+/// Writes a new entry, consisting of its local file header and its data, at the given offset.
+///
+/// `file_type` is the Unix file type of the entry (e.g. [`UNIX_FILE_TYPE_REGULAR`]), which is
+/// combined with the mode from `options`; directories are additionally given the DOS directory
+/// attribute. The entry is marked as having been created on Unix; its name is encoded using
+/// [`encode_entry_name`] and its timestamps and owner are stored in extra fields as specified in
+/// `options`.
+///
+/// The data is read from `data` until its end and compressed according to `options`. Unless the
+/// [size hint](NewEntryOptions::size_hint) shows that the sizes fit into their 32-bit fields, the
+/// local header carries a Zip64 extended information extra field with both sizes (and both 32-bit
+/// size fields are set to 0xFFFFFFFF); the header is rewritten in place once the sizes are known.
+/// The central directory entry only refers to a Zip64 extra field for sizes that do not fit.
+///
+/// Returns the central directory entry describing the new entry, whose
+/// [offset](ZipCentralDirectoryEntry::offset) is not yet known and has been set to 0, as well as
+/// the offset directly after the entry's data.
+pub(crate) fn write_entry<F: Seek + Write, R: Read>(
+    mut zip_file: F,
+    offset: u64,
+    name: &str,
+    file_type: u32,
+    data: R,
+    options: &NewEntryOptions,
+) -> Result<(ZipCentralDirectoryEntry, u64), Error> {
+    let timestamp = unix_timestamp(options.modified.unwrap_or_else(SystemTime::now));
+    let (last_mod_file_time, last_mod_file_date) = dos_time_and_date(timestamp);
+
+    let (local_attribute_fields, central_attribute_fields) = entry_extra_fields(timestamp, options)?;
+    let local_name = encode_entry_name(name, 0, &local_attribute_fields)?;
+    let central_name = encode_entry_name(name, 0, &central_attribute_fields)?;
+
+    // the extra field is written with placeholder sizes first, which keeps the header's length
+    let local_zip64 = might_need_zip64(options.size_hint, options.compression_method);
+    let local_extra_fields = |compressed_size: u64, uncompressed_size: u64| -> Result<Vec<u8>, Error> {
+        let mut extra_fields = Vec::new();
+        if local_zip64 {
+            let zip64_field = Zip64ExtraField {
+                uncompressed_size: Some(uncompressed_size),
+                compressed_size: Some(compressed_size),
+                ..Zip64ExtraField::default()
+            };
+            zip64_field.write(&mut extra_fields)?;
+        }
+        extra_fields.extend_from_slice(&local_name.extra_fields);
+        if extra_fields.len() > 0xFFFF {
+            return Err(Error::FieldTooLong);
+        }
+        Ok(extra_fields)
+    };
+
+    let mut local_header = LocalFileHeader {
+        required_version: if local_zip64 { ZIP64_VERSION } else { options.compression_method.required_version() },
+        general_purpose_bit_flag: local_name.general_purpose_bit_flag,
+        compression_method: options.compression_method.to_u16(),
+        last_mod_file_time,
+        last_mod_file_date,
+        crc32: 0,
+        compressed_size: if local_zip64 { u32::MAX } else { 0 },
+        uncompressed_size: if local_zip64 { u32::MAX } else { 0 },
+        file_name: local_name.file_name.clone(),
+        extra_fields: local_extra_fields(0, 0)?,
+    };
+    zip_file.seek(SeekFrom::Start(offset))?;
+    write_local_header(&mut zip_file, &local_header)?;
+    let data_offset = offset + local_header.record_len();
+
+    // compress the data
+    let mut reader = ChecksumReader {
+        inner: data,
+        crc: Crc::new(),
+        length: 0,
+    };
+    {
+        let mut writer = BufWriter::new(&mut zip_file);
+        match options.compression_method {
+            CompressionMethod::Stored => {
+                io::copy(&mut reader, &mut writer)?;
+            },
+            CompressionMethod::Deflated => {
+                let mut encoder = DeflateEncoder::new(&mut writer, Compression::default());
+                io::copy(&mut reader, &mut encoder)?;
+                encoder.finish()?;
+            },
+        }
+        writer.flush()?;
+    }
+    let compressed_size = zip_file.stream_position()? - data_offset;
+    let uncompressed_size = reader.length;
+    let crc32 = reader.crc.sum();
+
+    let narrow_compressed_size = u32::try_from(compressed_size).ok().filter(|s| *s != u32::MAX);
+    let narrow_uncompressed_size = u32::try_from(uncompressed_size).ok().filter(|s| *s != u32::MAX);
+    let uses_zip64 = narrow_compressed_size.is_none() || narrow_uncompressed_size.is_none();
+    if uses_zip64 && !local_zip64 {
+        return Err(Error::SizeHintExceeded {
+            hint: options.size_hint.unwrap_or(0),
+            found: compressed_size.max(uncompressed_size),
+        });
+    }
+
+    local_header.crc32 = crc32;
+    if local_zip64 {
+        local_header.extra_fields = local_extra_fields(compressed_size, uncompressed_size)?;
+    } else {
+        local_header.compressed_size = narrow_compressed_size.unwrap();
+        local_header.uncompressed_size = narrow_uncompressed_size.unwrap();
+    }
+    zip_file.seek(SeekFrom::Start(offset))?;
+    write_local_header(&mut zip_file, &local_header)?;
+
+    // the central directory entry only contains the large sizes
+    let mut central_extra_fields = Vec::new();
+    if uses_zip64 {
+        let central_zip64 = Zip64ExtraField {
+            uncompressed_size: narrow_uncompressed_size.is_none().then_some(uncompressed_size),
+            compressed_size: narrow_compressed_size.is_none().then_some(compressed_size),
+            ..Zip64ExtraField::default()
+        };
+        central_zip64.write(&mut central_extra_fields)?;
+    }
+    central_extra_fields.extend_from_slice(&central_name.extra_fields);
+    if central_extra_fields.len() > 0xFFFF {
+        return Err(Error::FieldTooLong);
+    }
+
+    let mut external_attributes = (file_type | u32::from(options.unix_mode & UNIX_MODE_MASK)) << 16;
+    if file_type == UNIX_FILE_TYPE_DIRECTORY {
+        external_attributes |= DOS_DIRECTORY_ATTRIBUTE;
+    }
+    let mut entry = CentralDirectoryEntry {
+        creator_version: HostSystem::Unix.apply_to_creator_version(ZIP64_VERSION),
+        required_version: local_header.required_version,
+        general_purpose_bit_flag: local_header.general_purpose_bit_flag,
+        compression_method: local_header.compression_method,
+        last_mod_file_time,
+        last_mod_file_date,
+        crc32,
+        compressed_size: narrow_compressed_size.unwrap_or(u32::MAX),
+        uncompressed_size: narrow_uncompressed_size.unwrap_or(u32::MAX),
+        file_name: central_name.file_name,
+        extra_fields: central_extra_fields,
+        file_comment: Vec::new(),
+        disk_number_start: 0,
+        internal_attributes: 0,
+        external_attributes,
+        local_header_relative_offset: 0,
+    };
+    set_local_header_offset(&mut entry, offset)?;
+
+    let new_entry = ZipCentralDirectoryEntry {
+        entry,
+        disk: 0,
+        offset: 0,
+        compressed_size,
+        uncompressed_size,
+        local_header_offset: offset,
+        disk_number_start: 0,
+    };
+    Ok((new_entry, data_offset + compressed_size))
+}
+
+
+/// Writes a new ZIP archive, one entry after the other.
+///
+/// Each entry is written out, consisting of its local file header and its (compressed) data, as
+/// soon as it is added using [`add_file`](Self::add_file), [`add_directory`](Self::add_directory)
+/// or [`add_symlink`](Self::add_symlink). The entries are marked as having been created on Unix
+/// and carry the Unix mode and time of last modification given in their [`NewEntryOptions`].
+/// [`finish`](Self::finish) then writes the central directory and the end records.
+///
+/// Zip64 extra fields and end records are written whenever the sizes or offsets of entries, the
+/// number of entries or the size or offset of the central directory do not fit into their regular
+/// fields. As the sizes of an entry are only known after its data has been written, its local
+/// header is rewritten afterwards; see [`NewEntryOptions::size_hint`] on how room for a Zip64
+/// extra field is reserved in it.
+///
+/// No check is performed whether multiple entries have the same name.
+#[derive(Debug)]
+pub struct ZipWriter<W> {
+    writer: W,
+    entries: Vec<ZipCentralDirectoryEntry>,
+    offset: u64,
+    comment: Vec<u8>,
+}
+impl<W: Seek + Write> ZipWriter<W> {
+    /// Creates a writer that writes a new archive into the given writer, starting at its current
+    /// position.
+    ///
+    /// Any data preceding this position, such as a self-extractor stub, becomes part of the
+    /// archive: the offsets stored in the archive are relative to the start of `writer`.
+    pub fn new(mut writer: W) -> Result<Self, Error> {
+        let offset = writer.stream_position()?;
+        Ok(Self {
+            writer,
+            entries: Vec::new(),
+            offset,
+            comment: Vec::new(),
+        })
+    }
+
+    /// Sets the comment of the archive, which may be at most 65535 bytes long.
+    pub fn comment(mut self, comment: Vec<u8>) -> Self {
+        self.comment = comment;
+        self
+    }
+
+    /// Adds a regular file whose data is read from `data` (e.g. a byte slice or a
+    /// [`File`](std::fs::File)) until its end.
+    ///
+    /// The data is compressed according to `options`. The CRC-32 checksum and the sizes are
+    /// calculated while writing.
+    pub fn add_file<R: Read>(&mut self, name: &str, data: R, options: &NewEntryOptions) -> Result<(), Error> {
+        self.add_entry(name, UNIX_FILE_TYPE_REGULAR, data, options)
+    }
+
+    /// Adds a directory.
+    ///
+    /// A slash is appended to the name if it does not end with one. Directories have no data; the
+    /// compression method in `options` is ignored.
+    pub fn add_directory(&mut self, name: &str, options: &NewEntryOptions) -> Result<(), Error> {
+        let mut directory_name = name.to_owned();
+        if !directory_name.ends_with('/') {
+            directory_name.push('/');
+        }
+        let stored_options = NewEntryOptions {
+            compression_method: CompressionMethod::Stored,
+            size_hint: Some(0),
+            ..*options
+        };
+        self.add_entry(&directory_name, UNIX_FILE_TYPE_DIRECTORY, io::empty(), &stored_options)
+    }
+
+    /// Adds a symbolic link pointing to the given target.
+    ///
+    /// The target is stored as the data of the entry. It is always stored without compression, as
+    /// some tools (including [`zip_read_symlink_target`](crate::zip_read_symlink_target)) do not
+    /// support compressed symbolic links; the compression method in `options` is ignored. Symbolic
+    /// links usually have the mode 0o777.
+    pub fn add_symlink(&mut self, name: &str, target: &[u8], options: &NewEntryOptions) -> Result<(), Error> {
+        let stored_options = NewEntryOptions {
+            compression_method: CompressionMethod::Stored,
+            size_hint: Some(u64::try_from(target.len()).unwrap()),
+            ..*options
+        };
+        self.add_entry(name, UNIX_FILE_TYPE_SYMLINK, target, &stored_options)
+    }
+
+    /// Returns the entries that have been added so far.
+    ///
+    /// The [offsets of their central directory entries](ZipCentralDirectoryEntry::offset) are not
+    /// yet known and have been set to 0.
+    pub fn entries(&self) -> &[ZipCentralDirectoryEntry] {
+        &self.entries
+    }
+
+    /// Writes the central directory and the end records, returning the underlying writer.
+    ///
+    /// The writer is positioned directly after the end of the archive.
+    pub fn finish(mut self) -> Result<W, Error> {
+        let entries: Vec<CentralDirectoryEntry> = self.entries.iter()
+            .map(|e| e.entry.clone())
+            .collect();
+
+        self.writer.seek(SeekFrom::Start(self.offset))?;
+        let mut writer = BufWriter::new(&mut self.writer);
+        write_central_directory(&mut writer, &entries, self.offset, &self.comment)?;
+        writer.flush()?;
+        drop(writer);
+
+        Ok(self.writer)
+    }
+
+    fn add_entry<R: Read>(&mut self, name: &str, file_type: u32, data: R, options: &NewEntryOptions) -> Result<(), Error> {
+        let (entry, end_offset) = write_entry(&mut self.writer, self.offset, name, file_type, data, options)?;
+        self.entries.push(entry);
+        self.offset = end_offset;
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use crate::{
+        EntryKind, ReadOptions, zip_check, zip_read_archive, zip_read_entry_data,
+        zip_read_local_header, zip_read_symlink_target, ZipArchive,
+    };
+    use crate::extra_field::RawExtraFieldIter;
+
+    /// A file whose bytes from `gap_start` up to `gap_end` are not stored and read as zeros.
+    ///
+    /// This allows writing entries larger than 4 GiB consisting of zeros.
+    struct GapFile {
+        head: Vec<u8>,
+        tail: Vec<u8>,
+        gap_start: u64,
+        gap_end: u64,
+        position: u64,
+    }
+    impl GapFile {
+        fn new(gap_start: u64, gap_end: u64) -> Self {
+            Self { head: Vec::new(), tail: Vec::new(), gap_start, gap_end, position: 0 }
+        }
+
+        fn len(&self) -> u64 {
+            if self.tail.is_empty() {
+                u64::try_from(self.head.len()).unwrap().max(self.position.min(self.gap_end))
+            } else {
+                self.gap_end + u64::try_from(self.tail.len()).unwrap()
+            }
+        }
+    }
+    impl Read for GapFile {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = if self.position < self.gap_start {
+                let start = usize::try_from(self.position).unwrap().min(self.head.len());
+                (&self.head[start..]).read(buf)?
+            } else if self.position < self.gap_end {
+                let n = buf.len().min(usize::try_from(self.gap_end - self.position).unwrap_or(usize::MAX));
+                buf[..n].fill(0);
+                n
+            } else {
+                let start = usize::try_from(self.position - self.gap_end).unwrap().min(self.tail.len());
+                (&self.tail[start..]).read(buf)?
+            };
+            self.position += u64::try_from(n).unwrap();
+            Ok(n)
+        }
+    }
+    impl Write for GapFile {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let (part, n) = if self.position < self.gap_start {
+                let n = buf.len().min(usize::try_from(self.gap_start - self.position).unwrap());
+                (Some((&mut self.head, usize::try_from(self.position).unwrap())), n)
+            } else if self.position < self.gap_end {
+                (None, buf.len().min(usize::try_from(self.gap_end - self.position).unwrap_or(usize::MAX)))
+            } else {
+                (Some((&mut self.tail, usize::try_from(self.position - self.gap_end).unwrap())), buf.len())
+            };
+            if let Some((part, start)) = part {
+                if part.len() < start + n {
+                    part.resize(start + n, 0);
+                }
+                part[start..start + n].copy_from_slice(&buf[..n]);
+            }
+            self.position += u64::try_from(n).unwrap();
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+    impl Seek for GapFile {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.position = match pos {
+                SeekFrom::Start(p) => p,
+                SeekFrom::End(d) => self.len().checked_add_signed(d).unwrap(),
+                SeekFrom::Current(d) => self.position.checked_add_signed(d).unwrap(),
+            };
+            Ok(self.position)
+        }
+    }
+
+    fn has_zip64_extra_field(extra_fields: &[u8]) -> bool {
+        RawExtraFieldIter::new(extra_fields)
+            .map(Result::unwrap)
+            .any(|(tag, _block)| tag == Zip64ExtraField::tag())
+    }
+
+    fn read_back(build: impl FnOnce(&mut ZipWriter<Cursor<Vec<u8>>>)) -> (Cursor<Vec<u8>>, ZipArchive) {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new())).unwrap();
+        build(&mut writer);
+        let mut zip_file = writer.finish().unwrap();
+        let archive = zip_read_archive(&mut zip_file, &ReadOptions::default()).unwrap();
+        assert_eq!(zip_check(&mut zip_file).unwrap(), Vec::new());
+        (zip_file, archive)
+    }
+
+    #[test]
+    fn test_might_need_zip64() {
+        let below = u64::from(u32::MAX) - 1;
+        assert!(!might_need_zip64(Some(0), CompressionMethod::Stored));
+        assert!(!might_need_zip64(Some(below), CompressionMethod::Stored));
+        assert!(might_need_zip64(Some(below + 1), CompressionMethod::Stored));
+        assert!(might_need_zip64(None, CompressionMethod::Stored));
+
+        // Deflate may expand incompressible data slightly
+        assert!(might_need_zip64(Some(below), CompressionMethod::Deflated));
+        assert!(!might_need_zip64(Some(0xFF00_0000), CompressionMethod::Deflated));
+        assert!(might_need_zip64(Some(u64::MAX), CompressionMethod::Deflated));
+    }
+
+    #[test]
+    fn test_size_hint() {
+        let data = b"some data that is written with and without a size hint";
+        let (mut zip_file, archive) = read_back(|writer| {
+            for (name, size_hint) in [("hint.txt", Some(data.len() as u64)), ("no-hint.txt", None)] {
+                for compression_method in [CompressionMethod::Stored, CompressionMethod::Deflated] {
+                    let options = NewEntryOptions { compression_method, size_hint, ..Default::default() };
+                    writer.add_file(&format!("{}-{}", compression_method, name), &data[..], &options).unwrap();
+                }
+            }
+        });
+
+        for entry in &archive.entries {
+            let name = String::from_utf8(entry.entry.file_name.clone()).unwrap();
+            let local_header = zip_read_local_header(&mut zip_file, entry).unwrap().header;
+            let reserved = name.ends_with("no-hint.txt");
+
+            // without a hint, room for both sizes is reserved in the local header
+            assert_eq!(has_zip64_extra_field(&local_header.extra_fields), reserved, "{}", name);
+            assert_eq!(local_header.required_version == ZIP64_VERSION, reserved, "{}", name);
+            if reserved {
+                assert_eq!(local_header.compressed_size, u32::MAX);
+                assert_eq!(local_header.uncompressed_size, u32::MAX);
+            } else {
+                assert_eq!(u64::from(local_header.compressed_size), entry.compressed_size);
+                assert_eq!(u64::from(local_header.uncompressed_size), entry.uncompressed_size);
+            }
+            assert_eq!(local_header.crc32, entry.entry.crc32);
+
+            // the central directory only refers to Zip64 for sizes that do not fit
+            assert!(!has_zip64_extra_field(&entry.entry.extra_fields), "{}", name);
+            assert_eq!(entry.uncompressed_size, data.len() as u64);
+
+            let mut read = Vec::new();
+            zip_read_entry_data(&mut zip_file, entry, &mut read).unwrap();
+            assert_eq!(read, data);
+        }
+    }
+
+    #[test]
+    fn test_zip64_entry() {
+        let size = u64::from(u32::MAX) + 1;
+        let mut writer = ZipWriter::new(GapFile::new(0x1000, size)).unwrap();
+        let options = NewEntryOptions {
+            compression_method: CompressionMethod::Stored,
+            ..Default::default()
+        };
+        writer.add_file("large.bin", io::repeat(0).take(size), &options).unwrap();
+        writer.add_file("small.txt", &b"after the large entry"[..], &options).unwrap();
+        let mut zip_file = writer.finish().unwrap();
+
+        let archive = zip_read_archive(&mut zip_file, &ReadOptions::default()).unwrap();
+        let large = &archive.entries[0];
+        assert_eq!(large.uncompressed_size, size);
+        assert_eq!(large.compressed_size, size);
+        assert_eq!(large.entry.uncompressed_size, u32::MAX);
+        assert_eq!(large.entry.compressed_size, u32::MAX);
+        assert!(has_zip64_extra_field(&large.entry.extra_fields));
+        // the CRC-32 checksum of 4 GiB of zeros
+        assert_eq!(large.entry.crc32, 0xD202_EF8D);
+
+        // the second entry lies beyond 4 GiB and needs a Zip64 extra field for its offset only
+        let small = &archive.entries[1];
+        assert!(small.local_header_offset > u32::MAX.into());
+        assert_eq!(small.entry.local_header_relative_offset, -1);
+        assert_eq!(small.entry.compressed_size, 21);
+        assert!(has_zip64_extra_field(&small.entry.extra_fields));
+        let mut read = Vec::new();
+        zip_read_entry_data(&mut zip_file, small, &mut read).unwrap();
+        assert_eq!(read, b"after the large entry");
+    }
+
+    #[test]
+    fn test_size_hint_exceeded() {
+        let size = u64::from(u32::MAX);
+        let mut writer = ZipWriter::new(GapFile::new(0x1000, size)).unwrap();
+        let options = NewEntryOptions {
+            compression_method: CompressionMethod::Stored,
+            size_hint: Some(1000),
+            ..Default::default()
+        };
+        let result = writer.add_file("large.bin", io::repeat(0).take(size), &options);
+        assert!(
+            matches!(result, Err(Error::SizeHintExceeded { hint: 1000, found }) if found == size),
+            "{:?}", result,
+        );
+    }
+
+    #[test]
+    fn test_directory_and_symlink() {
+        let modified = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        let (mut zip_file, archive) = read_back(|writer| {
+            let options = NewEntryOptions {
+                unix_mode: 0o750,
+                modified: Some(modified),
+                ..Default::default()
+            };
+            writer.add_directory("directory", &options).unwrap();
+            writer.add_directory("other/", &options).unwrap();
+            let options = NewEntryOptions {
+                unix_mode: 0o777,
+                modified: Some(modified),
+                compression_method: CompressionMethod::Deflated,
+                ..Default::default()
+            };
+            writer.add_symlink("directory/link", b"../other", &options).unwrap();
+        });
+
+        let directory = &archive.entries[0];
+        assert_eq!(directory.entry.file_name, b"directory/");
+        assert_eq!(archive.entries[1].entry.file_name, b"other/");
+        assert_eq!(directory.kind(), EntryKind::Directory);
+        assert_eq!(directory.unix_attributes(), Some(UNIX_FILE_TYPE_DIRECTORY | 0o750));
+        assert_eq!(directory.entry.external_attributes & DOS_DIRECTORY_ATTRIBUTE, DOS_DIRECTORY_ATTRIBUTE);
+        assert_eq!(directory.entry.compression_method, CompressionMethod::Stored.to_u16());
+        assert_eq!(directory.uncompressed_size, 0);
+        assert_eq!(directory.modification_time(), Some(modified));
+
+        let symlink = &archive.entries[2];
+        assert_eq!(symlink.kind(), EntryKind::Symlink);
+        assert_eq!(symlink.unix_attributes(), Some(UNIX_FILE_TYPE_SYMLINK | 0o777));
+        assert_eq!(symlink.entry.external_attributes & DOS_DIRECTORY_ATTRIBUTE, 0);
+        assert_eq!(symlink.entry.compression_method, CompressionMethod::Stored.to_u16());
+        assert_eq!(zip_read_symlink_target(&mut zip_file, symlink).unwrap(), b"../other");
+        assert_eq!(symlink.modification_time(), Some(modified));
+    }
+}
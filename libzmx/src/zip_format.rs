@@ -5,6 +5,7 @@ use std::io::{Read, Write};
 
 use zmx_macros::minimum_length;
 
+use crate::extra_field::{ExtraFieldIter, RawExtraFieldIter};
 use crate::io_ext::{ReadExt, WriteExt};
 
 
@@ -34,9 +35,8 @@ pub(crate) struct EndOfCentralDirectory {
 
     /// The ZIP file comment.
     ///
-    /// `None` if there is a comment but it is too long for its size to fit in a 16-bit field. Empty
-    /// comments are stored as `Some(v)` with an empty `v`.
-    pub comment: Option<Vec<u8>>,
+    /// The comment may be at most 65535 bytes long.
+    pub comment: Vec<u8>,
 }
 impl EndOfCentralDirectory {
     /// The constant signature of an End of Central Directory record.
@@ -50,7 +50,9 @@ impl EndOfCentralDirectory {
     }
 
     /// Write the end-of-central-directory record.
-    #[allow(unused)]
+    ///
+    /// Returns [`Error::FieldTooLong`](crate::Error::FieldTooLong) if the comment is longer than
+    /// 65535 bytes.
     pub fn write<W: Write>(&self, mut writer: W) -> Result<(), crate::Error> {
         // write signature
         writer.write_u32_le(Self::signature())?;
@@ -63,16 +65,10 @@ impl EndOfCentralDirectory {
         writer.write_u32_le(self.central_directory_size)?;
         writer.write_u32_le(self.central_dir_offset_on_disk)?;
 
-        match &self.comment {
-            Some(c) => {
-                let length: u16 = c.len().try_into().unwrap_or(0xFFFF);
-                writer.write_u16_le(length)?;
-                writer.write_all(c)?;
-            },
-            None => {
-                writer.write_u16_le(0xFFFF)?;
-            },
-        }
+        let comment_length: u16 = self.comment.len().try_into()
+            .map_err(|_| crate::Error::FieldTooLong)?;
+        writer.write_u16_le(comment_length)?;
+        writer.write_all(&self.comment)?;
 
         Ok(())
     }
@@ -89,14 +85,8 @@ impl EndOfCentralDirectory {
         let central_dir_offset_on_disk = reader.read_u32_le()?;
 
         let comment_length = reader.read_u16_le()?;
-        let comment = if comment_length == 0xFFFF {
-            None
-        } else {
-            let buf_length: usize = comment_length.try_into().unwrap();
-            let mut buf = vec![0u8; buf_length];
-            reader.read_exact(&mut buf)?;
-            Some(buf)
-        };
+        let mut comment = vec![0u8; comment_length.into()];
+        reader.read_exact(&mut comment)?;
 
         Ok(Self {
             disk_no,
@@ -149,13 +139,11 @@ impl Zip64EndOfCentralDirectoryLocator {
     /// It is equivalent to `b"PK\x06\x07"`, interpreted as `u32` in little-endian byte order.
     pub const fn signature() -> u32 { 0x07064B50 }
 
-    #[allow(unused)]
     const fn min_len_bias() -> u64 {
         4 // signature
     }
 
     /// Write the Zip64 end-of-central-directory locator record.
-    #[allow(unused)]
     pub fn write<W: Write>(&self, mut writer: W) -> Result<(), crate::Error> {
         // write signature
         writer.write_u32_le(Self::signature())?;
@@ -225,17 +213,18 @@ impl Zip64EndOfCentralDirectory {
     /// It is equivalent to `b"PK\x06\x06"`, interpreted as `u32` in little-endian byte order.
     pub const fn signature() -> u32 { 0x06064B50 }
 
-    const fn min_len_bias() -> u64 {
+    /// The length of the parts of the record that precede the data counted by its length field
+    /// (the signature and the length field itself).
+    pub const fn min_len_bias() -> u64 {
         4 // signature
         + 8 // length
     }
 
     /// Write the Zip64 end-of-central-directory record.
-    #[allow(unused)]
     pub fn write<W: Write>(&self, mut writer: W) -> Result<(), crate::Error> {
         // length is that of the whole structure including the extensible data sector
         // but excluding the signature (4 bytes) and the length field (8 bytes)
-        let length: u64 = Self::min_len() + u64::try_from(self.extensible_data_sector.len()).unwrap();
+        let length: u64 = Self::min_len() - Self::min_len_bias() + u64::try_from(self.extensible_data_sector.len()).unwrap();
 
         // write signature and length
         writer.write_u32_le(Self::signature())?;
@@ -258,13 +247,22 @@ impl Zip64EndOfCentralDirectory {
     /// Read a Zip64 end-of-central-directory record.
     ///
     /// It is assumed that the reader is positioned after the signature.
-    pub fn read_after_signature<R: Read>(mut reader: R) -> Result<Self, crate::Error> {
+    ///
+    /// `max_length` is the maximum number of bytes that may follow the record's length field, e.g.
+    /// the number of bytes between it and the Zip64 end-of-central-directory locator. If the record
+    /// declares a greater length, [`Error::RecordTooLarge`](crate::Error::RecordTooLarge) is
+    /// returned instead of attempting to read (and allocate memory for) it.
+    pub fn read_after_signature<R: Read>(mut reader: R, max_length: u64) -> Result<Self, crate::Error> {
         let size = reader.read_u64_le()?;
         const FIXED_FIELDS_LEN: u64 = Zip64EndOfCentralDirectory::min_len() - Zip64EndOfCentralDirectory::min_len_bias();
         if size < FIXED_FIELDS_LEN {
             return Err(crate::Error::RecordTooSmall);
         }
-        let extensible_length: usize = (size - Self::min_len()).try_into().unwrap();
+        if size > max_length {
+            return Err(crate::Error::RecordTooLarge);
+        }
+        let extensible_length: usize = (size - FIXED_FIELDS_LEN).try_into()
+            .map_err(|_| crate::Error::RecordTooLarge)?;
 
         let creator_version = reader.read_u16_le()?;
         let required_version = reader.read_u16_le()?;
@@ -355,29 +353,27 @@ impl CentralDirectoryEntry {
 
     const fn min_len_bias() -> u64 {
         4 // signature
+        + 2 // file name length
+        + 2 // extra field length
+        + 2 // file comment length
     }
 
     /// Write the central directory header record.
+    ///
+    /// Returns [`Error::FieldTooLong`](crate::Error::FieldTooLong) if the file name, extra field or
+    /// file comment is longer than 65535 bytes.
     pub fn write<W: Write>(&self, mut writer: W) -> Result<(), crate::Error> {
+
+        let file_name_length: u16 = self.file_name.len().try_into()
+            .map_err(|_| crate::Error::FieldTooLong)?;
+        let extra_field_length: u16 = self.extra_fields.len().try_into()
+            .map_err(|_| crate::Error::FieldTooLong)?;
+        let file_comment_length: u16 = self.file_comment.len().try_into()
+            .map_err(|_| crate::Error::FieldTooLong)?;
+
         // write signature
         writer.write_u32_le(Self::signature())?;
 
-        let file_name_length: u16 = if self.file_name.len() > 0xFFFF {
-            0xFFFF
-        } else {
-            self.file_name.len().try_into().unwrap()
-        };
-        let extra_field_length: u16 = if self.extra_fields.len() > 0xFFFF {
-            0xFFFF
-        } else {
-            self.extra_fields.len().try_into().unwrap()
-        };
-        let file_comment_length: u16 = if self.file_comment.len() > 0xFFFF {
-            0xFFFF
-        } else {
-            self.file_comment.len().try_into().unwrap()
-        };
-
         // write out fields in turn
         writer.write_u16_le(self.creator_version)?;
         writer.write_u16_le(self.required_version)?;
@@ -452,6 +448,273 @@ impl CentralDirectoryEntry {
             local_header_relative_offset,
         })
     }
+
+    /// Returns an iterator decoding the extra fields of this entry.
+    pub fn extra_field_iter(&self) -> ExtraFieldIter<'_> {
+        ExtraFieldIter::new(
+            &self.extra_fields,
+            self.uncompressed_size,
+            self.compressed_size,
+            self.local_header_relative_offset,
+            self.disk_number_start,
+        )
+    }
+}
+
+
+/// The general-purpose flag specifying that the CRC-32 checksum and sizes of an entry are stored in
+/// a [data descriptor](DataDescriptor) following its data instead of its local file header.
+pub(crate) const DATA_DESCRIPTOR_FLAG: u16 = 1 << 3;
+
+
+/// The "Local File Header" record.
+///
+/// This precedes the data of each entry and duplicates most of the information in its
+/// [central directory entry](CentralDirectoryEntry).
+#[minimum_length(biased)]
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct LocalFileHeader {
+    /// ZIP version required to extract this entry.
+    pub required_version: u16,
+
+    /// General-purpose field of bit flags.
+    pub general_purpose_bit_flag: u16,
+
+    /// Method with which the file was compressed.
+    pub compression_method: u16,
+
+    /// The file's time of last modification.
+    pub last_mod_file_time: u16,
+
+    /// The file's date of last modification.
+    pub last_mod_file_date: u16,
+
+    /// CRC-32 checksum of the data.
+    ///
+    /// Zero if the entry has a [data descriptor](Self::has_data_descriptor).
+    pub crc32: u32,
+
+    /// The compressed size of this file.
+    ///
+    /// Zero if the entry has a [data descriptor](Self::has_data_descriptor).
+    pub compressed_size: u32,
+
+    /// The uncompressed size of this file.
+    ///
+    /// Zero if the entry has a [data descriptor](Self::has_data_descriptor).
+    pub uncompressed_size: u32,
+
+    /// The file name of this entry.
+    pub file_name: Vec<u8>,
+
+    /// Data in the extra field of this entry.
+    pub extra_fields: Vec<u8>,
+}
+impl LocalFileHeader {
+    /// The constant signature of a Local File Header record.
+    ///
+    /// It is equivalent to `b"PK\x03\x04"`, interpreted as `u32` in little-endian byte order.
+    pub const fn signature() -> u32 { 0x04034B50 }
+
+    const fn min_len_bias() -> u64 {
+        4 // signature
+        + 2 // file name length
+        + 2 // extra field length
+    }
+
+    /// The length of this record, including its signature, file name and extra fields.
+    pub fn record_len(&self) -> u64 {
+        Self::min_len()
+            + u64::try_from(self.file_name.len()).unwrap()
+            + u64::try_from(self.extra_fields.len()).unwrap()
+    }
+
+    /// Whether the CRC-32 checksum and sizes of this entry are stored in a [data
+    /// descriptor](DataDescriptor) following its data.
+    pub const fn has_data_descriptor(&self) -> bool {
+        self.general_purpose_bit_flag & DATA_DESCRIPTOR_FLAG != 0
+    }
+
+    /// Returns the compressed and uncompressed size of this entry, taking them from the Zip64
+    /// extended information extra field if they are saturated.
+    ///
+    /// Unlike in the central directory, the Zip64 extra field in a local header must contain both
+    /// sizes if either of them is saturated. Some writers only store the saturated value, which is
+    /// also supported.
+    pub fn sizes(&self) -> (u64, u64) {
+        let mut compressed_size = u64::from(self.compressed_size);
+        let mut uncompressed_size = u64::from(self.uncompressed_size);
+        if self.compressed_size != u32::MAX && self.uncompressed_size != u32::MAX {
+            return (compressed_size, uncompressed_size);
+        }
+
+        let zip64_block = RawExtraFieldIter::new(&self.extra_fields)
+            .filter_map(|b| b.ok())
+            .find(|(tag, _block)| *tag == Zip64ExtraField::tag())
+            .map(|(_tag, block)| block);
+        if let Some(mut block) = zip64_block {
+            let both = block.len() >= 16;
+            if both || self.uncompressed_size == u32::MAX {
+                if let Ok(value) = block.read_u64_le() {
+                    uncompressed_size = value;
+                }
+            }
+            if both || self.compressed_size == u32::MAX {
+                if let Ok(value) = block.read_u64_le() {
+                    compressed_size = value;
+                }
+            }
+        }
+        (compressed_size, uncompressed_size)
+    }
+
+    /// Write the local file header record.
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), crate::Error> {
+        let file_name_length: u16 = self.file_name.len().try_into()
+            .map_err(|_| crate::Error::FieldTooLong)?;
+        let extra_field_length: u16 = self.extra_fields.len().try_into()
+            .map_err(|_| crate::Error::FieldTooLong)?;
+
+        // write signature
+        writer.write_u32_le(Self::signature())?;
+
+        // write out fields in turn
+        writer.write_u16_le(self.required_version)?;
+        writer.write_u16_le(self.general_purpose_bit_flag)?;
+        writer.write_u16_le(self.compression_method)?;
+        writer.write_u16_le(self.last_mod_file_time)?;
+        writer.write_u16_le(self.last_mod_file_date)?;
+        writer.write_u32_le(self.crc32)?;
+        writer.write_u32_le(self.compressed_size)?;
+        writer.write_u32_le(self.uncompressed_size)?;
+        writer.write_u16_le(file_name_length)?;
+        writer.write_u16_le(extra_field_length)?;
+
+        writer.write_all(&self.file_name)?;
+        writer.write_all(&self.extra_fields)?;
+
+        Ok(())
+    }
+
+    /// Read a local file header record.
+    ///
+    /// It is assumed that the reader is positioned after the signature.
+    pub fn read_after_signature<R: Read>(mut reader: R) -> Result<Self, crate::Error> {
+        let required_version = reader.read_u16_le()?;
+        let general_purpose_bit_flag = reader.read_u16_le()?;
+        let compression_method = reader.read_u16_le()?;
+        let last_mod_file_time = reader.read_u16_le()?;
+        let last_mod_file_date = reader.read_u16_le()?;
+        let crc32 = reader.read_u32_le()?;
+        let compressed_size = reader.read_u32_le()?;
+        let uncompressed_size = reader.read_u32_le()?;
+        let file_name_length = reader.read_u16_le()?;
+        let extra_field_length = reader.read_u16_le()?;
+
+        let mut file_name = vec![0u8; file_name_length.into()];
+        reader.read_exact(&mut file_name)?;
+
+        let mut extra_fields = vec![0u8; extra_field_length.into()];
+        reader.read_exact(&mut extra_fields)?;
+
+        Ok(Self {
+            required_version,
+            general_purpose_bit_flag,
+            compression_method,
+            last_mod_file_time,
+            last_mod_file_date,
+            crc32,
+            compressed_size,
+            uncompressed_size,
+            file_name,
+            extra_fields,
+        })
+    }
+}
+
+
+/// The "Data Descriptor" record.
+///
+/// This follows the data of an entry whose local header has the [corresponding
+/// flag](LocalFileHeader::has_data_descriptor) set, allowing the CRC-32 checksum and sizes to be
+/// written after the data has been compressed. The record has four variants: it may or may not
+/// start with a signature, and its sizes are 64 bits wide in Zip64 entries and 32 bits wide
+/// otherwise.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct DataDescriptor {
+    /// Whether the record starts with its signature.
+    pub has_signature: bool,
+
+    /// CRC-32 checksum of the data.
+    pub crc32: u32,
+
+    /// The compressed size of this file.
+    pub compressed_size: u64,
+
+    /// The uncompressed size of this file.
+    pub uncompressed_size: u64,
+}
+impl DataDescriptor {
+    /// The constant signature of a Data Descriptor record.
+    ///
+    /// It is equivalent to `b"PK\x07\x08"`, interpreted as `u32` in little-endian byte order.
+    pub const fn signature() -> u32 { 0x08074B50 }
+
+    /// The length of this record when written in the given variant.
+    pub const fn record_len(&self, zip64: bool) -> u64 {
+        (if self.has_signature { 4 } else { 0 })
+        + 4 // crc32
+        + if zip64 { 2*8 } else { 2*4 } // compressed_size, uncompressed_size
+    }
+
+    /// Write the data descriptor record, with 64-bit sizes if `zip64` is set.
+    ///
+    /// Returns [`Error::FieldTooLong`](crate::Error::FieldTooLong) if `zip64` is not set and a size
+    /// does not fit into 32 bits.
+    pub fn write<W: Write>(&self, mut writer: W, zip64: bool) -> Result<(), crate::Error> {
+        if self.has_signature {
+            writer.write_u32_le(Self::signature())?;
+        }
+        writer.write_u32_le(self.crc32)?;
+        if zip64 {
+            writer.write_u64_le(self.compressed_size)?;
+            writer.write_u64_le(self.uncompressed_size)?;
+        } else {
+            let compressed_size: u32 = self.compressed_size.try_into()
+                .map_err(|_| crate::Error::FieldTooLong)?;
+            let uncompressed_size: u32 = self.uncompressed_size.try_into()
+                .map_err(|_| crate::Error::FieldTooLong)?;
+            writer.write_u32_le(compressed_size)?;
+            writer.write_u32_le(uncompressed_size)?;
+        }
+        Ok(())
+    }
+
+    /// Read a data descriptor record, with 64-bit sizes if `zip64` is set.
+    ///
+    /// It is assumed that the reader is positioned directly after the data of the entry. As the
+    /// signature is optional, a record whose CRC-32 checksum happens to equal the signature is
+    /// misinterpreted; this ambiguity is inherent to the format.
+    pub fn read<R: Read>(mut reader: R, zip64: bool) -> Result<Self, crate::Error> {
+        let first_value = reader.read_u32_le()?;
+        let (has_signature, crc32) = if first_value == Self::signature() {
+            (true, reader.read_u32_le()?)
+        } else {
+            (false, first_value)
+        };
+        let (compressed_size, uncompressed_size) = if zip64 {
+            (reader.read_u64_le()?, reader.read_u64_le()?)
+        } else {
+            (reader.read_u32_le()?.into(), reader.read_u32_le()?.into())
+        };
+
+        Ok(Self {
+            has_signature,
+            crc32,
+            compressed_size,
+            uncompressed_size,
+        })
+    }
 }
 
 
@@ -460,7 +723,7 @@ impl CentralDirectoryEntry {
 /// This is one of the possible fields in a central directory entry's
 /// [`extra_fields`](CentralDirectoryEntry::extra_fields).
 #[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
-pub(crate) struct Zip64ExtraField {
+pub struct Zip64ExtraField {
     /// The uncompressed size of this file.
     pub uncompressed_size: Option<u64>,
 
@@ -478,7 +741,6 @@ impl Zip64ExtraField {
     pub const fn tag() -> u16 { 0x0001 }
 
     /// Write the extra field, including tag and length.
-    #[allow(unused)]
     pub fn write<W: Write>(&self, mut writer: W) -> Result<(), crate::Error> {
         // write tag
         writer.write_u16_le(Self::tag())?;
@@ -516,7 +778,6 @@ impl Zip64ExtraField {
     ///
     /// The relevant values from the central directory entry must be passed, as the extra field only
     /// contains those values that are out-of-range (and, therefore, have their max value).
-    #[allow(unused)]
     pub fn read_after_tag<R: Read>(
         mut reader: R,
         cdir_uncompressed_size: u32,
@@ -574,4 +835,337 @@ impl Zip64ExtraField {
             disk_number_start,
         })
     }
+
+    /// Decode the data of the extra field (without tag and length), tolerating unexpected lengths.
+    ///
+    /// Unlike [`read_after_tag`](Self::read_after_tag), the values are read in their specified
+    /// order as far as the data suffices: values beyond its end remain `None`, so that the values
+    /// from the central directory entry apply, and trailing data is ignored. The latter is written,
+    /// for example, by writers that always store all three 64-bit values.
+    pub fn from_data_lenient(
+        mut data: &[u8],
+        cdir_uncompressed_size: u32,
+        cdir_compressed_size: u32,
+        cdir_local_header_relative_offset: i32,
+        cdir_disk_number_start: u16,
+    ) -> Self {
+        let uncompressed_size = if cdir_uncompressed_size == u32::MAX {
+            data.read_u64_le().ok()
+        } else {
+            None
+        };
+        let compressed_size = if cdir_compressed_size == u32::MAX {
+            data.read_u64_le().ok()
+        } else {
+            None
+        };
+        let local_header_relative_offset = if cdir_local_header_relative_offset == -1 {
+            data.read_i64_le().ok()
+        } else {
+            None
+        };
+        let disk_number_start = if cdir_disk_number_start == u16::MAX {
+            data.read_u32_le().ok()
+        } else {
+            None
+        };
+
+        Self {
+            uncompressed_size,
+            compressed_size,
+            local_header_relative_offset,
+            disk_number_start,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extra_field::with_length_prefix;
+
+    fn round_trip_eocd(eocd: &EndOfCentralDirectory) {
+        let mut buf = Vec::new();
+        eocd.write(&mut buf).unwrap();
+        assert_eq!(buf.len(), usize::try_from(EndOfCentralDirectory::min_len()).unwrap() + eocd.comment.len());
+
+        let mut reader = buf.as_slice();
+        assert_eq!(reader.read_u32_le().unwrap(), EndOfCentralDirectory::signature());
+        let read_eocd = EndOfCentralDirectory::read_after_signature(&mut reader).unwrap();
+        assert_eq!(&read_eocd, eocd);
+        assert_eq!(reader.len(), 0);
+    }
+
+    #[test]
+    fn test_eocd_round_trip() {
+        let mut eocd = EndOfCentralDirectory {
+            disk_no: 0,
+            start_central_dir_disk_no: 0,
+            total_central_dir_entries_this_disk: 3,
+            total_central_dir_entries: 3,
+            central_directory_size: 0x1234,
+            central_dir_offset_on_disk: 0xDEADBEEF,
+            comment: Vec::new(),
+        };
+        round_trip_eocd(&eocd);
+
+        eocd.comment = b"archive comment".to_vec();
+        round_trip_eocd(&eocd);
+
+        // the longest possible comment
+        eocd.comment = vec![b'x'; 0xFFFF];
+        round_trip_eocd(&eocd);
+    }
+
+    #[test]
+    fn test_eocd_comment_too_long() {
+        let eocd = EndOfCentralDirectory {
+            comment: vec![b'x'; 0x10000],
+            ..Default::default()
+        };
+        let mut buf = Vec::new();
+        assert!(matches!(eocd.write(&mut buf), Err(crate::Error::FieldTooLong)));
+    }
+
+    #[test]
+    fn test_zip64_eocd_round_trip() {
+        let mut zip64_eocd = Zip64EndOfCentralDirectory {
+            creator_version: 0x031E,
+            required_version: 45,
+            disk_no: 0,
+            start_central_dir_disk_no: 0,
+            total_central_dir_entries_this_disk: 70000,
+            total_central_dir_entries: 70000,
+            central_directory_size: 0x1_0000_0000,
+            central_dir_offset_on_disk: 0x2_0000_0000,
+            extensible_data_sector: Vec::new(),
+        };
+
+        for extensible_data_sector in [Vec::new(), b"extensible".to_vec()] {
+            zip64_eocd.extensible_data_sector = extensible_data_sector;
+
+            let mut buf = Vec::new();
+            zip64_eocd.write(&mut buf).unwrap();
+            let expected_length = Zip64EndOfCentralDirectory::min_len() + u64::try_from(zip64_eocd.extensible_data_sector.len()).unwrap();
+            assert_eq!(u64::try_from(buf.len()).unwrap(), expected_length);
+
+            let mut reader = buf.as_slice();
+            assert_eq!(reader.read_u32_le().unwrap(), Zip64EndOfCentralDirectory::signature());
+            let read_zip64_eocd = Zip64EndOfCentralDirectory::read_after_signature(&mut reader, u64::MAX).unwrap();
+            assert_eq!(read_zip64_eocd, zip64_eocd);
+            assert_eq!(reader.len(), 0);
+        }
+    }
+
+    #[test]
+    fn test_zip64_eocd_length_limits() {
+        let zip64_eocd = Zip64EndOfCentralDirectory {
+            extensible_data_sector: b"extensible".to_vec(),
+            ..Default::default()
+        };
+        let mut buf = Vec::new();
+        zip64_eocd.write(&mut buf).unwrap();
+
+        // limit too small for the record
+        let mut reader = &buf[4..];
+        assert!(matches!(
+            Zip64EndOfCentralDirectory::read_after_signature(&mut reader, 44),
+            Err(crate::Error::RecordTooLarge),
+        ));
+
+        // length field smaller than the fixed fields
+        let mut buf = Vec::new();
+        buf.write_u64_le(43).unwrap();
+        assert!(matches!(
+            Zip64EndOfCentralDirectory::read_after_signature(buf.as_slice(), u64::MAX),
+            Err(crate::Error::RecordTooSmall),
+        ));
+    }
+
+    #[test]
+    fn test_zip64_eocd_locator_round_trip() {
+        let locator = Zip64EndOfCentralDirectoryLocator {
+            disk_no: 0,
+            offset_on_disk: 0x1_2345_6789,
+            total_disks: 1,
+        };
+
+        let mut buf = Vec::new();
+        locator.write(&mut buf).unwrap();
+        assert_eq!(u64::try_from(buf.len()).unwrap(), Zip64EndOfCentralDirectoryLocator::min_len());
+
+        let mut reader = buf.as_slice();
+        assert_eq!(reader.read_u32_le().unwrap(), Zip64EndOfCentralDirectoryLocator::signature());
+        let read_locator = Zip64EndOfCentralDirectoryLocator::read_after_signature(&mut reader).unwrap();
+        assert_eq!(read_locator, locator);
+        assert_eq!(reader.len(), 0);
+    }
+
+    #[test]
+    fn test_central_directory_entry_round_trip() {
+        let entry = CentralDirectoryEntry {
+            creator_version: 0x031E,
+            required_version: 20,
+            general_purpose_bit_flag: 0x0808,
+            compression_method: 8,
+            last_mod_file_time: 0x6C21,
+            last_mod_file_date: 0x5A4F,
+            crc32: 0xCAFEBABE,
+            compressed_size: 1234,
+            uncompressed_size: 5678,
+            file_name: b"dir/file.txt".to_vec(),
+            extra_fields: vec![0x55, 0x54, 0x05, 0x00, 0x01, 0x78, 0x56, 0x34, 0x12],
+            file_comment: b"a comment".to_vec(),
+            disk_number_start: 0,
+            internal_attributes: 1,
+            external_attributes: 0o100755 << 16,
+            local_header_relative_offset: -1,
+        };
+
+        let mut buf = Vec::new();
+        entry.write(&mut buf).unwrap();
+        let expected_length = usize::try_from(CentralDirectoryEntry::min_len()).unwrap()
+            + entry.file_name.len() + entry.extra_fields.len() + entry.file_comment.len();
+        assert_eq!(buf.len(), expected_length);
+
+        let mut reader = buf.as_slice();
+        assert_eq!(reader.read_u32_le().unwrap(), CentralDirectoryEntry::signature());
+        let read_entry = CentralDirectoryEntry::read_after_signature(&mut reader).unwrap();
+        assert_eq!(read_entry, entry);
+        assert_eq!(reader.len(), 0);
+    }
+
+    #[test]
+    fn test_local_file_header_round_trip() {
+        let header = LocalFileHeader {
+            required_version: 45,
+            general_purpose_bit_flag: 0x0800,
+            compression_method: 8,
+            last_mod_file_time: 0x6C21,
+            last_mod_file_date: 0x5A4F,
+            crc32: 0xCAFEBABE,
+            compressed_size: u32::MAX,
+            uncompressed_size: u32::MAX,
+            file_name: b"big.bin".to_vec(),
+            extra_fields: vec![
+                0x01, 0x00, 0x10, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
+            ],
+        };
+
+        let mut buf = Vec::new();
+        header.write(&mut buf).unwrap();
+        assert_eq!(u64::try_from(buf.len()).unwrap(), header.record_len());
+
+        let mut reader = buf.as_slice();
+        assert_eq!(reader.read_u32_le().unwrap(), LocalFileHeader::signature());
+        let read_header = LocalFileHeader::read_after_signature(&mut reader).unwrap();
+        assert_eq!(read_header, header);
+        assert_eq!(reader.len(), 0);
+
+        assert_eq!(read_header.sizes(), (0x1_0000_0000, 0x2_0000_0000));
+    }
+
+    #[test]
+    fn test_data_descriptor_round_trip() {
+        for has_signature in [false, true] {
+            for zip64 in [false, true] {
+                let descriptor = DataDescriptor {
+                    has_signature,
+                    crc32: 0xCAFEBABE,
+                    compressed_size: 1234,
+                    uncompressed_size: 5678,
+                };
+
+                let mut buf = Vec::new();
+                descriptor.write(&mut buf, zip64).unwrap();
+                assert_eq!(u64::try_from(buf.len()).unwrap(), descriptor.record_len(zip64));
+
+                let mut reader = buf.as_slice();
+                let read_descriptor = DataDescriptor::read(&mut reader, zip64).unwrap();
+                assert_eq!(read_descriptor, descriptor);
+                assert_eq!(reader.len(), 0);
+            }
+        }
+
+        let descriptor = DataDescriptor {
+            compressed_size: 0x1_0000_0000,
+            ..Default::default()
+        };
+        let mut buf = Vec::new();
+        assert!(matches!(descriptor.write(&mut buf, false), Err(crate::Error::FieldTooLong)));
+    }
+
+    #[test]
+    fn test_zip64_extra_field_round_trip() {
+        let fields = [
+            Zip64ExtraField::default(),
+            Zip64ExtraField {
+                uncompressed_size: Some(0x1_0000_0000),
+                ..Default::default()
+            },
+            Zip64ExtraField {
+                uncompressed_size: Some(0x1_0000_0001),
+                compressed_size: Some(0x1_0000_0000),
+                ..Default::default()
+            },
+            Zip64ExtraField {
+                local_header_relative_offset: Some(0x3_0000_0000),
+                disk_number_start: Some(0x10000),
+                ..Default::default()
+            },
+            Zip64ExtraField {
+                uncompressed_size: Some(0x1_0000_0001),
+                compressed_size: Some(0x1_0000_0000),
+                local_header_relative_offset: Some(0x3_0000_0000),
+                disk_number_start: Some(0x10000),
+            },
+        ];
+
+        for field in fields {
+            let mut buf = Vec::new();
+            field.write(&mut buf).unwrap();
+
+            // the central directory entry saturates exactly those fields stored in the extra field
+            let mut reader = buf.as_slice();
+            assert_eq!(reader.read_u16_le().unwrap(), Zip64ExtraField::tag());
+            let read_field = Zip64ExtraField::read_after_tag(
+                &mut reader,
+                if field.uncompressed_size.is_some() { u32::MAX } else { 0 },
+                if field.compressed_size.is_some() { u32::MAX } else { 0 },
+                if field.local_header_relative_offset.is_some() { -1 } else { 0 },
+                if field.disk_number_start.is_some() { u16::MAX } else { 0 },
+            ).unwrap();
+            assert_eq!(read_field, field);
+            assert_eq!(reader.len(), 0);
+        }
+    }
+
+    #[test]
+    fn test_zip64_extra_field_lenient() {
+        // all three 64-bit values, although only the sizes are saturated
+        let mut data = Vec::new();
+        data.extend_from_slice(&0x1_0000_0001u64.to_le_bytes());
+        data.extend_from_slice(&0x1_0000_0000u64.to_le_bytes());
+        data.extend_from_slice(&0x1234u64.to_le_bytes());
+        assert!(matches!(
+            Zip64ExtraField::read_after_tag(with_length_prefix(&data).as_slice(), u32::MAX, u32::MAX, 0x1234, 0),
+            Err(crate::Error::UnexpectedExtraDataLength(24)),
+        ));
+        let field = Zip64ExtraField::from_data_lenient(&data, u32::MAX, u32::MAX, 0x1234, 0);
+        assert_eq!(field, Zip64ExtraField {
+            uncompressed_size: Some(0x1_0000_0001),
+            compressed_size: Some(0x1_0000_0000),
+            ..Default::default()
+        });
+
+        // too short for the offset, which remains unresolved
+        let field = Zip64ExtraField::from_data_lenient(&data[..12], u32::MAX, 0, -1, 0);
+        assert_eq!(field, Zip64ExtraField {
+            uncompressed_size: Some(0x1_0000_0001),
+            ..Default::default()
+        });
+    }
 }
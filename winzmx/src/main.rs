@@ -16,7 +16,7 @@ use std::ptr::null_mut;
 use std::sync::OnceLock;
 
 use libzmx::{
-    ZipCentralDirectoryEntry, best_effort_decode, zip_get_files, zip_make_executable,
+    ZipCentralDirectoryEntry, decode_entry_name, zip_get_files, zip_make_executable,
     zip_make_not_executable,
 };
 use windows::core::{PCWSTR, PWSTR, w};
@@ -308,7 +308,7 @@ fn handle_button_clicked(state: &mut State) {
     for index_u32 in selected_buf {
         let index: usize = index_u32.try_into().unwrap();
         let entry = &state.entries[index];
-        let file_name = best_effort_decode(&entry.entry.file_name);
+        let file_name = decode_entry_name(&entry.entry, None);
         if make_executable {
             if let Err(e) = zip_make_executable(&mut state.zip_file, entry.offset) {
                 let message = format!("failed to make {:?} ({}) executable:\r\n{}", file_name, entry.offset, e);
@@ -340,7 +340,7 @@ fn handle_button_clicked(state: &mut State) {
 fn populate_list_box_from_entries(state: &mut State) {
     for entry in &state.entries {
         let checkbox = if entry.is_executable() { CHECKBOX_TICKED } else { CHECKBOX_EMPTY };
-        let entry_name = best_effort_decode(&entry.entry.file_name);
+        let entry_name = decode_entry_name(&entry.entry, None);
         let entry_text = format!("{} {}", checkbox, entry_name);
         let entry_text_holder = StringHolder::from_str(&entry_text);
         unsafe { SendMessageW(state.list_box, LB_ADDSTRING, WPARAM(0), LPARAM(entry_text_holder.as_ptr() as isize)) };
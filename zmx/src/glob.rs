@@ -0,0 +1,341 @@
+//! Matching of paths against shell-style wildcard patterns.
+
+
+use std::fmt;
+use std::str::FromStr;
+
+
+/// An error that occurs when parsing a pattern.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) enum PatternParseError {
+    /// The pattern is empty.
+    Empty,
+
+    /// A character class (`[...]`) is not closed.
+    UnclosedCharacterClass,
+
+    /// The pattern ends with an escape character (`\`).
+    TrailingEscape,
+}
+impl fmt::Display for PatternParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty
+                => write!(f, "empty pattern"),
+            Self::UnclosedCharacterClass
+                => write!(f, "unclosed character class in pattern"),
+            Self::TrailingEscape
+                => write!(f, "pattern ends with an escape character"),
+        }
+    }
+}
+impl std::error::Error for PatternParseError {
+}
+
+
+/// A single element of a pattern.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+enum Token {
+    /// A character that matches itself.
+    Literal(char),
+
+    /// `?`: any single character except `/`.
+    AnyChar,
+
+    /// `*`: any sequence of characters not containing `/`.
+    AnyChars,
+
+    /// `**`: any sequence of characters, including `/`.
+    AnyPath,
+
+    /// `**/` at the start of the pattern or after a `/`: zero or more directories, i.e. the empty
+    /// sequence or any sequence of characters ending in `/`.
+    AnyDirectories,
+
+    /// `[...]`: any single character (except `/`) in (or, if negated, not in) the given ranges.
+    Class { negated: bool, ranges: Vec<(char, char)> },
+}
+
+
+/// A shell-style wildcard pattern, e.g. `*.pdb` or `__MACOSX/`.
+///
+/// The following wildcards are supported: `?` matches any single character, `*` matches any
+/// sequence of characters and `[...]` matches one of the given characters or ranges (negated by a
+/// leading `!` or `^`); none of these match `/`. `**` matches any sequence of characters including
+/// `/`; as a whole component, it also matches no directory at all, so that `**/foo` matches `foo`
+/// at any depth (including the root) and `a/**/b` matches `a/b`. A backslash matches the following
+/// character literally.
+///
+/// In the manner of `.gitignore`, a pattern that does not contain a slash (except at its end) is
+/// matched against each component of a path, while a pattern that does is anchored at the root. A
+/// path also matches if one of the directories containing it does. A pattern ending in a slash only
+/// matches directories.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) struct Pattern {
+    text: String,
+    tokens: Vec<Token>,
+    anchored: bool,
+    directory_only: bool,
+}
+impl Pattern {
+    /// Returns the pattern as it has been specified.
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// Returns whether the given path matches this pattern.
+    ///
+    /// Components of the path are separated by `/`; a leading or trailing slash is ignored.
+    /// `is_directory` specifies whether the path itself refers to a directory.
+    pub fn matches_path(&self, path: &str, is_directory: bool) -> bool {
+        let path = path.trim_start_matches('/').trim_end_matches('/');
+        let components: Vec<&str> = path.split('/').collect();
+
+        for (i, component) in components.iter().enumerate() {
+            // all but the last component are directories
+            let is_last = i == components.len() - 1;
+            if self.directory_only && is_last && !is_directory {
+                continue;
+            }
+
+            let candidate = if self.anchored {
+                let end = components[..=i].iter().map(|c| c.len() + 1).sum::<usize>() - 1;
+                &path[..end]
+            } else {
+                component
+            };
+            let candidate_chars: Vec<char> = candidate.chars().collect();
+            if match_tokens(&self.tokens, &candidate_chars) {
+                return true;
+            }
+        }
+        false
+    }
+}
+impl FromStr for Pattern {
+    type Err = PatternParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let directory_only = s.ends_with('/');
+        let trimmed = s.trim_end_matches('/');
+        let anchored = trimmed.contains('/');
+        let trimmed = trimmed.trim_start_matches('/');
+        if trimmed.is_empty() {
+            return Err(PatternParseError::Empty);
+        }
+
+        let mut tokens = Vec::new();
+        let mut chars = trimmed.chars().peekable();
+        while let Some(c) = chars.next() {
+            let token = match c {
+                '?' => Token::AnyChar,
+                '*' => {
+                    if chars.peek() == Some(&'*') {
+                        chars.next();
+                        let starts_component = matches!(tokens.last(), None|Some(Token::Literal('/')));
+                        if starts_component && chars.peek() == Some(&'/') {
+                            chars.next();
+                            Token::AnyDirectories
+                        } else {
+                            Token::AnyPath
+                        }
+                    } else {
+                        Token::AnyChars
+                    }
+                },
+                '\\' => match chars.next() {
+                    Some(escaped) => Token::Literal(escaped),
+                    None => return Err(PatternParseError::TrailingEscape),
+                },
+                '[' => {
+                    let negated = matches!(chars.peek(), Some('!'|'^'));
+                    if negated {
+                        chars.next();
+                    }
+                    let mut ranges = Vec::new();
+                    let mut closed = false;
+                    let mut first = true;
+                    while let Some(start) = chars.next() {
+                        // a closing bracket at the start of the class is taken literally
+                        if start == ']' && !first {
+                            closed = true;
+                            break;
+                        }
+                        first = false;
+                        let mut lookahead = chars.clone();
+                        let end = match (lookahead.next(), lookahead.next()) {
+                            (Some('-'), Some(e)) if e != ']' => {
+                                chars.next();
+                                chars.next();
+                                e
+                            },
+                            _ => start,
+                        };
+                        ranges.push((start, end));
+                    }
+                    if !closed {
+                        return Err(PatternParseError::UnclosedCharacterClass);
+                    }
+                    Token::Class { negated, ranges }
+                },
+                other => Token::Literal(other),
+            };
+            tokens.push(token);
+        }
+
+        Ok(Self {
+            text: s.to_owned(),
+            tokens,
+            anchored,
+            directory_only,
+        })
+    }
+}
+
+
+/// Returns whether the given tokens match the whole of the given text.
+///
+/// Instead of backtracking, which takes exponential time for patterns such as `*a*a*a*b`, this
+/// determines for each token (from the last to the first) at which positions of the text the
+/// remaining tokens match, taking O(tokens × text) time.
+fn match_tokens(tokens: &[Token], text: &[char]) -> bool {
+    // matches_after[j] is true if the tokens after the current one match text[j..]
+    let mut matches_after: Vec<bool> = (0..=text.len()).map(|j| j == text.len()).collect();
+    for token in tokens.iter().rev() {
+        let mut matches_from = vec![false; text.len() + 1];
+        let mut directories_end_found = false;
+        for j in (0..=text.len()).rev() {
+            let c = text.get(j);
+            let single_char_then_rest = |allowed: bool| allowed && matches_after[j + 1];
+            matches_from[j] = match token {
+                Token::Literal(l) => single_char_then_rest(c == Some(l)),
+                Token::AnyChar => single_char_then_rest(matches!(c, Some(c) if *c != '/')),
+                Token::Class { negated, ranges } => single_char_then_rest(match c {
+                    Some(c) if *c != '/' => {
+                        let in_ranges = ranges.iter().any(|(start, end)| start <= c && c <= end);
+                        in_ranges != *negated
+                    },
+                    _ => false,
+                }),
+                Token::AnyChars => {
+                    // either match nothing or one more character that is not a slash
+                    matches_after[j] || (matches!(c, Some(c) if *c != '/') && matches_from[j + 1])
+                },
+                Token::AnyPath => matches_after[j] || (c.is_some() && matches_from[j + 1]),
+                Token::AnyDirectories => {
+                    // either match nothing or everything up to and including some later slash
+                    if c == Some(&'/') && matches_after[j + 1] {
+                        directories_end_found = true;
+                    }
+                    matches_after[j] || directories_end_found
+                },
+            };
+        }
+        matches_after = matches_from;
+    }
+    matches_after[0]
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, path: &str, is_directory: bool) -> bool {
+        pattern.parse::<Pattern>().unwrap().matches_path(path, is_directory)
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!("".parse::<Pattern>(), Err(PatternParseError::Empty));
+        assert_eq!("/".parse::<Pattern>(), Err(PatternParseError::Empty));
+        assert_eq!("[abc".parse::<Pattern>(), Err(PatternParseError::UnclosedCharacterClass));
+        assert_eq!("abc\\".parse::<Pattern>(), Err(PatternParseError::TrailingEscape));
+    }
+
+    #[test]
+    fn test_wildcards() {
+        assert!(matches("*.pdb", "foo.pdb", false));
+        assert!(!matches("*.pdb", "foo.pdbx", false));
+        assert!(matches("?.txt", "a.txt", false));
+        assert!(!matches("?.txt", "ab.txt", false));
+        assert!(matches("file[0-9].txt", "file7.txt", false));
+        assert!(!matches("file[!0-9].txt", "file7.txt", false));
+        assert!(matches("file[^0-9].txt", "fileA.txt", false));
+        assert!(matches("[]]", "]", false));
+        assert!(matches("\\*.txt", "*.txt", false));
+        assert!(!matches("\\*.txt", "a.txt", false));
+
+        // single-component wildcards do not match slashes
+        assert!(!matches("a*b", "a/b", false));
+        assert!(!matches("a?b", "a/b", false));
+        assert!(!matches("a[/]b", "a/b", false));
+    }
+
+    #[test]
+    fn test_unanchored() {
+        // patterns without a slash match any component...
+        assert!(matches("*.pdb", "bin/debug/foo.pdb", false));
+        assert!(matches(".DS_Store", "a/b/.DS_Store", false));
+
+        // ...and paths within a matching directory
+        assert!(matches("build", "src/build/out.o", false));
+        assert!(!matches("build", "src/builder/out.o", false));
+    }
+
+    #[test]
+    fn test_anchored() {
+        assert!(matches("src/*.rs", "src/main.rs", false));
+        assert!(!matches("src/*.rs", "lib/src/main.rs", false));
+        assert!(!matches("src/*.rs", "src/bin/main.rs", false));
+        assert!(matches("/target", "target/debug/zmx", false));
+        assert!(!matches("/target", "zmx/target", false));
+        assert!(matches("src/**", "src/bin/main.rs", false));
+        assert!(!matches("src/**", "src", true));
+    }
+
+    #[test]
+    fn test_directory_only() {
+        assert!(matches("__MACOSX/", "__MACOSX", true));
+        assert!(!matches("__MACOSX/", "__MACOSX", false));
+        assert!(matches("__MACOSX/", "__MACOSX/._foo", false));
+        assert!(matches("__MACOSX/", "/a/__MACOSX/", true));
+    }
+
+    #[test]
+    fn test_any_directories() {
+        // a leading **/ matches at any depth, including the root
+        assert!(matches("**/foo", "foo", false));
+        assert!(matches("**/foo", "a/foo", false));
+        assert!(matches("**/foo", "a/b/foo", false));
+        assert!(matches("**/foo", "a/foo/bar", false));
+        assert!(!matches("**/foo", "a/xfoo", false));
+        assert!(matches("**/foo/bar", "foo/bar", false));
+        assert!(matches("**/foo/bar", "x/y/foo/bar", false));
+
+        // /**/ matches zero or more directories
+        assert!(matches("a/**/b", "a/b", false));
+        assert!(matches("a/**/b", "a/x/b", false));
+        assert!(matches("a/**/b", "a/x/y/b", false));
+        assert!(!matches("a/**/b", "a/xb", false));
+        assert!(!matches("a/**/b", "x/a/b", false));
+
+        // elsewhere, ** matches any sequence of characters
+        assert!(matches("x/a**b", "x/a/y/b", false));
+        assert!(matches("x/a**b", "x/ab", false));
+        assert!(matches("a**b", "x/ayb", false));
+    }
+
+    #[test]
+    fn test_many_wildcards() {
+        // would take exponential time with naive backtracking
+        let pattern: Pattern = "*a*a*a*a*a*a*a*a*a*a*a*a*b".parse().unwrap();
+        let text = "a".repeat(200);
+        assert!(!pattern.matches_path(&text, false));
+        assert!(pattern.matches_path(&format!("{}b", text), false));
+
+        let pattern: Pattern = "**a**a**a**a**a**a**a**a**a**a**b".parse().unwrap();
+        let text = "a/".repeat(100);
+        assert!(!pattern.matches_path(&text, false));
+    }
+}
@@ -1,80 +1,1100 @@
-use std::collections::{BTreeMap, HashMap};
-use std::fs::File;
-use std::path::PathBuf;
+mod glob;
+mod mode;
+
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
 
-use clap::Parser;
-use libzmx::{best_effort_decode, ZipCentralDirectoryEntry, zip_get_files, zip_make_executable};
+use clap::{ArgGroup, Parser, Subcommand};
+use libzmx::{
+    best_effort_decode, CodePage, CompressionMethod, decode_entry_name, EntryKind, ExtractOptions, MultiVolume,
+    NewEntryOptions, ReadOptions, UnixModeDefaults, ZipCentralDirectoryEntry, ZipWriter, zip_append, zip_extract, zip_check_disks, zip_prepend, zip_make_executable_with_defaults, zip_make_not_symlink, zip_make_symlink, zip_read_archive_disks,
+    zip_delete, zip_read_symlink_target, zip_rename, zip_set_unix_mode,
+};
+
+use crate::glob::Pattern;
+use crate::mode::{ModeSpec, parse_octal_mode};
 
 
 #[derive(Parser)]
+#[command(
+    subcommand_negates_reqs = true,
+    override_usage = "zmx [OPTIONS] <ZIP_PATH> [EXECUTABLE_FILES]...\n       zmx [OPTIONS] <COMMAND>",
+)]
 struct Opts {
+    /// The code page in which to decode entry names that are not marked as UTF-8 (e.g. "cp850").
+    ///
+    /// By default, such names are decoded using code page 437, as specified by the ZIP format.
+    #[arg(short, long, global = true)]
+    pub code_page: Option<CodePage>,
+
+    /// The mode (in octal) on which to base the new mode of files that do not have a Unix mode yet.
+    #[arg(long, global = true, default_value = "0644", value_parser = parse_octal_mode)]
+    pub base_file_mode: u16,
+
+    /// The mode (in octal) on which to base the new mode of directories that do not have a Unix
+    /// mode yet.
+    #[arg(long, global = true, default_value = "0755", value_parser = parse_octal_mode)]
+    pub base_dir_mode: u16,
+
+    /// Accept archives with more than 65535 entries whose entry count has wrapped around because
+    /// no Zip64 records have been written.
+    #[arg(long, global = true)]
+    pub lenient_entry_count: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// The path to the ZIP file to modify.
-    pub zip_path: PathBuf,
+    #[arg(required = true)]
+    pub zip_path: Option<PathBuf>,
 
     /// The names of the ZIP entries to make executable.
     pub executable_files: Vec<Vec<u8>>,
 }
 
+#[derive(Subcommand)]
+enum Command {
+    /// Sets the Unix mode of ZIP entries.
+    Chmod(ChmodOpts),
 
-fn main() -> ExitCode {
-    let opts = Opts::parse();
+    /// Turns uncompressed ZIP entries into symbolic links whose targets are their contents.
+    Symlink(EntriesOpts),
+
+    /// Turns symbolic link ZIP entries into regular files whose contents are their targets.
+    Unsymlink(EntriesOpts),
+
+    /// Checks the structural integrity of a ZIP file.
+    Check(CheckOpts),
+
+    /// Prepends a shebang line or a binary stub to a ZIP file, adjusting its offsets accordingly.
+    Prepend(PrependOpts),
+
+    /// Renames a ZIP entry, retaining its attributes.
+    Rename(RenameOpts),
+
+    /// Removes ZIP entries whose names match the given patterns.
+    Rm(RmOpts),
+
+    /// Adds files to a ZIP file, retaining the existing entries.
+    Add(AddOpts),
+
+    /// Creates a ZIP file from the contents of a directory, recording their Unix modes.
+    Create(CreateOpts),
+
+    /// Extracts the contents of a ZIP file, applying the Unix modes of its entries.
+    Extract(ExtractOpts),
+}
+
+#[derive(Parser)]
+struct ExtractOpts {
+    /// The permission bits (in octal) to remove from the modes of the extracted files and
+    /// directories.
+    #[arg(long, default_value = "0022", value_parser = parse_octal_mode)]
+    pub umask: u16,
+
+    /// Retain the setuid, setgid and sticky bits of the extracted files and directories.
+    #[arg(short = 'K', long)]
+    pub keep_special_bits: bool,
+
+    /// The path to the ZIP file to extract.
+    pub zip_path: PathBuf,
+
+    /// The directory into which to extract the ZIP file. By default, the current directory is used.
+    pub destination: Option<PathBuf>,
+}
+
+#[derive(Parser)]
+struct CreateOpts {
+    /// Only add files and directories whose paths (relative to the directory) match one of these
+    /// patterns, along with the directories containing them.
+    ///
+    /// The patterns are matched in the same manner as those of "zmx rm".
+    #[arg(short, long = "include", value_name = "PATTERN")]
+    pub includes: Vec<Pattern>,
+
+    /// Skip files and directories whose paths (relative to the directory) match one of these
+    /// patterns, including the contents of such directories.
+    #[arg(short = 'x', long = "exclude", value_name = "PATTERN")]
+    pub excludes: Vec<Pattern>,
+
+    /// Follow symbolic links, adding the files and directories they point to instead of the links
+    /// themselves.
+    #[arg(short = 'L', long)]
+    pub follow_symlinks: bool,
+
+    /// Record the numeric user and group IDs owning each file.
+    #[arg(long)]
+    pub owner: bool,
+
+    /// Record modification and access times in extended timestamp fields, which are more precise
+    /// than the MS-DOS timestamps (which are written in UTC).
+    #[arg(long)]
+    pub extended_timestamps: bool,
+
+    /// Store the files without compressing them.
+    #[arg(long)]
+    pub store: bool,
+
+    /// The path to the ZIP file to create. An existing file is replaced.
+    pub zip_path: PathBuf,
+
+    /// The directory whose contents to add. Its contents are placed at the root of the ZIP file.
+    pub directory: PathBuf,
+}
+
+#[derive(Parser)]
+struct AddOpts {
+    /// The mode (in octal) to give the new entries (e.g. "0755").
+    ///
+    /// By default, the mode of each file is used (on Unix) or 0644 (elsewhere).
+    #[arg(long, value_parser = parse_octal_mode)]
+    pub mode: Option<u16>,
+
+    /// Store the files without compressing them.
+    #[arg(long)]
+    pub store: bool,
+
+    /// The path to the ZIP file to modify.
+    pub zip_path: PathBuf,
+
+    /// The files to add, each optionally followed by a colon and the name of its ZIP entry (e.g.
+    /// "launch.sh:bin/launch").
+    ///
+    /// By default, the path of the file (relative, with "/" as the separator) is used as the name.
+    #[arg(required = true)]
+    pub files: Vec<String>,
+}
+
+#[derive(Parser)]
+struct RmOpts {
+    /// Also remove the data of the entries, moving the remaining data to reclaim the space.
+    #[arg(long)]
+    pub compact: bool,
+
+    /// The path to the ZIP file to modify.
+    pub zip_path: PathBuf,
+
+    /// The patterns matching the names of the ZIP entries to remove (e.g. "*.pdb" or "__MACOSX/").
+    ///
+    /// "*" and "?" match any characters except "/", "**" also matches "/" and "**/" also matches no
+    /// directory at all, so that "**/foo" matches "foo" at any depth. Patterns without a "/"
+    /// match any path component; a pattern ending in "/" only matches directories. Entries within a
+    /// matching directory are removed as well.
+    #[arg(required = true)]
+    pub patterns: Vec<Pattern>,
+}
+
+#[derive(Parser)]
+struct RenameOpts {
+    /// The path to the ZIP file to modify.
+    pub zip_path: PathBuf,
+
+    /// The current name of the ZIP entry.
+    // spelled out so that clap takes a single value instead of a list of bytes
+    #[arg(value_parser = clap::value_parser!(Vec<u8>))]
+    pub old_name: std::vec::Vec<u8>,
+
+    /// The new name of the ZIP entry.
+    pub new_name: String,
+}
+
+#[derive(Parser)]
+#[command(group(ArgGroup::new("stub_source").required(true).args(["shebang", "stub"])))]
+struct PrependOpts {
+    /// The interpreter line to prepend (e.g. "/usr/bin/env python3").
+    ///
+    /// The leading "#!" and the trailing newline are added if they are missing.
+    #[arg(long)]
+    pub shebang: Option<String>,
+
+    /// The path to a file whose contents to prepend (e.g. a self-extractor stub).
+    #[arg(long)]
+    pub stub: Option<PathBuf>,
+
+    /// Mark the resulting file as executable (Unix only).
+    #[arg(short = 'x', long)]
+    pub executable: bool,
+
+    /// The path at which to store the result. By default, the ZIP file is replaced.
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// The path to the ZIP file to which to prepend.
+    pub zip_path: PathBuf,
+}
+
+#[derive(Parser)]
+struct CheckOpts {
+    /// The path to the ZIP file to check.
+    pub zip_path: PathBuf,
+}
+
+#[derive(Parser)]
+struct EntriesOpts {
+    /// The path to the ZIP file to modify.
+    pub zip_path: PathBuf,
+
+    /// The names of the ZIP entries to modify.
+    #[arg(required = true)]
+    pub entries: Vec<Vec<u8>>,
+}
+
+#[derive(Parser)]
+struct ChmodOpts {
+    /// The path to the ZIP file to modify.
+    pub zip_path: PathBuf,
+
+    /// The mode to set, either in octal (e.g. "0644") or in symbolic form (e.g. "u+x,go-w").
+    pub mode: ModeSpec,
+
+    /// The names of the ZIP entries whose mode to set.
+    #[arg(required = true)]
+    pub entries: Vec<Vec<u8>>,
+}
+
+
+/// Opens the ZIP file at the given path along with its other volumes, if it has been split.
+fn open_zip_file(zip_path: &Path, write: bool) -> MultiVolume<File> {
+    let options = File::options()
+        .read(true)
+        .write(write)
+        .append(false)
+        .truncate(false)
+        .clone();
+    MultiVolume::open(zip_path, &options)
+        .expect("failed to open ZIP file")
+}
+
+
+/// Reads the list of entries from the central directory of the given ZIP file.
+fn get_entries(zip_file: &mut MultiVolume<File>, read_options: &ReadOptions) -> Vec<ZipCentralDirectoryEntry> {
+    let disk_offsets = zip_file.disk_offsets().to_vec();
+    zip_read_archive_disks(zip_file, &disk_offsets, read_options)
+        .expect("failed to get file list from ZIP file")
+        .entries
+}
+
+
+/// Looks up the entries with the given names, which may be raw or decoded.
+///
+/// Outputs an error message and returns `None` if any entry cannot be found.
+fn find_entries<'e>(
+    zip_path: &Path,
+    entries: &'e [ZipCentralDirectoryEntry],
+    names: &[Vec<u8>],
+    code_page: Option<CodePage>,
+) -> Option<Vec<&'e ZipCentralDirectoryEntry>> {
+    // entries can be specified using their raw or their decoded names
+    let decoded_names: Vec<Vec<u8>> = entries
+        .iter()
+        .map(|e| decode_entry_name(&e.entry, code_page).into_bytes())
+        .collect();
+    let mut name_to_entry: HashMap<&[u8], &ZipCentralDirectoryEntry> = entries
+        .iter()
+        .zip(decoded_names.iter())
+        .map(|(e, dn)| (dn.as_slice(), e))
+        .collect();
+    for entry in entries {
+        name_to_entry.insert(entry.entry.file_name.as_slice(), entry);
+    }
 
-    {
-        let mut zip_file = File::options()
-            .read(true)
-            .write(true)
-            .append(false)
-            .truncate(false)
-            .open(&opts.zip_path)
-            .expect("failed to open ZIP file");
-
-        // collect entry names
-        let entries = zip_get_files(&mut zip_file)
-            .expect("failed to get file list from ZIP file");
-
-        if opts.executable_files.len() == 0 {
-            // no file names specified; output current state of things
-            for cde in &entries {
-                let entry_name = best_effort_decode(&cde.entry.file_name);
-                println!("{} {}", if cde.is_executable() { 'x' } else { ' ' }, entry_name);
+    let mut bad = false;
+    let mut found = Vec::with_capacity(names.len());
+    for name in names {
+        match name_to_entry.get(name.as_slice()) {
+            Some(entry) => found.push(*entry),
+            None => {
+                let entry_name = best_effort_decode(name);
+                eprintln!("ZIP file {} does not contain entry {:?}", zip_path.display(), entry_name);
+                bad = true;
+            },
+        }
+    }
+    if bad {
+        None
+    } else {
+        Some(found)
+    }
+}
+
+
+/// Returns the character representing the given entry kind in listings, in the style of `ls -l`.
+fn kind_char(kind: EntryKind) -> char {
+    match kind {
+        EntryKind::File => '-',
+        EntryKind::Directory => 'd',
+        EntryKind::Symlink => 'l',
+        EntryKind::Other => 'o',
+        EntryKind::Unknown => '?',
+    }
+}
+
+
+fn list_or_make_executable(
+    zip_path: &Path,
+    executable_files: &[Vec<u8>],
+    code_page: Option<CodePage>,
+    mode_defaults: &UnixModeDefaults,
+    read_options: &ReadOptions,
+) -> ExitCode {
+    let mut zip_file = open_zip_file(zip_path, !executable_files.is_empty());
+
+    // collect entry names
+    let entries = get_entries(&mut zip_file, read_options);
+
+    if executable_files.len() == 0 {
+        // no file names specified; output current state of things
+        for cde in &entries {
+            let entry_name = decode_entry_name(&cde.entry, code_page);
+            let kind = cde.kind();
+            let exec_char = if cde.is_executable() { 'x' } else { ' ' };
+            if kind == EntryKind::Symlink {
+                let target = match zip_read_symlink_target(&mut zip_file, cde) {
+                    Ok(t) => best_effort_decode(&t),
+                    Err(e) => format!("<unreadable: {}>", e),
+                };
+                println!("{}{} {} -> {}", kind_char(kind), exec_char, entry_name, target);
+            } else {
+                println!("{}{} {}", kind_char(kind), exec_char, entry_name);
             }
-            return ExitCode::SUCCESS;
         }
+        return ExitCode::SUCCESS;
+    }
 
-        let name_to_entry: HashMap<&[u8], &ZipCentralDirectoryEntry> = entries
-            .iter()
-            .map(|e| (e.entry.file_name.as_slice(), e))
-            .collect();
+    let exec_entries = match find_entries(zip_path, &entries, executable_files, code_page) {
+        Some(ee) => ee,
+        None => return ExitCode::FAILURE,
+    };
 
-        let mut bad = false;
-        for exec_file in &opts.executable_files {
-            if !name_to_entry.contains_key(exec_file.as_slice()) {
-                let entry_name = best_effort_decode(exec_file);
-                eprintln!("ZIP file {} does not contain entry {:?}", opts.zip_path.display(), entry_name);
-                bad = true;
+    // only regular files can be made executable
+    let mut bad = false;
+    for entry in &exec_entries {
+        let kind = entry.kind();
+        if kind != EntryKind::File {
+            let entry_name = decode_entry_name(&entry.entry, code_page);
+            eprintln!("refusing to make {:?} executable: it is a {}", entry_name, kind);
+            bad = true;
+        }
+    }
+    if bad {
+        return ExitCode::FAILURE;
+    }
+
+    // make requested files executable
+    // store locations in BTree map to make sure we mostly seek forward
+    let mut exec_location_to_path: BTreeMap<u64, String> = BTreeMap::new();
+    for entry in exec_entries {
+        let entry_name = decode_entry_name(&entry.entry, code_page);
+        exec_location_to_path.insert(entry.offset, entry_name);
+    }
+
+    for (exec_location, path) in exec_location_to_path {
+        if let Err(e) = zip_make_executable_with_defaults(&mut zip_file, exec_location, mode_defaults) {
+            panic!("failed to make {:?} executable: {}", path, e);
+        }
+    }
+
+    ExitCode::SUCCESS
+}
+
+
+fn chmod(
+    opts: &ChmodOpts,
+    code_page: Option<CodePage>,
+    mode_defaults: &UnixModeDefaults,
+    read_options: &ReadOptions,
+) -> ExitCode {
+    let mut zip_file = open_zip_file(&opts.zip_path, true);
+
+    let entries = get_entries(&mut zip_file, read_options);
+    let chmod_entries = match find_entries(&opts.zip_path, &entries, &opts.entries, code_page) {
+        Some(ce) => ce,
+        None => return ExitCode::FAILURE,
+    };
+
+    // store locations in BTree map to make sure we mostly seek forward
+    let mut location_to_mode: BTreeMap<u64, (String, u16)> = BTreeMap::new();
+    for entry in chmod_entries {
+        let entry_name = decode_entry_name(&entry.entry, code_page);
+        let is_directory = entry.kind() == EntryKind::Directory;
+        let current_mode = entry.unix_mode_or_default(mode_defaults);
+        let new_mode = opts.mode.apply(current_mode, is_directory);
+        location_to_mode.insert(entry.offset, (entry_name, new_mode));
+    }
+
+    for (location, (path, mode)) in location_to_mode {
+        if let Err(e) = zip_set_unix_mode(&mut zip_file, location, mode) {
+            panic!("failed to set mode of {:?} to {:04o}: {}", path, mode, e);
+        }
+    }
+
+    ExitCode::SUCCESS
+}
+
+
+fn symlink(
+    opts: &EntriesOpts,
+    make_symlink: bool,
+    code_page: Option<CodePage>,
+    read_options: &ReadOptions,
+) -> ExitCode {
+    let mut zip_file = open_zip_file(&opts.zip_path, true);
+
+    let entries = get_entries(&mut zip_file, read_options);
+    let link_entries = match find_entries(&opts.zip_path, &entries, &opts.entries, code_page) {
+        Some(le) => le,
+        None => return ExitCode::FAILURE,
+    };
+
+    // store locations in BTree map to make sure we mostly seek forward
+    let location_to_path: BTreeMap<u64, String> = link_entries
+        .into_iter()
+        .map(|e| (e.offset, decode_entry_name(&e.entry, code_page)))
+        .collect();
+
+    let mut bad = false;
+    for (location, path) in location_to_path {
+        let result = if make_symlink {
+            zip_make_symlink(&mut zip_file, location)
+        } else {
+            zip_make_not_symlink(&mut zip_file, location)
+        };
+        if let Err(e) = result {
+            eprintln!(
+                "failed to make {:?} {}: {}",
+                path, if make_symlink { "a symbolic link" } else { "a regular file" }, e,
+            );
+            bad = true;
+        }
+    }
+
+    if bad {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+
+fn check(opts: &CheckOpts) -> ExitCode {
+    let mut zip_file = open_zip_file(&opts.zip_path, false);
+    let disk_offsets = zip_file.disk_offsets().to_vec();
+
+    let findings = match zip_check_disks(&mut zip_file, &disk_offsets) {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("failed to check ZIP file {}: {}", opts.zip_path.display(), e);
+            return ExitCode::FAILURE;
+        },
+    };
+    for finding in &findings {
+        println!("{}: {}", opts.zip_path.display(), finding);
+    }
+
+    if !findings.is_empty() {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+
+/// Returns the contents of a shebang line that invokes the given interpreter.
+fn shebang_line(interpreter: &str) -> Vec<u8> {
+    let mut line = String::new();
+    if !interpreter.starts_with("#!") {
+        line.push_str("#!");
+    }
+    line.push_str(interpreter);
+    if !interpreter.ends_with('\n') {
+        line.push('\n');
+    }
+    line.into_bytes()
+}
+
+
+/// Grants execute permission on the given file to everyone who may read it.
+#[cfg(unix)]
+fn make_file_executable(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut permissions = fs::metadata(path)?.permissions();
+    let mode = permissions.mode();
+    permissions.set_mode(mode | ((mode & 0o444) >> 2));
+    fs::set_permissions(path, permissions)
+}
+
+#[cfg(not(unix))]
+fn make_file_executable(_path: &Path) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "execute permissions are only supported on Unix"))
+}
+
+
+/// Returns the path of a hidden temporary file in the same directory as the given file, which can
+/// replace the latter by renaming.
+fn temp_path_next_to(path: &Path) -> PathBuf {
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(path.file_name().expect("path has no file name"));
+    temp_name.push(".zmx-tmp");
+    path.with_file_name(temp_name)
+}
+
+
+/// Returns whether both paths refer to the same existing file.
+fn is_same_file(one: &Path, other: &Path) -> bool {
+    match (fs::canonicalize(one), fs::canonicalize(other)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+
+fn prepend(opts: &PrependOpts, read_options: &ReadOptions) -> ExitCode {
+    let stub = match (&opts.shebang, &opts.stub) {
+        (Some(interpreter), _) => shebang_line(interpreter),
+        (None, Some(stub_path)) => fs::read(stub_path)
+            .expect("failed to read stub file"),
+        (None, None) => unreachable!("clap requires a stub source"),
+    };
+
+    let mut zip_file = File::open(&opts.zip_path)
+        .expect("failed to open ZIP file");
+
+    // write to a temporary file next to the output file and move it into place once complete; this
+    // also covers an output path naming the ZIP file itself, which must not be truncated early
+    let final_path = opts.output.as_ref().unwrap_or(&opts.zip_path);
+    let in_place = match &opts.output {
+        Some(op) => is_same_file(op, &opts.zip_path),
+        None => true,
+    };
+    let temp_path = temp_path_next_to(final_path);
+    let output_file = File::create(&temp_path)
+        .expect("failed to create output file");
+
+    if let Err(e) = zip_prepend(&mut zip_file, BufWriter::new(output_file), &stub, read_options) {
+        eprintln!("failed to prepend to {}: {}", opts.zip_path.display(), e);
+        let _ = fs::remove_file(&temp_path);
+        return ExitCode::FAILURE;
+    }
+
+    if in_place {
+        let permissions = zip_file.metadata()
+            .expect("failed to obtain ZIP file metadata")
+            .permissions();
+        fs::set_permissions(&temp_path, permissions)
+            .expect("failed to set permissions of output file");
+    }
+    fs::rename(&temp_path, final_path)
+        .expect("failed to move output file into place");
+
+    if opts.executable {
+        if let Err(e) = make_file_executable(final_path) {
+            eprintln!("failed to make {} executable: {}", final_path.display(), e);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    ExitCode::SUCCESS
+}
+
+
+fn rename(opts: &RenameOpts, code_page: Option<CodePage>, read_options: &ReadOptions) -> ExitCode {
+    let mut zip_file = open_zip_file(&opts.zip_path, true);
+    let disk_offsets = zip_file.disk_offsets().to_vec();
+    let mut archive = zip_read_archive_disks(&mut zip_file, &disk_offsets, read_options)
+        .expect("failed to get file list from ZIP file");
+
+    let index = {
+        let found = match find_entries(&opts.zip_path, &archive.entries, std::slice::from_ref(&opts.old_name), code_page) {
+            Some(f) => f,
+            None => return ExitCode::FAILURE,
+        };
+        archive.entries.iter()
+            .position(|e| std::ptr::eq(e, found[0]))
+            .unwrap()
+    };
+
+    let new_name_taken = archive.entries.iter()
+        .any(|e| e.entry.file_name == opts.new_name.as_bytes() || decode_entry_name(&e.entry, code_page) == opts.new_name);
+    if new_name_taken {
+        eprintln!("ZIP file {} already contains entry {:?}", opts.zip_path.display(), opts.new_name);
+        return ExitCode::FAILURE;
+    }
+
+    if let Err(e) = zip_rename(&mut zip_file, &mut archive, index, &opts.new_name) {
+        eprintln!("failed to rename {:?} to {:?}: {}", best_effort_decode(&opts.old_name), opts.new_name, e);
+        return ExitCode::FAILURE;
+    }
+
+    ExitCode::SUCCESS
+}
+
+
+fn rm(opts: &RmOpts, code_page: Option<CodePage>, read_options: &ReadOptions) -> ExitCode {
+    let mut zip_file = open_zip_file(&opts.zip_path, true);
+    let disk_offsets = zip_file.disk_offsets().to_vec();
+    let mut archive = zip_read_archive_disks(&mut zip_file, &disk_offsets, read_options)
+        .expect("failed to get file list from ZIP file");
+
+    // decide which entries to remove, ensuring that each pattern matches at least one of them
+    let mut pattern_matched = vec![false; opts.patterns.len()];
+    let mut remove_offsets = HashSet::new();
+    for entry in &archive.entries {
+        let entry_name = decode_entry_name(&entry.entry, code_page);
+        let is_directory = entry.kind() == EntryKind::Directory;
+        for (pattern, matched) in opts.patterns.iter().zip(pattern_matched.iter_mut()) {
+            if pattern.matches_path(&entry_name, is_directory) {
+                *matched = true;
+                remove_offsets.insert(entry.offset);
             }
         }
-        if bad {
+    }
+    let mut bad = false;
+    for (pattern, matched) in opts.patterns.iter().zip(pattern_matched.iter()) {
+        if !matched {
+            eprintln!("no entries of ZIP file {} match {:?}", opts.zip_path.display(), pattern.as_str());
+            bad = true;
+        }
+    }
+    if bad {
+        return ExitCode::FAILURE;
+    }
+
+    if let Err(e) = zip_delete(&mut zip_file, &mut archive, |e| remove_offsets.contains(&e.offset), opts.compact) {
+        eprintln!("failed to remove entries from {}: {}", opts.zip_path.display(), e);
+        return ExitCode::FAILURE;
+    }
+
+    ExitCode::SUCCESS
+}
+
+
+/// Returns the Unix mode of the file with the given metadata.
+#[cfg(unix)]
+fn file_unix_mode(metadata: &fs::Metadata) -> u16 {
+    use std::os::unix::fs::PermissionsExt;
+
+    (metadata.permissions().mode() & 0o7777).try_into().unwrap()
+}
+
+#[cfg(not(unix))]
+fn file_unix_mode(_metadata: &fs::Metadata) -> u16 {
+    0o644
+}
+
+
+/// Derives the name of a ZIP entry from the path of a file.
+///
+/// Returns `None` if the path leads out of the current directory.
+fn entry_name_from_path(path: &Path) -> Option<String> {
+    use std::path::Component;
+
+    let mut components = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Prefix(_)|Component::RootDir|Component::CurDir => {},
+            Component::ParentDir => return None,
+            Component::Normal(name) => components.push(name.to_string_lossy()),
+        }
+    }
+    if components.is_empty() {
+        return None;
+    }
+    Some(components.join("/"))
+}
+
+
+fn add(opts: &AddOpts, code_page: Option<CodePage>, read_options: &ReadOptions) -> ExitCode {
+    // work out the names of the new entries and ensure that they are unique
+    let mut new_files = Vec::with_capacity(opts.files.len());
+    for file_spec in &opts.files {
+        let (file_path, entry_name) = match file_spec.split_once(':') {
+            Some((path, name)) => (PathBuf::from(path), name.to_owned()),
+            None => {
+                let path = PathBuf::from(file_spec);
+                match entry_name_from_path(&path) {
+                    Some(name) => (path, name),
+                    None => {
+                        eprintln!("cannot derive an entry name from {:?}; specify one using FILE:NAME", file_spec);
+                        return ExitCode::FAILURE;
+                    },
+                }
+            },
+        };
+        new_files.push((file_path, entry_name));
+    }
+
+    let mut zip_file = open_zip_file(&opts.zip_path, true);
+    let disk_offsets = zip_file.disk_offsets().to_vec();
+    let mut archive = zip_read_archive_disks(&mut zip_file, &disk_offsets, read_options)
+        .expect("failed to get file list from ZIP file");
+
+    let mut taken_names: HashSet<String> = archive.entries.iter()
+        .map(|e| decode_entry_name(&e.entry, code_page))
+        .collect();
+    let mut bad = false;
+    for (_, entry_name) in &new_files {
+        if !taken_names.insert(entry_name.clone()) {
+            eprintln!("ZIP file {} already contains entry {:?}", opts.zip_path.display(), entry_name);
+            bad = true;
+        }
+    }
+    if bad {
+        return ExitCode::FAILURE;
+    }
+
+    let compression_method = if opts.store { CompressionMethod::Stored } else { CompressionMethod::Deflated };
+    for (file_path, entry_name) in &new_files {
+        let file = File::open(file_path)
+            .expect("failed to open file to add");
+        let metadata = file.metadata()
+            .expect("failed to obtain metadata of file to add");
+        if !metadata.is_file() {
+            eprintln!("{} is not a regular file", file_path.display());
             return ExitCode::FAILURE;
         }
+        let options = NewEntryOptions {
+            compression_method,
+            unix_mode: opts.mode.unwrap_or_else(|| file_unix_mode(&metadata)),
+            modified: metadata.modified().ok(),
+            size_hint: Some(metadata.len()),
+            ..NewEntryOptions::default()
+        };
 
-        // make requested files executable
-        // store locations in BTree map to make sure we mostly seek forward
-        let mut exec_location_to_path: BTreeMap<u64, String> = BTreeMap::new();
-        for exec_file in &opts.executable_files {
-            let entry = name_to_entry.get(exec_file.as_slice())
-                .expect("entry suddenly disappeared from central directory");
-            let entry_name = best_effort_decode(exec_file.as_slice());
-            exec_location_to_path.insert(entry.offset, entry_name);
+        if let Err(e) = zip_append(&mut zip_file, &mut archive, entry_name, io::BufReader::new(file), &options) {
+            eprintln!("failed to add {} to {}: {}", file_path.display(), opts.zip_path.display(), e);
+            return ExitCode::FAILURE;
         }
+    }
+
+    ExitCode::SUCCESS
+}
+
+
+/// Returns the numeric IDs of the user and group owning the file with the given metadata.
+#[cfg(unix)]
+fn file_owner(metadata: &fs::Metadata) -> Option<(u32, u32)> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some((metadata.uid(), metadata.gid()))
+}
+
+#[cfg(not(unix))]
+fn file_owner(_metadata: &fs::Metadata) -> Option<(u32, u32)> {
+    None
+}
+
+
+/// Returns the target of a symbolic link in the form in which it is stored in a ZIP entry.
+#[cfg(unix)]
+fn symlink_target_bytes(target: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+
+    target.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+fn symlink_target_bytes(target: &Path) -> Vec<u8> {
+    target.to_string_lossy().replace('\\', "/").into_bytes()
+}
+
+
+/// A file system item found within a directory tree.
+struct TreeItem {
+    /// The path of the item.
+    pub path: PathBuf,
+
+    /// The path of the item relative to the root of the tree, with "/" as the separator.
+    pub name: String,
+
+    /// The metadata of the item; that of the link itself if the item is a symbolic link that is
+    /// not being followed.
+    pub metadata: fs::Metadata,
+}
+
+
+/// Collects the items within the given directory recursively, depth-first and sorted by name.
+///
+/// Items whose names match one of the exclusion patterns are skipped along with their contents, as
+/// are the items whose canonical paths are among `skip_paths` (the canonical paths of the ZIP file
+/// being created and its temporary file), even if they are reached through a symbolic link. Special
+/// files are skipped with a warning. `ancestors` contains the canonical paths of the directories being traversed, which are
+/// used to detect loops when following symbolic links.
+fn collect_tree(
+    directory: &Path,
+    name_prefix: &str,
+    follow_symlinks: bool,
+    excludes: &[Pattern],
+    skip_paths: &[PathBuf],
+    ancestors: &mut Vec<PathBuf>,
+    items: &mut Vec<TreeItem>,
+) -> Result<(), String> {
+    let read_dir = fs::read_dir(directory)
+        .map_err(|e| format!("failed to read directory {}: {}", directory.display(), e))?;
+    let mut children = Vec::new();
+    for entry_res in read_dir {
+        let entry = entry_res
+            .map_err(|e| format!("failed to read directory {}: {}", directory.display(), e))?;
+        children.push(entry.path());
+    }
+    children.sort_unstable();
+
+    for path in children {
+        // a path that cannot be resolved (e.g. a dangling symbolic link) cannot lead to the ZIP file
+        let canonical_path = fs::canonicalize(&path)
+            .unwrap_or_else(|_| path.clone());
+        if skip_paths.contains(&canonical_path) {
+            continue;
+        }
+        let file_name = path.file_name().unwrap();
+        let name = match file_name.to_str() {
+            Some(n) => format!("{}{}", name_prefix, n),
+            None => return Err(format!("name of {} is not valid UTF-8", path.display())),
+        };
+
+        let metadata_res = if follow_symlinks {
+            fs::metadata(&path)
+        } else {
+            fs::symlink_metadata(&path)
+        };
+        let metadata = metadata_res
+            .map_err(|e| format!("failed to obtain metadata of {}: {}", path.display(), e))?;
+        let file_type = metadata.file_type();
+        if !file_type.is_file() && !file_type.is_dir() && !file_type.is_symlink() {
+            eprintln!("skipping special file {}", path.display());
+            continue;
+        }
+        if excludes.iter().any(|p| p.matches_path(&name, file_type.is_dir())) {
+            continue;
+        }
+
+        if file_type.is_dir() {
+            let canonical_path = fs::canonicalize(&path)
+                .map_err(|e| format!("failed to resolve {}: {}", path.display(), e))?;
+            if ancestors.contains(&canonical_path) {
+                return Err(format!("{} leads to a directory containing it", path.display()));
+            }
+            let child_prefix = format!("{}/", name);
+            items.push(TreeItem { path: path.clone(), name, metadata });
+
+            ancestors.push(canonical_path);
+            collect_tree(&path, &child_prefix, follow_symlinks, excludes, skip_paths, ancestors, items)?;
+            ancestors.pop();
+        } else {
+            items.push(TreeItem { path, name, metadata });
+        }
+    }
+    Ok(())
+}
 
-        for (exec_location, path) in exec_location_to_path {
-            if let Err(e) = zip_make_executable(&mut zip_file, exec_location) {
-                panic!("failed to make {:?} executable: {}", path, e);
+
+fn create(opts: &CreateOpts) -> ExitCode {
+    let root = match fs::canonicalize(&opts.directory) {
+        Ok(r) if r.is_dir() => r,
+        Ok(_) => {
+            eprintln!("{} is not a directory", opts.directory.display());
+            return ExitCode::FAILURE;
+        },
+        Err(e) => {
+            eprintln!("failed to open directory {}: {}", opts.directory.display(), e);
+            return ExitCode::FAILURE;
+        },
+    };
+
+    // the ZIP file is written to a temporary file, which replaces it only once complete; neither of
+    // them must end up in the archive if they are located within the directory
+    let temp_path = temp_path_next_to(&opts.zip_path);
+    let zip_directory = match opts.zip_path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    let canonical_zip_directory = fs::canonicalize(zip_directory)
+        .expect("failed to resolve directory of ZIP file");
+    let skip_paths = [
+        canonical_zip_directory.join(opts.zip_path.file_name().unwrap()),
+        canonical_zip_directory.join(temp_path.file_name().unwrap()),
+    ];
+
+    // walk the tree before touching any file so that errors leave an existing ZIP file untouched
+    let mut items = Vec::new();
+    let mut ancestors = vec![root.clone()];
+    let collected = collect_tree(
+        &root,
+        "",
+        opts.follow_symlinks,
+        &opts.excludes,
+        &skip_paths,
+        &mut ancestors,
+        &mut items,
+    );
+    if let Err(message) = collected {
+        eprintln!("{}", message);
+        return ExitCode::FAILURE;
+    }
+
+    // with inclusion patterns, keep the matching items and the directories leading to them
+    let mut included = vec![true; items.len()];
+    if !opts.includes.is_empty() {
+        let mut needed_directories = HashSet::new();
+        for (item, item_included) in items.iter().zip(included.iter_mut()) {
+            *item_included = opts.includes.iter()
+                .any(|p| p.matches_path(&item.name, item.metadata.is_dir()));
+            if *item_included {
+                for (slash_index, _) in item.name.match_indices('/') {
+                    needed_directories.insert(&item.name[..slash_index]);
+                }
+            }
+        }
+        for (item, item_included) in items.iter().zip(included.iter_mut()) {
+            if needed_directories.contains(item.name.as_str()) {
+                *item_included = true;
             }
         }
     }
 
+    let zip_file = File::create(&temp_path)
+        .expect("failed to create ZIP file");
+    let compression_method = if opts.store { CompressionMethod::Stored } else { CompressionMethod::Deflated };
+    let mut writer = ZipWriter::new(BufWriter::new(zip_file))
+        .expect("failed to start writing ZIP file");
+    for (item, _) in items.iter().zip(included.iter()).filter(|(_, inc)| **inc) {
+        let options = NewEntryOptions {
+            compression_method,
+            unix_mode: file_unix_mode(&item.metadata),
+            modified: item.metadata.modified().ok(),
+            accessed: item.metadata.accessed().ok(),
+            extended_timestamp: opts.extended_timestamps,
+            owner: if opts.owner { file_owner(&item.metadata) } else { None },
+            size_hint: Some(item.metadata.len()),
+        };
+
+        let file_type = item.metadata.file_type();
+        let result = if file_type.is_dir() {
+            writer.add_directory(&item.name, &options)
+        } else if file_type.is_symlink() {
+            fs::read_link(&item.path)
+                .map_err(libzmx::Error::from)
+                .and_then(|target| writer.add_symlink(&item.name, &symlink_target_bytes(&target), &options))
+        } else {
+            File::open(&item.path)
+                .map_err(libzmx::Error::from)
+                .and_then(|file| writer.add_file(&item.name, io::BufReader::new(file), &options))
+        };
+        if let Err(e) = result {
+            eprintln!("failed to add {} to {}: {}", item.path.display(), opts.zip_path.display(), e);
+            drop(writer);
+            let _ = fs::remove_file(&temp_path);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    let finished = writer.finish()
+        .and_then(|w| w.into_inner().map_err(|e| libzmx::Error::Io(e.into_error())));
+    if let Err(e) = finished {
+        eprintln!("failed to finish writing {}: {}", opts.zip_path.display(), e);
+        let _ = fs::remove_file(&temp_path);
+        return ExitCode::FAILURE;
+    }
+    fs::rename(&temp_path, &opts.zip_path)
+        .expect("failed to move ZIP file into place");
+
     ExitCode::SUCCESS
 }
+
+
+fn extract(opts: &ExtractOpts, code_page: Option<CodePage>, mode_defaults: &UnixModeDefaults, read_options: &ReadOptions) -> ExitCode {
+    let mut zip_file = open_zip_file(&opts.zip_path, false);
+    let disk_offsets = zip_file.disk_offsets().to_vec();
+    let archive = zip_read_archive_disks(&mut zip_file, &disk_offsets, read_options)
+        .expect("failed to get file list from ZIP file");
+
+    let destination = opts.destination.as_deref()
+        .unwrap_or(Path::new("."));
+    let extract_options = ExtractOptions {
+        umask: opts.umask,
+        keep_special_bits: opts.keep_special_bits,
+        mode_defaults: *mode_defaults,
+        code_page,
+    };
+    if let Err(e) = zip_extract(&mut zip_file, &archive, destination, &extract_options) {
+        eprintln!("failed to extract {} into {}: {}", opts.zip_path.display(), destination.display(), e);
+        return ExitCode::FAILURE;
+    }
+
+    ExitCode::SUCCESS
+}
+
+
+fn main() -> ExitCode {
+    let opts = Opts::parse();
+    let mode_defaults = UnixModeDefaults {
+        file_mode: opts.base_file_mode,
+        directory_mode: opts.base_dir_mode,
+    };
+    let read_options = ReadOptions {
+        lenient_entry_count: opts.lenient_entry_count,
+    };
+
+    match &opts.command {
+        Some(Command::Chmod(chmod_opts)) => chmod(chmod_opts, opts.code_page, &mode_defaults, &read_options),
+        Some(Command::Symlink(entries_opts)) => symlink(entries_opts, true, opts.code_page, &read_options),
+        Some(Command::Unsymlink(entries_opts)) => symlink(entries_opts, false, opts.code_page, &read_options),
+        Some(Command::Check(check_opts)) => check(check_opts),
+        Some(Command::Prepend(prepend_opts)) => prepend(prepend_opts, &read_options),
+        Some(Command::Rename(rename_opts)) => rename(rename_opts, opts.code_page, &read_options),
+        Some(Command::Rm(rm_opts)) => rm(rm_opts, opts.code_page, &read_options),
+        Some(Command::Add(add_opts)) => add(add_opts, opts.code_page, &read_options),
+        Some(Command::Create(create_opts)) => create(create_opts),
+        Some(Command::Extract(extract_opts)) => extract(extract_opts, opts.code_page, &mode_defaults, &read_options),
+        None => {
+            let zip_path = opts.zip_path.as_ref()
+                .expect("ZIP path is required");
+            list_or_make_executable(
+                zip_path,
+                &opts.executable_files,
+                opts.code_page,
+                &mode_defaults,
+                &read_options,
+            )
+        },
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_collect_tree_skips_zip_file_behind_symlink() {
+        let base = std::env::temp_dir().join(format!("zmx-collect-tree-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&base);
+        fs::create_dir_all(base.join("tree")).unwrap();
+        fs::create_dir_all(base.join("out")).unwrap();
+        let base = fs::canonicalize(&base).unwrap();
+        fs::write(base.join("tree/file.txt"), b"file").unwrap();
+        fs::write(base.join("out/archive.zip"), b"not yet an archive").unwrap();
+        fs::write(base.join("out/other.txt"), b"other").unwrap();
+        std::os::unix::fs::symlink("../out", base.join("tree/out")).unwrap();
+
+        let root = base.join("tree");
+        let skip_paths = [base.join("out/archive.zip")];
+        let collect = |follow_symlinks| {
+            let mut items = Vec::new();
+            let mut ancestors = vec![root.clone()];
+            collect_tree(&root, "", follow_symlinks, &[], &skip_paths, &mut ancestors, &mut items).unwrap();
+            items.into_iter()
+                .map(|i| i.name)
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(collect(true), ["file.txt", "out", "out/other.txt"]);
+        assert_eq!(collect(false), ["file.txt", "out"]);
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
@@ -0,0 +1,308 @@
+//! Parsing and application of Unix mode specifications in the style of `chmod`.
+
+
+use std::fmt;
+use std::str::FromStr;
+
+
+const WHO_USER: u16 = 0o4700;
+const WHO_GROUP: u16 = 0o2070;
+const WHO_OTHERS: u16 = 0o1007;
+const WHO_ALL: u16 = WHO_USER | WHO_GROUP | WHO_OTHERS;
+
+const PERM_READ: u16 = 0o0444;
+const PERM_WRITE: u16 = 0o0222;
+const PERM_EXECUTE: u16 = 0o0111;
+const PERM_SET_ID: u16 = 0o6000;
+const PERM_STICKY: u16 = 0o1000;
+
+
+/// An error that occurs when parsing a mode specification.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) enum ModeParseError {
+    /// The mode specification is empty.
+    Empty,
+
+    /// An octal mode specification has a value outside of 0o0000 to 0o7777.
+    OctalOutOfRange,
+
+    /// A symbolic mode specification contains an unexpected character.
+    UnexpectedCharacter(char),
+
+    /// A clause of a symbolic mode specification is missing an operator (`+`, `-` or `=`).
+    MissingOperator,
+}
+impl fmt::Display for ModeParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty
+                => write!(f, "empty mode"),
+            Self::OctalOutOfRange
+                => write!(f, "octal mode out of range (maximum is 7777)"),
+            Self::UnexpectedCharacter(c)
+                => write!(f, "unexpected character {:?} in mode", c),
+            Self::MissingOperator
+                => write!(f, "missing operator (+, - or =) in symbolic mode"),
+        }
+    }
+}
+impl std::error::Error for ModeParseError {
+}
+
+
+/// Parses an octal mode specification such as `0644`.
+pub(crate) fn parse_octal_mode(s: &str) -> Result<u16, ModeParseError> {
+    if s.is_empty() {
+        return Err(ModeParseError::Empty);
+    }
+    if let Some(c) = s.chars().find(|c| !('0'..='7').contains(c)) {
+        return Err(ModeParseError::UnexpectedCharacter(c));
+    }
+    let mode = u16::from_str_radix(s, 8)
+        .map_err(|_| ModeParseError::OctalOutOfRange)?;
+    if mode > 0o7777 {
+        return Err(ModeParseError::OctalOutOfRange);
+    }
+    Ok(mode)
+}
+
+
+/// The operator of a symbolic mode action.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) enum Operator {
+    Add,
+    Remove,
+    Set,
+}
+
+/// A symbolic mode action, e.g. `+x`.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) struct Action {
+    operator: Operator,
+    permissions: u16,
+    conditional_execute: bool,
+}
+
+/// A symbolic mode clause, e.g. `go-w+r`.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) struct Clause {
+    who: u16,
+    actions: Vec<Action>,
+}
+
+
+/// A mode specification, either absolute (`0644`) or symbolic (`u+x,go-w`).
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) enum ModeSpec {
+    Absolute(u16),
+    Symbolic(Vec<Clause>),
+}
+impl ModeSpec {
+    /// Applies this mode specification to the given current mode, returning the new mode.
+    ///
+    /// Only the permission, setuid, setgid and sticky bits (0o7777) are considered.
+    pub fn apply(&self, current_mode: u16, is_directory: bool) -> u16 {
+        let clauses = match self {
+            Self::Absolute(mode) => return *mode,
+            Self::Symbolic(c) => c,
+        };
+
+        let mut mode = current_mode & 0o7777;
+        for clause in clauses {
+            for action in &clause.actions {
+                let mut permissions = action.permissions;
+                // like chmod, X refers to the execute bits as modified by the preceding actions
+                if action.conditional_execute && (is_directory || mode & PERM_EXECUTE != 0) {
+                    permissions |= PERM_EXECUTE;
+                }
+                let affected = permissions & clause.who;
+                match action.operator {
+                    Operator::Add => mode |= affected,
+                    Operator::Remove => mode &= !affected,
+                    Operator::Set => {
+                        // setuid/setgid bits of directories are retained unless explicitly named
+                        let mut cleared = clause.who & !PERM_SET_ID & !PERM_STICKY;
+                        if !is_directory {
+                            cleared |= clause.who & PERM_SET_ID;
+                        }
+                        if clause.who & WHO_OTHERS == WHO_OTHERS {
+                            cleared |= PERM_STICKY;
+                        }
+                        mode = (mode & !cleared) | affected;
+                    },
+                }
+            }
+        }
+        mode
+    }
+
+    fn parse_clause(clause_str: &str) -> Result<Clause, ModeParseError> {
+        let mut chars = clause_str.chars().peekable();
+
+        let mut who = 0;
+        while let Some(c) = chars.peek() {
+            match c {
+                'u' => who |= WHO_USER,
+                'g' => who |= WHO_GROUP,
+                'o' => who |= WHO_OTHERS,
+                'a' => who |= WHO_ALL,
+                _ => break,
+            }
+            chars.next();
+        }
+        if who == 0 {
+            who = WHO_ALL;
+        }
+
+        let mut actions = Vec::new();
+        while let Some(c) = chars.next() {
+            let operator = match c {
+                '+' => Operator::Add,
+                '-' => Operator::Remove,
+                '=' => Operator::Set,
+                other => return Err(ModeParseError::UnexpectedCharacter(other)),
+            };
+
+            let mut permissions = 0;
+            let mut conditional_execute = false;
+            while let Some(p) = chars.peek() {
+                match p {
+                    'r' => permissions |= PERM_READ,
+                    'w' => permissions |= PERM_WRITE,
+                    'x' => permissions |= PERM_EXECUTE,
+                    'X' => conditional_execute = true,
+                    's' => permissions |= PERM_SET_ID,
+                    't' => permissions |= PERM_STICKY,
+                    '+'|'-'|'=' => break,
+                    other => return Err(ModeParseError::UnexpectedCharacter(*other)),
+                }
+                chars.next();
+            }
+
+            actions.push(Action {
+                operator,
+                permissions,
+                conditional_execute,
+            });
+        }
+        if actions.is_empty() {
+            return Err(ModeParseError::MissingOperator);
+        }
+
+        Ok(Clause {
+            who,
+            actions,
+        })
+    }
+}
+impl FromStr for ModeSpec {
+    type Err = ModeParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err(ModeParseError::Empty);
+        }
+
+        if s.chars().all(|c| c.is_ascii_digit()) {
+            return parse_octal_mode(s).map(Self::Absolute);
+        }
+
+        let clauses = s.split(',')
+            .map(Self::parse_clause)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::Symbolic(clauses))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(spec: &str, current_mode: u16, is_directory: bool) -> u16 {
+        spec.parse::<ModeSpec>().unwrap().apply(current_mode, is_directory)
+    }
+
+    #[test]
+    fn test_parse_octal() {
+        assert_eq!(parse_octal_mode("644"), Ok(0o644));
+        assert_eq!(parse_octal_mode("04755"), Ok(0o4755));
+        assert_eq!(parse_octal_mode("7777"), Ok(0o7777));
+        assert_eq!(parse_octal_mode("10000"), Err(ModeParseError::OctalOutOfRange));
+        assert_eq!(parse_octal_mode("0o644"), Err(ModeParseError::UnexpectedCharacter('o')));
+        assert_eq!(parse_octal_mode("648"), Err(ModeParseError::UnexpectedCharacter('8')));
+        assert_eq!(parse_octal_mode(""), Err(ModeParseError::Empty));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!("".parse::<ModeSpec>(), Err(ModeParseError::Empty));
+        assert_eq!("u".parse::<ModeSpec>(), Err(ModeParseError::MissingOperator));
+        assert_eq!("u+x,".parse::<ModeSpec>(), Err(ModeParseError::MissingOperator));
+        assert_eq!("u+y".parse::<ModeSpec>(), Err(ModeParseError::UnexpectedCharacter('y')));
+        assert_eq!("k+x".parse::<ModeSpec>(), Err(ModeParseError::UnexpectedCharacter('k')));
+        assert_eq!("9".parse::<ModeSpec>(), Err(ModeParseError::UnexpectedCharacter('9')));
+    }
+
+    #[test]
+    fn test_apply_absolute() {
+        assert_eq!(apply("0755", 0o4644, false), 0o755);
+        assert_eq!(apply("600", 0o777, true), 0o600);
+    }
+
+    #[test]
+    fn test_apply_symbolic() {
+        assert_eq!(apply("u+x", 0o644, false), 0o744);
+        assert_eq!(apply("+x", 0o644, false), 0o755);
+        assert_eq!(apply("go-w", 0o666, false), 0o644);
+        assert_eq!(apply("a=r", 0o755, false), 0o444);
+        assert_eq!(apply("ug=rw,o=", 0o755, false), 0o660);
+        assert_eq!(apply("u=rwx,g=rx,o=r", 0o000, false), 0o754);
+        assert_eq!(apply("g-w+x", 0o664, false), 0o654);
+        assert_eq!(apply("o=", 0o777, false), 0o770);
+    }
+
+    #[test]
+    fn test_apply_conditional_execute() {
+        // files only gain execute permission if some execute bit is already set
+        assert_eq!(apply("a+X", 0o644, false), 0o644);
+        assert_eq!(apply("a+X", 0o744, false), 0o755);
+        assert_eq!(apply("a+X", 0o600, true), 0o711);
+
+        // the mode as modified by the preceding actions and clauses is decisive
+        assert_eq!(apply("a-x+X", 0o744, false), 0o644);
+        assert_eq!(apply("a+x-x+X", 0o644, false), 0o644);
+        assert_eq!(apply("u+x,go+X", 0o644, false), 0o755);
+        assert_eq!(apply("a-x+X", 0o755, true), 0o755);
+    }
+
+    #[test]
+    fn test_apply_special_bits() {
+        assert_eq!(apply("u+s", 0o755, false), 0o4755);
+        assert_eq!(apply("g+s", 0o755, true), 0o2755);
+        assert_eq!(apply("+s", 0o755, false), 0o6755);
+        assert_eq!(apply("+t", 0o777, true), 0o1777);
+        assert_eq!(apply("o+t", 0o777, true), 0o1777);
+        assert_eq!(apply("u+t", 0o777, true), 0o777);
+        assert_eq!(apply("ug-s", 0o6755, false), 0o755);
+        assert_eq!(apply("-t", 0o1777, true), 0o777);
+    }
+
+    #[test]
+    fn test_apply_set_special_bits() {
+        // = clears the setuid and setgid bits of the named classes for files...
+        assert_eq!(apply("u=rwx", 0o4755, false), 0o755);
+        assert_eq!(apply("g=rx", 0o6755, false), 0o4755);
+        assert_eq!(apply("a=rx", 0o6755, false), 0o555);
+
+        // ...but retains those of directories unless they are named explicitly
+        assert_eq!(apply("u=rwx", 0o4755, true), 0o4755);
+        assert_eq!(apply("a=rx", 0o6755, true), 0o6555);
+        assert_eq!(apply("g=rxs", 0o755, true), 0o2755);
+
+        // the sticky bit is only cleared if the others class is named
+        assert_eq!(apply("u=rwx", 0o1777, true), 0o1777);
+        assert_eq!(apply("o=rx", 0o1777, true), 0o775);
+        assert_eq!(apply("a=rwxt", 0o777, true), 0o1777);
+    }
+}